@@ -1,15 +1,30 @@
 use crate::config::{
-    calculate_font_size, APP_ICON, DEFAULT_FONT_SIZE, DEFAULT_ZOOM_LEVEL, MAX_ZOOM_LEVEL,
-    MIN_ZOOM_LEVEL,
+    calculate_font_size, APP_ICON, DEFAULT_FONT_SIZE, DEFAULT_TAB_WIDTH, DEFAULT_WRAP_COLUMN,
+    DEFAULT_ZOOM_LEVEL, HISTORY_SNAPSHOT_INTERVAL_SECS, MAX_ZOOM_LEVEL, MIN_ZOOM_LEVEL,
+    SEARCH_HISTORY_MAX_ENTRIES, TAB_WIDTHS, WRAP_COLUMNS,
 };
-use crate::editor::SyntaxHighlighter;
-use crate::theme::{apply_egui_style, ThemeMode};
+use crate::editor::{
+    bracket_near, byte_offset, byte_ranges, caret_key, caret_lines, char_ranges, detect_indent,
+    diff_hunks, edit_line_blocks, edits_pairs, fold_regions, hidden_ranges, hide_ranges, in_ranges,
+    indents, line_at, line_ranges, line_spans, line_start_char, lines_of, minimap_lines,
+    paint_backgrounds, parse_goto, reflow_paragraphs, remap_lines, remap_range, retain_lines,
+    splice_lines, split_lines, toggle_block_comment, toggle_line_comment, transform_lines,
+    transform_selections, widen_tabs, wrap_typed_lines, Caret, CaretCommand, CaretContext,
+    CaretInput, CaretKey, ChangeKind, DiffPane, DiffView, DiffViewAction, FoldRegion, GotoTarget,
+    Hunk, IndentStyle, LineEdit, LineTransform, MinimapLine, MultiCursor, SearchOptions, Searcher,
+    SyntaxHighlighter, TextTransform, BRACKETS, FOLD_PLACEHOLDER,
+};
+use crate::history::{LocalHistory, Snapshot, SnapshotKind};
+use crate::theme::{apply_egui_style, ThemeColors, ThemeMode};
+use crate::vcs::{blame, file_log, head_content, show_revision, BlameLine, FileCommit};
 use chrono::Local;
 use eframe::egui;
 use egui::{ColorImage, FontId, RichText, TextureHandle};
 use rfd::FileDialog;
-use std::path::PathBuf;
-use std::sync::mpsc::{self, Receiver};
+use std::collections::BTreeSet;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
 pub enum PreviewLayout {
@@ -33,6 +48,33 @@ impl PreviewLayout {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum LineNumbers {
+    Hidden,
+    #[default]
+    Absolute,
+    /// Distance from the caret line, which shows its own number.
+    Relative,
+}
+
+impl LineNumbers {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LineNumbers::Hidden => "Off",
+            LineNumbers::Absolute => "Absolute",
+            LineNumbers::Relative => "Relative",
+        }
+    }
+
+    /// The number shown next to zero-based `line` when the caret is on `current`.
+    fn number(&self, line: usize, current: usize) -> usize {
+        match self {
+            LineNumbers::Relative if line != current => line.abs_diff(current),
+            _ => line + 1,
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
 pub enum EditorTab {
     #[default]
@@ -42,18 +84,225 @@ pub enum EditorTab {
 
 #[derive(Clone, Debug)]
 struct SearchResult {
-    index: usize,
+    /// Byte range in `text`.
+    range: Range<usize>,
+    /// Char range in `text`, as used by the editor's cursor.
+    chars: Range<usize>,
+    /// Zero-based lines of the first and last char of the match.
+    lines: Range<usize>,
     flash_timer: f32,
 }
 
+#[derive(Clone, Copy, Debug)]
+struct GitPopup {
+    hunk: usize,
+    pos: egui::Pos2,
+    opened: u64,
+}
+
+const GIT_MARKER_WIDTH: f32 = 4.0;
+const GIT_GUTTER_WIDTH: f32 = 10.0;
+const GIT_DIFF_DELAY: f32 = 0.2;
+const BLAME_DELAY: f32 = 1.0;
+const MARKER_STRIP_WIDTH: f32 = 8.0;
+const MARKER_HEIGHT: f32 = 2.0;
+/// Space around the text inside the editor's frame.
+const EDITOR_MARGIN: egui::Margin = egui::Margin::symmetric(4.0, 2.0);
+const MINIMAP_WIDTH: f32 = 100.0;
+const MINIMAP_LINE_HEIGHT: f32 = 2.0;
+const MINIMAP_CHAR_WIDTH: f32 = 1.0;
+/// Seconds between minimap rebuilds while the text keeps changing.
+const MINIMAP_DELAY: f64 = 0.3;
+/// Longest selection whose other occurrences are marked on the scrollbar.
+const MAX_OCCURRENCE_LEN: usize = 200;
+
+/// Other occurrences of the selected text, cached until the selection or text changes.
+struct Occurrences {
+    needle: String,
+    text: String,
+    ranges: Vec<Range<usize>>,
+    chars: Vec<Range<usize>>,
+}
+
+/// The minimap runs of the buffer, cached until it or its highlighting changes.
+struct MinimapCache {
+    revision: u64,
+    /// When the runs were built, in egui's input time.
+    built: f64,
+    syntax: Option<String>,
+    dark: bool,
+    tab_width: usize,
+    lines: Vec<MinimapLine>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum MarkerTarget {
+    Search(usize),
+    Occurrence(usize),
+    Line(usize),
+}
+
+const SEARCH_HISTORY_KEY: &str = "search_history";
+const REPLACE_HISTORY_KEY: &str = "replace_history";
+/// Opening and closing chars typed together.
+type Pairs = Vec<(char, char)>;
+
+const INDENT_SPACES_KEY: &str = "indent_spaces";
+const TAB_WIDTH_KEY: &str = "tab_width";
+const DETECT_INDENT_KEY: &str = "detect_indent";
+const WRAP_COLUMN_KEY: &str = "wrap_column";
+const WRAP_AS_YOU_TYPE_KEY: &str = "wrap_as_you_type";
+const LINE_NUMBERS_KEY: &str = "line_numbers";
+const HIGHLIGHT_LINE_KEY: &str = "highlight_line";
+const HIGHLIGHT_COLUMN_KEY: &str = "highlight_column";
+const MINIMAP_KEY: &str = "minimap";
+/// Folded header lines per file, one `lines<TAB>path` entry per line.
+const FOLDS_KEY: &str = "folds";
+const MAX_FOLDED_FILES: usize = 100;
+
+/// The background of each search match in the text. The current match fades from the
+/// flash color while its timer runs out.
+fn match_highlights(
+    results: &[SearchResult],
+    current: usize,
+    colors: &ThemeColors,
+) -> Vec<(Range<usize>, egui::Color32)> {
+    results
+        .iter()
+        .enumerate()
+        .map(|(i, result)| {
+            let color = if i == current {
+                colors
+                    .search_current_bg
+                    .lerp_to_gamma(colors.search_flash_bg, result.flash_timer.clamp(0.0, 1.0))
+            } else {
+                colors.search_match_bg
+            };
+            (result.range.clone(), color)
+        })
+        .collect()
+}
+
+/// Removes the first paste event, leaving any later ones for the editor.
+fn take_paste(events: &mut Vec<egui::Event>) -> Option<String> {
+    let at = events
+        .iter()
+        .position(|e| matches!(e, egui::Event::Paste(_)))?;
+    match events.remove(at) {
+        egui::Event::Paste(text) => Some(text),
+        _ => None,
+    }
+}
+
+/// "Replaced 3 occurrences", for Replace All.
+fn replaced_message(count: usize) -> String {
+    let noun = if count == 1 {
+        "occurrence"
+    } else {
+        "occurrences"
+    };
+    format!("Replaced {} {}", count, noun)
+}
+
+/// Moves `entry` to the front of `history`, dropping the oldest entries past the limit.
+fn remember(history: &mut Vec<String>, entry: &str) {
+    if entry.is_empty() || entry.contains('\n') {
+        return;
+    }
+    history.retain(|h| h != entry);
+    history.insert(0, entry.to_string());
+    history.truncate(SEARCH_HISTORY_MAX_ENTRIES);
+}
+
+/// Steps through `history` into `field`: up goes to older entries, down to newer ones and
+/// finally back to an empty field.
+fn browse_history(history: &[String], pos: &mut Option<usize>, field: &mut String, up: bool) {
+    let next = match (*pos, up) {
+        (None, true) if !history.is_empty() => Some(0),
+        (Some(p), true) => Some((p + 1).min(history.len().saturating_sub(1))),
+        (Some(p), false) => p.checked_sub(1),
+        (None, _) => return,
+    };
+    *pos = next;
+    *field = next
+        .and_then(|p| history.get(p))
+        .cloned()
+        .unwrap_or_default();
+}
+
+/// Lines of context shown above and below each match in the Find All panel.
+const FIND_ALL_CONTEXT_LINES: usize = 1;
+
+/// One caret per galley row between the points `from` and `to`, spanning their x range.
+fn column_carets(
+    galley: &egui::Galley,
+    origin: egui::Pos2,
+    from: egui::Pos2,
+    to: egui::Pos2,
+) -> MultiCursor {
+    let (from, to) = (from - origin, to - origin);
+    let caret_at = |y: f32| {
+        Caret::new(
+            galley.cursor_from_pos(egui::vec2(from.x, y)).ccursor.index,
+            galley.cursor_from_pos(egui::vec2(to.x, y)).ccursor.index,
+        )
+    };
+    let (top, bottom) = (from.y.min(to.y), from.y.max(to.y));
+    let carets = galley
+        .rows
+        .iter()
+        .filter(|r| r.rect.bottom() >= top && r.rect.top() <= bottom)
+        .map(|r| caret_at(r.rect.center().y))
+        .collect();
+    MultiCursor::new(carets, caret_at(to.y))
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum LineCommand {
+    Edit(LineEdit),
+    Split,
+    Transform(LineTransform),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum FoldCommand {
+    Fold,
+    Unfold,
+    FoldAll,
+    UnfoldAll,
+    /// Folds every region nested at least this deep.
+    Level(usize),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum PanelDock {
+    Bottom,
+    Right,
+}
+
+struct ScrollMarker {
+    y: egui::Rangef,
+    color: egui::Color32,
+    target: MarkerTarget,
+}
+
+struct RevisionView {
+    title: String,
+    text: String,
+    syntax: Option<String>,
+}
+
 #[derive(Clone, Debug)]
 enum FdAct {
     Open,
     SaveAs(String),
+    Compare,
 }
 
 pub struct Notepad {
     text: String,
+    /// Bumped on every change to `text`, for caches to key on instead of a copy of it.
+    revision: u64,
     undo_stack: Vec<String>,
     redo_stack: Vec<String>,
     last_saved: String,
@@ -63,11 +312,21 @@ pub struct Notepad {
     font_size: f32,
     zoom: i16,
     status_bar: bool,
+    line_numbers: LineNumbers,
+    /// Line where a click-drag on the line numbers started.
+    line_drag_anchor: Option<usize>,
+    highlight_line: bool,
+    highlight_column: bool,
+    minimap: bool,
+    minimap_cache: Option<MinimapCache>,
+    /// Distance from the top of the minimap viewport to where it was grabbed.
+    minimap_grab: Option<f32>,
     about: bool,
     confirm: bool,
     confirm_act: ConfirmAct,
     cur_line: usize,
     cur_col: usize,
+    selection: Option<std::ops::Range<usize>>,
     fd_rx: Option<Receiver<Option<PathBuf>>>,
     fd_act: Option<FdAct>,
     icon: Option<TextureHandle>,
@@ -89,6 +348,8 @@ pub struct Notepad {
     markdown_cache: egui_commonmark::CommonMarkCache,
     editor_scroll_offset: f32,
     editor_scroll_max: f32,
+    /// Char index to bring into view once the editor galley is laid out.
+    editor_scroll_to: Option<usize>,
     preview_scroll_offset: f32,
     preview_scroll_max: f32,
     syncing_scroll: bool,
@@ -100,13 +361,98 @@ pub struct Notepad {
     search_focus: bool,
     search_select_all: bool,
     search_input_has_focus: bool,
-    search_cursor_pos: Option<usize>,
+    /// Char range to select in the editor on its next frame.
+    pending_selection: Option<Range<usize>>,
+    search_options: SearchOptions,
+    search_error: Option<String>,
+    replace_query: String,
+    replace_input_has_focus: bool,
+    /// Byte range of `text` the search is limited to.
+    search_scope: Option<Range<usize>>,
+    /// The text `search_scope` refers to, to follow it through edits.
+    search_scope_text: String,
+    /// Replacements made by the last Replace All, until the next edit or search.
+    replace_count: Option<usize>,
+    search_history: Vec<String>,
+    replace_history: Vec<String>,
+    search_history_pos: Option<usize>,
+    replace_history_pos: Option<usize>,
 
     notification_text: String,
     notification_timer: f32,
 
     pending_large_file: Option<PathBuf>,
     large_file_confirm: bool,
+
+    diff_view: Option<DiffView>,
+    /// Selected text waiting for the clipboard to compare with, and the pass that
+    /// requested it.
+    clipboard_request: Option<(String, u64)>,
+
+    local_history: Option<LocalHistory>,
+    history_panel: bool,
+    history_entries: Vec<Snapshot>,
+    history_timer: f32,
+
+    git_base: Option<String>,
+    /// The file `git_base` was read for.
+    git_base_file: Option<PathBuf>,
+    git_base_rx: Option<Receiver<(PathBuf, Option<String>)>>,
+    git_hunks: Vec<Hunk>,
+    git_diff_text: Option<String>,
+    git_diff_timer: f32,
+    git_popup: Option<GitPopup>,
+    window_focused: bool,
+
+    show_blame: bool,
+    blame: Vec<BlameLine>,
+    blame_text: Option<String>,
+    blame_timer: f32,
+    /// A running blame, with the text it blames.
+    blame_rx: Option<Receiver<(String, Option<Vec<BlameLine>>)>>,
+    file_history_panel: bool,
+    file_commits: Vec<FileCommit>,
+    selected_commit: Option<usize>,
+    revision_view: Option<RevisionView>,
+
+    occurrences: Option<Occurrences>,
+
+    find_all_panel: bool,
+    find_all_dock: PanelDock,
+
+    goto_line: bool,
+    goto_input: String,
+    goto_focus: bool,
+
+    /// The extra carets while the editor has several, and the input edited through them.
+    caret_input: CaretInput,
+
+    /// Indentation used for new files and when detection is off or finds nothing.
+    default_indent: IndentStyle,
+    /// Indentation of the current file.
+    indent: IndentStyle,
+    detect_indent: bool,
+
+    /// Column that paragraphs are reflowed to.
+    wrap_column: usize,
+    /// Breaks the line being typed once it passes `wrap_column`.
+    wrap_as_you_type: bool,
+
+    /// Header lines of the folded regions, counted in `fold_source`.
+    folds: BTreeSet<usize>,
+    fold_source: String,
+    /// The revision of the text `fold_source` holds.
+    fold_revision: u64,
+    /// Fold regions for the revision, syntax and tab width they were computed from.
+    fold_cache: Option<(u64, Option<String>, usize, Vec<FoldRegion>)>,
+    /// Folded header lines of recently saved files, newest first.
+    saved_folds: Vec<(PathBuf, Vec<usize>)>,
+
+    /// Char ranges inside strings and comments, for the revision and syntax they were
+    /// computed from.
+    literal_cache: Option<(u64, Option<String>, Vec<Range<usize>>)>,
+    /// Auto-closed pairs of the syntax they were computed for.
+    pairs_cache: Option<(Option<String>, Pairs)>,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -147,6 +493,7 @@ impl Default for Notepad {
             .collect();
         Self {
             text: String::new(),
+            revision: 0,
             undo_stack: Vec::new(),
             redo_stack: Vec::new(),
             last_saved: String::new(),
@@ -156,11 +503,19 @@ impl Default for Notepad {
             font_size: DEFAULT_FONT_SIZE,
             zoom: DEFAULT_ZOOM_LEVEL,
             status_bar: true,
+            line_numbers: LineNumbers::default(),
+            line_drag_anchor: None,
+            highlight_line: true,
+            highlight_column: false,
+            minimap: true,
+            minimap_cache: None,
+            minimap_grab: None,
             about: false,
             confirm: false,
             confirm_act: ConfirmAct::None,
             cur_line: 1,
             cur_col: 1,
+            selection: None,
             fd_rx: None,
             fd_act: None,
             icon: None,
@@ -179,6 +534,7 @@ impl Default for Notepad {
             markdown_cache: egui_commonmark::CommonMarkCache::default(),
             editor_scroll_offset: 0.0,
             editor_scroll_max: 0.0,
+            editor_scroll_to: None,
             preview_scroll_offset: 0.0,
             preview_scroll_max: 0.0,
             syncing_scroll: false,
@@ -189,11 +545,70 @@ impl Default for Notepad {
             search_focus: false,
             search_select_all: false,
             search_input_has_focus: false,
-            search_cursor_pos: None,
+            pending_selection: None,
+            search_options: SearchOptions::default(),
+            search_error: None,
+            replace_query: String::new(),
+            replace_input_has_focus: false,
+            search_scope: None,
+            search_scope_text: String::new(),
+            replace_count: None,
+            search_history: Vec::new(),
+            replace_history: Vec::new(),
+            search_history_pos: None,
+            replace_history_pos: None,
             notification_text: String::new(),
             notification_timer: 0.0,
             pending_large_file: None,
             large_file_confirm: false,
+            diff_view: None,
+            clipboard_request: None,
+            local_history: LocalHistory::open_default(),
+            history_panel: false,
+            history_entries: Vec::new(),
+            history_timer: 0.0,
+            git_base: None,
+            git_base_file: None,
+            git_base_rx: None,
+            git_hunks: Vec::new(),
+            git_diff_text: None,
+            git_diff_timer: 0.0,
+            git_popup: None,
+            window_focused: true,
+            show_blame: false,
+            blame: Vec::new(),
+            blame_text: None,
+            blame_rx: None,
+            blame_timer: 0.0,
+            file_history_panel: false,
+            file_commits: Vec::new(),
+            selected_commit: None,
+            revision_view: None,
+            occurrences: None,
+            find_all_panel: false,
+            find_all_dock: PanelDock::Bottom,
+            goto_line: false,
+            goto_input: String::new(),
+            goto_focus: false,
+            caret_input: CaretInput::default(),
+            default_indent: IndentStyle {
+                spaces: true,
+                width: DEFAULT_TAB_WIDTH,
+            },
+            indent: IndentStyle {
+                spaces: true,
+                width: DEFAULT_TAB_WIDTH,
+            },
+            detect_indent: true,
+            wrap_column: DEFAULT_WRAP_COLUMN,
+            wrap_as_you_type: false,
+            folds: BTreeSet::new(),
+            fold_source: String::new(),
+            fold_revision: 0,
+            fold_cache: None,
+            saved_folds: Vec::new(),
+            literal_cache: None,
+            pairs_cache: None,
         }
     }
 }
@@ -214,6 +629,69 @@ impl Notepad {
                 Default::default(),
             ));
         }
+        if let Some(storage) = cc.storage {
+            let load = |key| {
+                storage
+                    .get_string(key)
+                    .map(|s| s.lines().map(str::to_string).collect())
+                    .unwrap_or_default()
+            };
+            app.search_history = load(SEARCH_HISTORY_KEY);
+            app.replace_history = load(REPLACE_HISTORY_KEY);
+            if let Some(spaces) = storage.get_string(INDENT_SPACES_KEY) {
+                app.default_indent.spaces = spaces == "true";
+            }
+            if let Some(width) = storage
+                .get_string(TAB_WIDTH_KEY)
+                .and_then(|w| w.parse().ok())
+                .filter(|w| TAB_WIDTHS.contains(w))
+            {
+                app.default_indent.width = width;
+            }
+            if let Some(detect) = storage.get_string(DETECT_INDENT_KEY) {
+                app.detect_indent = detect == "true";
+            }
+            if let Some(column) = storage
+                .get_string(WRAP_COLUMN_KEY)
+                .and_then(|c| c.parse().ok())
+                .filter(|c| WRAP_COLUMNS.contains(c))
+            {
+                app.wrap_column = column;
+            }
+            if let Some(wrap) = storage.get_string(WRAP_AS_YOU_TYPE_KEY) {
+                app.wrap_as_you_type = wrap == "true";
+            }
+            if let Some(numbers) = storage.get_string(LINE_NUMBERS_KEY) {
+                app.line_numbers = [
+                    LineNumbers::Hidden,
+                    LineNumbers::Absolute,
+                    LineNumbers::Relative,
+                ]
+                .into_iter()
+                .find(|n| n.name() == numbers)
+                .unwrap_or_default();
+            }
+            if let Some(highlight) = storage.get_string(HIGHLIGHT_LINE_KEY) {
+                app.highlight_line = highlight == "true";
+            }
+            if let Some(highlight) = storage.get_string(HIGHLIGHT_COLUMN_KEY) {
+                app.highlight_column = highlight == "true";
+            }
+            if let Some(minimap) = storage.get_string(MINIMAP_KEY) {
+                app.minimap = minimap == "true";
+            }
+            app.saved_folds = storage
+                .get_string(FOLDS_KEY)
+                .unwrap_or_default()
+                .lines()
+                .filter_map(|entry| {
+                    let (lines, path) = entry.split_once('\t')?;
+                    let lines = lines.split(',').filter_map(|l| l.parse().ok()).collect();
+                    Some((PathBuf::from(path), lines))
+                })
+                .collect();
+            app.indent = app.default_indent;
+        }
         app.system_dark = cc.egui_ctx.style().visuals.dark_mode;
         app.apply_theme(&cc.egui_ctx);
         app
@@ -293,26 +771,60 @@ impl Notepad {
     }
 
     fn perform_search(&mut self) {
+        self.find_matches();
+        self.current_search_idx = 0;
+        if !self.search_results.is_empty() {
+            self.search_results[0].flash_timer = 1.0;
+            self.scroll_to_search_result();
+        }
+    }
+
+    /// Re-runs the search after the text changed, keeping the current match index.
+    fn refresh_search(&mut self) {
+        self.replace_count = None;
+        if !self.search_active && !self.find_all_panel {
+            return;
+        }
+        self.find_matches();
+        self.current_search_idx = self
+            .current_search_idx
+            .min(self.search_results.len().saturating_sub(1));
+    }
+
+    fn find_matches(&mut self) {
         self.search_results.clear();
+        self.replace_count = None;
+        self.search_error = None;
         if self.search_query.is_empty() {
-            self.current_search_idx = 0;
             return;
         }
-        let query = self.search_query.to_lowercase();
-        let text_lower = self.text.to_lowercase();
-        let mut start = 0;
-        while let Some(pos) = text_lower[start..].find(&query) {
-            let abs_pos = start + pos;
-            self.search_results.push(SearchResult {
-                index: abs_pos,
-                flash_timer: 0.0,
-            });
-            start = abs_pos + 1;
+        if let Some(scope) = self.search_scope.clone() {
+            if self.search_scope_text != self.text {
+                self.search_scope = remap_range(&self.search_scope_text, &self.text, scope);
+                self.search_scope_text = self.text.clone();
+            }
         }
-        self.current_search_idx = 0;
-        if !self.search_results.is_empty() {
-            self.search_results[0].flash_timer = 1.0;
-            self.scroll_to_search_result();
+        match Searcher::new(&self.search_query, self.search_options) {
+            Ok(searcher) => {
+                let mut ranges = searcher.find_all(&self.text);
+                if let Some(scope) = &self.search_scope {
+                    ranges.retain(|r| r.start >= scope.start && r.end <= scope.end);
+                }
+                let firsts = lines_of(&self.text, ranges.iter().map(|r| r.start));
+                let lasts = lines_of(&self.text, ranges.iter().map(|r| r.end - 1));
+                self.search_results = char_ranges(&self.text, &ranges)
+                    .into_iter()
+                    .zip(ranges)
+                    .zip(firsts.into_iter().zip(lasts))
+                    .map(|((chars, range), (first, last))| SearchResult {
+                        range,
+                        chars,
+                        lines: first..last,
+                        flash_timer: 0.0,
+                    })
+                    .collect();
+            }
+            Err(e) => self.search_error = Some(e),
         }
     }
 
@@ -320,11 +832,15 @@ impl Notepad {
         if self.search_results.is_empty() {
             return;
         }
-        let result = &self.search_results[self.current_search_idx];
-        let line_count = self.text[..result.index].matches('\n').count();
-        let line_height = self.font_size * 1.5;
-        self.editor_scroll_offset = (line_count as f32 * line_height).max(0.0);
-        self.syncing_scroll = true;
+        self.editor_scroll_to = Some(self.search_results[self.current_search_idx].chars.start);
+    }
+
+    fn search_highlights(&self) -> Vec<(Range<usize>, egui::Color32)> {
+        if !self.search_active {
+            return Vec::new();
+        }
+        let colors = ThemeColors::for_mode(self.theme_mode, self.system_dark);
+        match_highlights(&self.search_results, self.current_search_idx, &colors)
     }
 
     fn flash_current_result(&mut self) {
@@ -356,6 +872,332 @@ impl Notepad {
         self.flash_current_result();
     }
 
+    fn replace_current(&mut self) {
+        let Some(range) = self
+            .search_results
+            .get(self.current_search_idx)
+            .map(|r| r.range.clone())
+        else {
+            return;
+        };
+        let Ok(searcher) = Searcher::new(&self.search_query, self.search_options) else {
+            return;
+        };
+        self.remember_replace();
+        let replacement = searcher.replacement(&self.text, range.clone(), &self.replace_query);
+        self.text.replace_range(range.clone(), &replacement);
+        self.reset_carets();
+        self.save_undo();
+        self.modified = true;
+
+        let resume = range.start + replacement.len();
+        self.perform_search();
+        if let Some(i) = self
+            .search_results
+            .iter()
+            .position(|r| r.range.start >= resume)
+        {
+            self.current_search_idx = i;
+            self.scroll_to_search_result();
+            self.flash_current_result();
+        }
+    }
+
+    fn replace_all(&mut self, in_selection: bool) {
+        let Ok(searcher) = Searcher::new(&self.search_query, self.search_options) else {
+            return;
+        };
+        self.remember_replace();
+        let scope = match (in_selection, self.selection.clone()) {
+            (false, _) => self.search_scope.clone().unwrap_or(0..self.text.len()),
+            (true, Some(sel)) => {
+                byte_offset(&self.text, sel.start)..byte_offset(&self.text, sel.end)
+            }
+            (true, None) => return,
+        };
+        let (text, count) = searcher.replace_all(&self.text, scope.clone(), &self.replace_query);
+        if count > 0 {
+            if in_selection {
+                let end = scope.end + text.len() - self.text.len();
+                let start = self.text[..scope.start].chars().count();
+                let sel = start..start + text[scope.start..end].chars().count();
+                self.selection = Some(sel.clone());
+                self.pending_selection = Some(sel);
+            }
+            self.text = text;
+            self.reset_carets();
+            self.save_undo();
+            self.modified = true;
+            self.perform_search();
+        }
+        self.replace_count = Some(count);
+        self.show_notification(&replaced_message(count));
+    }
+
+    /// Zero-based lines touched by any search result, in ascending order.
+    fn matching_lines(&self) -> Vec<usize> {
+        let mut lines: Vec<usize> = Vec::new();
+        for result in &self.search_results {
+            let from = lines
+                .last()
+                .map_or(result.lines.start, |&l| result.lines.start.max(l + 1));
+            lines.extend(from..=result.lines.end);
+        }
+        lines
+    }
+
+    fn copy_matching_lines(&mut self, ctx: &egui::Context) {
+        let lines = line_ranges(&self.text);
+        let matching = self.matching_lines();
+        let text: Vec<&str> = matching
+            .iter()
+            .filter_map(|&l| lines.get(l).map(|r| &self.text[r.clone()]))
+            .collect();
+        ctx.copy_text(text.join("\n"));
+        self.show_notification(&format!("Copied {} line(s)", text.len()));
+    }
+
+    /// Deletes the lines containing matches, or with `keep` deletes all other lines.
+    fn filter_matching_lines(&mut self, keep: bool) {
+        let matching = self.matching_lines();
+        if matching.is_empty() {
+            return;
+        }
+        let mut next = matching.iter().peekable();
+        let text = retain_lines(&self.text, |line| {
+            while next.next_if(|&&l| l < line).is_some() {}
+            (next.peek() == Some(&&line)) == keep
+        });
+        let removed = self.text.lines().count() - text.lines().count();
+        self.text = text;
+        self.reset_carets();
+        self.save_undo();
+        self.modified = true;
+        self.refresh_search();
+        self.show_notification(&format!("Removed {} line(s)", removed));
+    }
+
+    fn find_all_results_panel(&mut self, ctx: &egui::Context, dark: bool) {
+        let mut close = false;
+        let mut jump = None;
+        let mut copy = false;
+        let mut filter = None;
+        let colors = ThemeColors::for_mode(self.theme_mode, self.system_dark);
+        let text_color = if dark {
+            egui::Color32::from_rgb(0xd4, 0xd4, 0xd4)
+        } else {
+            egui::Color32::from_rgb(0x33, 0x33, 0x33)
+        };
+
+        let current_dock = self.find_all_dock;
+        let mut dock = current_dock;
+        let mut contents = |ui: &mut egui::Ui| {
+            ui.horizontal(|ui| {
+                if ui.button("X").clicked() {
+                    close = true;
+                }
+                ui.label(RichText::new("Find All").strong());
+                ui.label(format!(
+                    "{} match(es) for \"{}\"",
+                    self.search_results.len(),
+                    self.search_query
+                ));
+                ui.separator();
+                let has_results = !self.search_results.is_empty();
+                if ui
+                    .add_enabled(has_results, egui::Button::new("Copy Lines"))
+                    .on_hover_text("Copy all matching lines")
+                    .clicked()
+                {
+                    copy = true;
+                }
+                if ui
+                    .add_enabled(has_results, egui::Button::new("Delete Lines"))
+                    .on_hover_text("Delete all matching lines")
+                    .clicked()
+                {
+                    filter = Some(false);
+                }
+                if ui
+                    .add_enabled(has_results, egui::Button::new("Keep Lines"))
+                    .on_hover_text("Delete all lines that do not match")
+                    .clicked()
+                {
+                    filter = Some(true);
+                }
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    let (icon, hint, other) = match current_dock {
+                        PanelDock::Bottom => ("➡", "Dock Right", PanelDock::Right),
+                        PanelDock::Right => ("⬇", "Dock Bottom", PanelDock::Bottom),
+                    };
+                    if ui.button(icon).on_hover_text(hint).clicked() {
+                        dock = other;
+                    }
+                });
+            });
+            ui.separator();
+
+            let lines = line_ranges(&self.text);
+            let font = FontId::monospace(12.0);
+            let row_height = ui.fonts(|f| f.row_height(&font));
+            let spacing = ui.spacing().item_spacing.y;
+            let per_entry = 1 + 2 * FIND_ALL_CONTEXT_LINES;
+            let entry_height = per_entry as f32 * row_height + (per_entry - 1) as f32 * spacing;
+            let number_width = lines.len().to_string().len();
+            let line_text = |line: usize| lines.get(line).map_or("", |r| &self.text[r.clone()]);
+
+            egui::ScrollArea::both()
+                .id_salt("find_all_list")
+                .auto_shrink([false, false])
+                .show_rows(ui, entry_height, self.search_results.len(), |ui, rows| {
+                    for i in rows {
+                        let result = &self.search_results[i];
+                        let line = result.lines.start;
+                        let entry = ui.vertical(|ui| {
+                            let first = line.saturating_sub(FIND_ALL_CONTEXT_LINES);
+                            for context in first..line + FIND_ALL_CONTEXT_LINES + 1 {
+                                let mut job = egui::text::LayoutJob::default();
+                                let number = if context < lines.len() {
+                                    format!("{:>w$}  ", context + 1, w = number_width)
+                                } else {
+                                    String::new()
+                                };
+                                let dim = egui::TextFormat::simple(font.clone(), colors.dim_text);
+                                job.append(&number, 0.0, dim.clone());
+                                let content = line_text(context);
+                                if context == line {
+                                    let start = lines[line].start;
+                                    let from = (result.range.start - start).min(content.len());
+                                    let to = (result.range.end - start).min(content.len());
+                                    let normal = egui::TextFormat::simple(font.clone(), text_color);
+                                    let mut emph = normal.clone();
+                                    emph.background = colors.search_match_bg;
+                                    job.append(&content[..from], 0.0, normal.clone());
+                                    job.append(&content[from..to], 0.0, emph);
+                                    job.append(&content[to..], 0.0, normal);
+                                } else {
+                                    job.append(content, 0.0, dim);
+                                }
+                                ui.add(egui::Label::new(job).extend());
+                            }
+                            if line < FIND_ALL_CONTEXT_LINES {
+                                for _ in line..FIND_ALL_CONTEXT_LINES {
+                                    ui.add_space(row_height + spacing);
+                                }
+                            }
+                        });
+                        let rect = entry.response.rect;
+                        let response =
+                            ui.interact(rect, egui::Id::new(("find_all", i)), egui::Sense::click());
+                        if i == self.current_search_idx {
+                            ui.painter().rect_filled(rect, 2.0, colors.current_line_bg);
+                        }
+                        if response.hovered() {
+                            ui.painter().rect_filled(rect, 2.0, colors.current_line_bg);
+                        }
+                        if response.clicked() {
+                            jump = Some(i);
+                        }
+                    }
+                });
+        };
+
+        match current_dock {
+            PanelDock::Bottom => {
+                egui::TopBottomPanel::bottom("find_all_panel")
+                    .resizable(true)
+                    .default_height(200.0)
+                    .show(ctx, |ui| contents(ui));
+            }
+            PanelDock::Right => {
+                egui::SidePanel::right("find_all_panel_right")
+                    .resizable(true)
+                    .default_width(360.0)
+                    .show(ctx, |ui| contents(ui));
+            }
+        }
+
+        self.find_all_dock = dock;
+        if close {
+            self.find_all_panel = false;
+        }
+        if copy {
+            self.copy_matching_lines(ctx);
+        }
+        if let Some(keep) = filter {
+            self.filter_matching_lines(keep);
+        }
+        if let Some(i) = jump {
+            self.current_search_idx = i;
+            self.scroll_to_search_result();
+            self.flash_current_result();
+            self.pending_selection = Some(self.search_results[i].chars.clone());
+            self.editor_focus_request = true;
+        }
+    }
+
+    fn toggle_search_scope(&mut self) {
+        self.search_scope = match (&self.search_scope, &self.selection) {
+            (None, Some(sel)) => {
+                Some(byte_offset(&self.text, sel.start)..byte_offset(&self.text, sel.end))
+            }
+            _ => None,
+        };
+        self.search_scope_text = self.text.clone();
+        self.perform_search();
+    }
+
+    /// Up/Down in the search bar: browse the focused field's history while it is empty
+    /// (or already browsing), otherwise step through the results.
+    fn search_arrow(&mut self, up: bool) {
+        if self.replace_input_has_focus {
+            if self.replace_query.is_empty() || self.replace_history_pos.is_some() {
+                browse_history(
+                    &self.replace_history,
+                    &mut self.replace_history_pos,
+                    &mut self.replace_query,
+                    up,
+                );
+            }
+        } else if self.search_query.is_empty() || self.search_history_pos.is_some() {
+            browse_history(
+                &self.search_history,
+                &mut self.search_history_pos,
+                &mut self.search_query,
+                up,
+            );
+            self.perform_search();
+        } else if up {
+            self.prev_search_result();
+        } else {
+            self.next_search_result();
+        }
+    }
+
+    fn remember_search(&mut self) {
+        remember(&mut self.search_history, &self.search_query);
+        self.search_history_pos = None;
+    }
+
+    fn remember_replace(&mut self) {
+        self.remember_search();
+        remember(&mut self.replace_history, &self.replace_query);
+        self.replace_history_pos = None;
+    }
+
+    fn close_search(&mut self) {
+        self.remember_search();
+        if let Some(result) = self.search_results.get(self.current_search_idx) {
+            self.pending_selection = Some(result.chars.clone());
+        }
+        self.search_active = false;
+        if self.active_tab == EditorTab::Editor {
+            self.editor_focus_request = true;
+        } else {
+            self.preview_focus_request = true;
+        }
+    }
+
     fn show_notification(&mut self, text: &str) {
         self.notification_text = text.to_string();
         self.notification_timer = 3.0;
@@ -386,6 +1228,8 @@ impl Notepad {
     }
 
     fn save_undo(&mut self) {
+        self.revision += 1;
+        self.replace_count = None;
         if self.text != self.last_saved {
             self.undo_stack.push(self.last_saved.clone());
             if self.undo_stack.len() > 100 {
@@ -396,12 +1240,21 @@ impl Notepad {
         }
     }
 
+    /// Drops the extra carets and the column block, which point into the text being
+    /// replaced.
+    fn reset_carets(&mut self) {
+        self.caret_input.reset();
+    }
+
     fn undo(&mut self) {
         if let Some(p) = self.undo_stack.pop() {
             self.redo_stack.push(self.text.clone());
             self.text = p;
+            self.revision += 1;
             self.last_saved = self.text.clone();
+            self.reset_carets();
             self.upd_mod();
+            self.refresh_search();
         }
     }
 
@@ -409,8 +1262,11 @@ impl Notepad {
         if let Some(n) = self.redo_stack.pop() {
             self.undo_stack.push(self.text.clone());
             self.text = n;
+            self.revision += 1;
             self.last_saved = self.text.clone();
+            self.reset_carets();
             self.upd_mod();
+            self.refresh_search();
         }
     }
 
@@ -422,6 +1278,7 @@ impl Notepad {
     }
 
     fn check_fd(&mut self) {
+        let mut saved_as = None;
         if let Some(rx) = &self.fd_rx {
             if let Ok(Some(p)) = rx.try_recv() {
                 match &self.fd_act {
@@ -448,9 +1305,16 @@ impl Notepad {
                             }
                         }
                     }
+                    Some(FdAct::Compare) => match std::fs::read_to_string(&p) {
+                        Ok(c) => self.compare_with_file(c, p),
+                        Err(e) => {
+                            self.show_notification(&format!("Cannot open file: {}", e));
+                        }
+                    },
                     Some(FdAct::SaveAs(c)) => {
                         let final_path = self.fix_extension(&p, c);
                         if std::fs::write(&final_path, c).is_ok() {
+                            saved_as = Some(c.clone());
                             self.file = Some(final_path);
                             self.modified = false;
                             if self.auto_detect {
@@ -464,10 +1328,17 @@ impl Notepad {
                 self.fd_act = None;
             }
         }
+        if let Some(content) = saved_as {
+            self.record_snapshot(&content, SnapshotKind::Save);
+            self.remember_folds();
+            self.refresh_history();
+            self.reload_git_base();
+        }
     }
 
     fn load_file_content(&mut self, content: String, path: PathBuf) {
         self.text = content;
+        self.revision += 1;
         self.last_saved = self.text.clone();
         self.undo_stack.clear();
         self.redo_stack.clear();
@@ -475,21 +1346,32 @@ impl Notepad {
         self.modified = false;
         self.auto_detect = true;
         self.auto_detect_language();
+        self.indent = self
+            .detect_indent
+            .then(|| detect_indent(&self.text, self.default_indent.width))
+            .flatten()
+            .unwrap_or(self.default_indent);
         self.editor_scroll_offset = 0.0;
         self.search_query.clear();
         self.search_results.clear();
         self.search_active = false;
-        self.search_cursor_pos = None;
+        self.search_scope = None;
+        self.reset_carets();
+        self.pending_selection = None;
+        self.restore_folds();
         self.update_preview_state();
         self.active_tab = EditorTab::Editor;
+        self.history_timer = 0.0;
+        self.refresh_history();
+        self.reload_git_base();
     }
 
-    fn fix_extension(&self, path: &PathBuf, _content: &str) -> PathBuf {
+    fn fix_extension(&self, path: &Path, _content: &str) -> PathBuf {
         let default_ext = self.get_default_extension();
 
         if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
             if ext == default_ext {
-                return path.clone();
+                return path.to_path_buf();
             }
             if ext == "txt" && default_ext != "txt" {
                 let stem = path
@@ -500,7 +1382,7 @@ impl Notepad {
             }
         }
 
-        path.clone()
+        path.to_path_buf()
     }
 
     fn req_new(&mut self) {
@@ -514,6 +1396,7 @@ impl Notepad {
 
     fn do_new(&mut self) {
         self.text.clear();
+        self.revision += 1;
         self.undo_stack.clear();
         self.redo_stack.clear();
         self.last_saved.clear();
@@ -528,6 +1411,12 @@ impl Notepad {
         self.search_query.clear();
         self.search_results.clear();
         self.search_active = false;
+        self.search_scope = None;
+        self.reset_carets();
+        self.folds.clear();
+        self.indent = self.default_indent;
+        self.history_entries.clear();
+        self.reload_git_base();
     }
 
     fn req_open(&mut self) {
@@ -554,6 +1443,9 @@ impl Notepad {
         if let Some(ref p) = self.file {
             if std::fs::write(p, &self.text).is_ok() {
                 self.modified = false;
+                let content = self.text.clone();
+                self.record_snapshot(&content, SnapshotKind::Save);
+                self.remember_folds();
             }
         } else {
             self.save_as();
@@ -637,48 +1529,439 @@ impl Notepad {
         }
     }
 
-    fn ins_time(&mut self) {
-        self.save_undo();
-        self.text
-            .push_str(&Local::now().format("%Y-%m-%d %H:%M").to_string());
-        self.modified = true;
+    fn saved_text(&self) -> String {
+        self.file
+            .as_ref()
+            .and_then(|p| std::fs::read_to_string(p).ok())
+            .unwrap_or_default()
     }
 
-    fn upd_cur(&mut self, o: &egui::text_edit::TextEditOutput) {
-        if let Some(r) = o.cursor_range.clone() {
-            let b: String = self.text.chars().take(r.primary.ccursor.index).collect();
-            let v: Vec<&str> = b.split('\n').collect();
-            self.cur_line = v.len();
-            self.cur_col = v.last().map(|l| l.chars().count() + 1).unwrap_or(1);
-        }
+    fn file_name(&self) -> String {
+        self.file
+            .as_ref()
+            .and_then(|p| p.file_name())
+            .and_then(|n| n.to_str())
+            .unwrap_or("Untitled")
+            .to_string()
     }
 
-    fn z_in(&mut self) {
-        if self.zoom < MAX_ZOOM_LEVEL {
-            self.zoom = (self.zoom + 10).min(MAX_ZOOM_LEVEL);
-            self.font_size = calculate_font_size(self.zoom);
-        }
+    fn buffer_pane(&self) -> DiffPane {
+        DiffPane::new(format!("Current ({})", self.file_name()), self.text.clone())
+            .buffer()
+            .syntax(self.current_syntax.clone())
     }
 
-    fn z_out(&mut self) {
-        if self.zoom > MIN_ZOOM_LEVEL {
-            self.zoom = (self.zoom - 10).max(MIN_ZOOM_LEVEL);
-            self.font_size = calculate_font_size(self.zoom);
-        }
+    fn compare_with_saved(&mut self) {
+        let name = self.file_name();
+        let saved = DiffPane::new(format!("Saved ({})", name), self.saved_text())
+            .syntax(self.current_syntax.clone());
+        self.diff_view = Some(DiffView::new(
+            format!("Compare with Saved: {}", name),
+            saved,
+            self.buffer_pane(),
+        ));
     }
 
-    fn z_res(&mut self) {
-        self.zoom = DEFAULT_ZOOM_LEVEL;
-        self.font_size = DEFAULT_FONT_SIZE;
+    fn req_compare_file(&mut self) {
+        let (tx, rx) = mpsc::channel();
+        self.fd_rx = Some(rx);
+        self.fd_act = Some(FdAct::Compare);
+        std::thread::spawn(move || {
+            let _ = tx.send(FileDialog::new().pick_file());
+        });
     }
 
-    fn upd_title(&self, ctx: &egui::Context) {
-        let title = if let Some(ref p) = self.file {
-            let name = p.file_name().and_then(|n| n.to_str()).unwrap_or("Untitled");
-            if self.modified {
-                format!("*{} - MemoChan", name)
-            } else {
-                format!("{} - MemoChan", name)
+    fn compare_with_file(&mut self, content: String, path: PathBuf) {
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("Untitled")
+            .to_string();
+        let syntax = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .and_then(|e| self.highlighter.find_syntax_by_extension(e))
+            .map(|s| s.name.clone())
+            .or_else(|| self.current_syntax.clone());
+        let other = DiffPane::new(name.clone(), content)
+            .file(path)
+            .syntax(syntax);
+        self.diff_view = Some(DiffView::new(
+            format!("Compare: {} ↔ {}", name, self.file_name()),
+            other,
+            self.buffer_pane(),
+        ));
+    }
+
+    fn selected_text(&self) -> Option<String> {
+        self.selection
+            .as_ref()
+            .map(|r| self.text.chars().skip(r.start).take(r.len()).collect())
+    }
+
+    /// Asks egui for the clipboard; `check_clipboard` opens the diff in the next pass.
+    fn compare_selection_with_clipboard(&mut self, ctx: &egui::Context) {
+        let Some(selected) = self.selected_text() else {
+            self.show_notification("Select text to compare");
+            return;
+        };
+        ctx.send_viewport_cmd(egui::ViewportCommand::RequestPaste);
+        ctx.request_repaint();
+        self.clipboard_request = Some((selected, ctx.cumulative_pass_nr()));
+    }
+
+    /// Takes the paste answering `compare_selection_with_clipboard` before the editor
+    /// can insert it. The integration queues it ahead of the next pass's own input, so
+    /// only that pass's first paste is ours; any later one is the user's.
+    fn check_clipboard(&mut self, ctx: &egui::Context) {
+        match self.clipboard_request {
+            Some((_, requested)) if ctx.cumulative_pass_nr() > requested => {}
+            _ => return,
+        }
+        let Some((selected, _)) = self.clipboard_request.take() else {
+            return;
+        };
+        let Some(clipboard) = ctx.input_mut(|i| take_paste(&mut i.events)) else {
+            self.show_notification("The clipboard holds no text");
+            return;
+        };
+        self.diff_view = Some(DiffView::new(
+            "Compare Selection with Clipboard",
+            DiffPane::new("Selection", selected).syntax(self.current_syntax.clone()),
+            DiffPane::new("Clipboard", clipboard).syntax(self.current_syntax.clone()),
+        ));
+    }
+
+    fn close_diff_view(&mut self) {
+        self.diff_view = None;
+        self.editor_focus_request = true;
+        if self.confirm_act != ConfirmAct::None {
+            self.confirm = true;
+        }
+    }
+
+    fn record_snapshot(&mut self, content: &str, kind: SnapshotKind) {
+        self.history_timer = 0.0;
+        let (Some(history), Some(file)) = (&self.local_history, &self.file) else {
+            return;
+        };
+        match history.record(file, content, kind) {
+            Ok(true) if self.history_panel => self.refresh_history(),
+            Ok(_) => {}
+            Err(e) => self.show_notification(&format!("Cannot record history: {}", e)),
+        }
+    }
+
+    fn refresh_history(&mut self) {
+        self.history_entries = match (&self.local_history, &self.file) {
+            (Some(history), Some(file)) => history.list(file),
+            _ => Vec::new(),
+        };
+    }
+
+    fn toggle_history_panel(&mut self) {
+        self.history_panel = !self.history_panel;
+        if self.history_panel {
+            self.refresh_history();
+        }
+    }
+
+    fn compare_with_snapshot(&mut self, snapshot: &Snapshot) {
+        let Some(history) = &self.local_history else {
+            return;
+        };
+        match history.read(snapshot) {
+            Ok(content) => {
+                let label = snapshot.time.format("%Y-%m-%d %H:%M:%S").to_string();
+                let pane =
+                    DiffPane::new(label.clone(), content).syntax(self.current_syntax.clone());
+                self.diff_view = Some(DiffView::new(
+                    format!("Local History: {}", label),
+                    pane,
+                    self.buffer_pane(),
+                ));
+            }
+            Err(e) => self.show_notification(&format!("Cannot read snapshot: {}", e)),
+        }
+    }
+
+    fn restore_snapshot(&mut self, snapshot: &Snapshot) {
+        let Some(history) = &self.local_history else {
+            return;
+        };
+        match history.read(snapshot) {
+            Ok(content) => {
+                self.text = content;
+                self.reset_carets();
+                self.save_undo();
+                self.upd_mod();
+                self.show_notification("Snapshot restored");
+            }
+            Err(e) => self.show_notification(&format!("Cannot read snapshot: {}", e)),
+        }
+    }
+
+    /// Reads the file's HEAD revision on a worker thread, for `check_git_base` to pick up.
+    /// The current revision is kept meanwhile unless it belongs to another file.
+    fn reload_git_base(&mut self) {
+        if self.git_base_file != self.file {
+            self.git_base_file = None;
+            self.set_git_base(None);
+        }
+        self.git_base_rx = self.file.clone().map(|file| {
+            let (tx, rx) = mpsc::channel();
+            std::thread::spawn(move || {
+                let base = head_content(&file);
+                let _ = tx.send((file, base));
+            });
+            rx
+        });
+    }
+
+    fn check_git_base(&mut self) {
+        let Some(rx) = &self.git_base_rx else {
+            return;
+        };
+        let (file, base) = match rx.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => {
+                self.git_base_rx = None;
+                return;
+            }
+        };
+        self.git_base_rx = None;
+        if self.file.as_ref() != Some(&file) {
+            return;
+        }
+        if self.git_base_file.as_ref() != Some(&file) || base != self.git_base {
+            self.git_base_file = Some(file);
+            self.set_git_base(base);
+        }
+    }
+
+    fn set_git_base(&mut self, base: Option<String>) {
+        self.git_base = base;
+        self.git_diff_text = None;
+        self.git_diff_timer = GIT_DIFF_DELAY;
+        self.git_popup = None;
+        if self.git_base.is_none() {
+            self.git_hunks.clear();
+        }
+        self.blame.clear();
+        self.blame_text = None;
+        self.blame_rx = None;
+        self.blame_timer = BLAME_DELAY;
+        if self.file_history_panel {
+            self.refresh_file_history();
+        }
+    }
+
+    fn toggle_blame(&mut self) {
+        self.show_blame = !self.show_blame;
+        self.blame_text = None;
+        self.blame_timer = BLAME_DELAY;
+    }
+
+    /// Re-blames the buffer on a worker thread once it has been idle for `BLAME_DELAY`
+    /// seconds. Results for a text that has changed since are dropped.
+    fn update_blame(&mut self, dt: f32) {
+        if let Some(rx) = &self.blame_rx {
+            match rx.try_recv() {
+                Ok((text, lines)) => {
+                    self.blame_rx = None;
+                    if text == self.text {
+                        self.blame = lines.unwrap_or_default();
+                        self.blame_text = Some(text);
+                    }
+                }
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => self.blame_rx = None,
+            }
+        }
+        let Some(file) = self
+            .file
+            .as_ref()
+            .filter(|_| self.show_blame && self.git_base.is_some())
+        else {
+            return;
+        };
+        if self.blame_text.as_deref() == Some(self.text.as_str()) {
+            return;
+        }
+        self.blame_timer += dt;
+        if self.blame_timer >= BLAME_DELAY {
+            let (tx, rx) = mpsc::channel();
+            let (file, text) = (file.clone(), self.text.clone());
+            std::thread::spawn(move || {
+                let lines = blame(&file, &text);
+                let _ = tx.send((text, lines));
+            });
+            self.blame_rx = Some(rx);
+            self.blame_timer = 0.0;
+        }
+    }
+
+    fn blame_width(&self) -> f32 {
+        if self.show_blame && self.git_base.is_some() {
+            self.font_size * 16.0
+        } else {
+            0.0
+        }
+    }
+
+    fn refresh_file_history(&mut self) {
+        self.file_commits = self.file.as_deref().map(file_log).unwrap_or_default();
+        self.selected_commit = None;
+    }
+
+    fn toggle_file_history(&mut self) {
+        self.file_history_panel = !self.file_history_panel;
+        if self.file_history_panel {
+            self.refresh_file_history();
+        }
+    }
+
+    fn revision_content(&mut self, index: usize) -> Option<(FileCommit, String)> {
+        let commit = self.file_commits.get(index)?.clone();
+        let file = self.file.as_ref()?;
+        match show_revision(file, &commit) {
+            Some(text) => Some((commit, text)),
+            None => {
+                self.show_notification(&format!("Cannot read revision {}", commit.short));
+                None
+            }
+        }
+    }
+
+    fn open_revision(&mut self, index: usize) {
+        if let Some((commit, text)) = self.revision_content(index) {
+            self.revision_view = Some(RevisionView {
+                title: format!("{} @ {} ({})", commit.path, commit.short, commit.date),
+                text,
+                syntax: self.current_syntax.clone(),
+            });
+        }
+    }
+
+    fn compare_revision(&mut self, index: usize) {
+        if let Some((commit, text)) = self.revision_content(index) {
+            let label = format!("{} ({})", commit.short, commit.date);
+            self.diff_view = Some(DiffView::new(
+                format!("Compare with Revision: {}", commit.short),
+                DiffPane::new(label, text).syntax(self.current_syntax.clone()),
+                self.buffer_pane(),
+            ));
+        }
+    }
+
+    /// Re-diffs the buffer against HEAD, at most every `GIT_DIFF_DELAY` seconds while typing.
+    fn update_git_hunks(&mut self, dt: f32) {
+        let Some(base) = &self.git_base else {
+            return;
+        };
+        if self.git_diff_text.as_deref() == Some(self.text.as_str()) {
+            self.git_diff_timer = 0.0;
+            return;
+        }
+        self.git_diff_timer += dt;
+        if self.git_diff_timer >= GIT_DIFF_DELAY {
+            self.git_hunks = diff_hunks(base, &self.text);
+            self.git_diff_text = Some(self.text.clone());
+            self.git_diff_timer = 0.0;
+        }
+    }
+
+    fn revert_git_hunk(&mut self, index: usize) {
+        let (Some(base), Some(hunk)) = (&self.git_base, self.git_hunks.get(index)) else {
+            return;
+        };
+        self.text = splice_lines(
+            &self.text,
+            hunk.new_lines.clone(),
+            base,
+            hunk.old_lines.clone(),
+        );
+        self.reset_carets();
+        self.save_undo();
+        self.upd_mod();
+        self.git_popup = None;
+        self.git_diff_timer = GIT_DIFF_DELAY;
+    }
+
+    fn gutter_width(&self, ctx: &egui::Context) -> f32 {
+        let git = if self.git_base.is_some() {
+            self.blame_width() + GIT_GUTTER_WIDTH
+        } else {
+            0.0
+        };
+        git + self.line_number_width(ctx) + self.fold_gutter_width()
+    }
+
+    fn line_number_font(&self) -> FontId {
+        FontId::proportional(self.font_size * 0.9)
+    }
+
+    /// Width of the line number column: the widest number plus padding on both sides.
+    fn line_number_width(&self, ctx: &egui::Context) -> f32 {
+        if self.line_numbers == LineNumbers::Hidden {
+            return 0.0;
+        }
+        let lines = self.text.bytes().filter(|&b| b == b'\n').count() + 1;
+        let digits = lines.to_string().len().max(2);
+        let digit = ctx.fonts(|f| f.glyph_width(&self.line_number_font(), '0'));
+        digits as f32 * digit + self.font_size
+    }
+
+    fn fold_gutter_width(&self) -> f32 {
+        self.font_size
+    }
+
+    fn ins_time(&mut self) {
+        self.save_undo();
+        self.text
+            .push_str(&Local::now().format("%Y-%m-%d %H:%M").to_string());
+        self.revision += 1;
+        self.modified = true;
+    }
+
+    fn upd_cur(&mut self, o: &egui::text_edit::TextEditOutput) {
+        if let Some(r) = o.cursor_range {
+            let range = r.as_sorted_char_range();
+            self.selection = (!range.is_empty()).then_some(range);
+            let b: String = self.text.chars().take(r.primary.ccursor.index).collect();
+            let v: Vec<&str> = b.split('\n').collect();
+            self.cur_line = v.len();
+            self.cur_col = v.last().map(|l| l.chars().count() + 1).unwrap_or(1);
+        }
+    }
+
+    fn z_in(&mut self) {
+        if self.zoom < MAX_ZOOM_LEVEL {
+            self.zoom = (self.zoom + 10).min(MAX_ZOOM_LEVEL);
+            self.font_size = calculate_font_size(self.zoom);
+        }
+    }
+
+    fn z_out(&mut self) {
+        if self.zoom > MIN_ZOOM_LEVEL {
+            self.zoom = (self.zoom - 10).max(MIN_ZOOM_LEVEL);
+            self.font_size = calculate_font_size(self.zoom);
+        }
+    }
+
+    fn z_res(&mut self) {
+        self.zoom = DEFAULT_ZOOM_LEVEL;
+        self.font_size = DEFAULT_FONT_SIZE;
+    }
+
+    fn upd_title(&self, ctx: &egui::Context) {
+        let title = if let Some(ref p) = self.file {
+            let name = p.file_name().and_then(|n| n.to_str()).unwrap_or("Untitled");
+            if self.modified {
+                format!("*{} - MemoChan", name)
+            } else {
+                format!("{} - MemoChan", name)
             }
         } else {
             if self.modified {
@@ -692,9 +1975,40 @@ impl Notepad {
 }
 
 impl eframe::App for Notepad {
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        storage.set_string(SEARCH_HISTORY_KEY, self.search_history.join("\n"));
+        storage.set_string(REPLACE_HISTORY_KEY, self.replace_history.join("\n"));
+        storage.set_string(INDENT_SPACES_KEY, self.default_indent.spaces.to_string());
+        storage.set_string(TAB_WIDTH_KEY, self.default_indent.width.to_string());
+        storage.set_string(DETECT_INDENT_KEY, self.detect_indent.to_string());
+        storage.set_string(WRAP_COLUMN_KEY, self.wrap_column.to_string());
+        storage.set_string(WRAP_AS_YOU_TYPE_KEY, self.wrap_as_you_type.to_string());
+        storage.set_string(LINE_NUMBERS_KEY, self.line_numbers.name().to_string());
+        storage.set_string(HIGHLIGHT_LINE_KEY, self.highlight_line.to_string());
+        storage.set_string(HIGHLIGHT_COLUMN_KEY, self.highlight_column.to_string());
+        storage.set_string(MINIMAP_KEY, self.minimap.to_string());
+        let folds: Vec<String> = self
+            .saved_folds
+            .iter()
+            .map(|(path, lines)| {
+                let lines: Vec<String> = lines.iter().map(usize::to_string).collect();
+                format!("{}\t{}", lines.join(","), path.display())
+            })
+            .collect();
+        storage.set_string(FOLDS_KEY, folds.join("\n"));
+    }
+
     fn update(&mut self, ctx: &egui::Context, _: &mut eframe::Frame) {
         self.check_fd();
+        self.check_clipboard(ctx);
+        self.check_git_base();
         self.upd_title(ctx);
+
+        let focused = ctx.input(|i| i.viewport().focused.unwrap_or(true));
+        if focused && !self.window_focused {
+            self.reload_git_base();
+        }
+        self.window_focused = focused;
         ctx.set_zoom_factor(1.0);
 
         let dark = self.is_dark();
@@ -714,7 +2028,7 @@ impl eframe::App for Notepad {
                         ..
                     } = e
                     {
-                        if is_cmd(&modifiers) {
+                        if is_cmd(modifiers) {
                             match key {
                                 egui::Key::N => self.req_new(),
                                 egui::Key::O => self.req_open(),
@@ -735,6 +2049,17 @@ impl eframe::App for Notepad {
                                 egui::Key::Y => self.redo(),
                                 egui::Key::P => toggle_tab = true,
                                 egui::Key::F => toggle_search = true,
+                                egui::Key::G => self.open_goto_line(),
+                                egui::Key::Slash => self.toggle_comment(ctx, modifiers.shift),
+                                egui::Key::OpenBracket if modifiers.shift => {
+                                    self.fold_command(ctx, FoldCommand::Fold)
+                                }
+                                egui::Key::CloseBracket if modifiers.shift => {
+                                    self.fold_command(ctx, FoldCommand::Unfold)
+                                }
+                                egui::Key::Backslash if modifiers.shift => {
+                                    self.jump_to_bracket(ctx)
+                                }
                                 _ => {}
                             }
                         }
@@ -759,20 +2084,9 @@ impl eframe::App for Notepad {
                         } = e
                         {
                             match key {
-                                egui::Key::Escape => {
-                                    if !self.search_results.is_empty() {
-                                        let result = &self.search_results[self.current_search_idx];
-                                        self.search_cursor_pos = Some(result.index);
-                                    }
-                                    self.search_active = false;
-                                    if self.active_tab == EditorTab::Editor {
-                                        self.editor_focus_request = true;
-                                    } else {
-                                        self.preview_focus_request = true;
-                                    }
-                                }
-                                egui::Key::ArrowUp => self.prev_search_result(),
-                                egui::Key::ArrowDown => self.next_search_result(),
+                                egui::Key::Escape => self.close_search(),
+                                egui::Key::ArrowUp => self.search_arrow(true),
+                                egui::Key::ArrowDown => self.search_arrow(false),
                                 _ => {}
                             }
                         }
@@ -849,16 +2163,7 @@ impl eframe::App for Notepad {
                 .show(ctx, |ui| {
                     ui.horizontal(|ui| {
                         if ui.button("X").clicked() {
-                            if !self.search_results.is_empty() {
-                                let result = &self.search_results[self.current_search_idx];
-                                self.search_cursor_pos = Some(result.index);
-                            }
-                            self.search_active = false;
-                            if self.active_tab == EditorTab::Editor {
-                                self.editor_focus_request = true;
-                            } else {
-                                self.preview_focus_request = true;
-                            }
+                            self.close_search();
                         }
                         ui.label("Find:");
 
@@ -874,7 +2179,7 @@ impl eframe::App for Notepad {
                         }
 
                         if self.search_select_all {
-                            let text_len = self.search_query.len();
+                            let text_len = self.search_query.chars().count();
                             output.state.cursor.set_char_range(Some(
                                 egui::text::CCursorRange::two(
                                     egui::text::CCursor::new(0),
@@ -885,36 +2190,166 @@ impl eframe::App for Notepad {
                             self.search_select_all = false;
                         }
 
-                        self.search_input_has_focus = output.response.has_focus();
-
                         if output.response.changed() {
+                            self.search_history_pos = None;
                             self.perform_search();
                         }
                         if output.response.lost_focus()
                             && ui.input(|i| i.key_pressed(egui::Key::Enter))
                         {
+                            self.remember_search();
                             self.next_search_result();
                             self.search_focus = true;
                         }
+                        let mut picked = None;
+                        ui.add_enabled_ui(!self.search_history.is_empty(), |ui| {
+                            ui.menu_button("⏷", |ui| {
+                                for entry in &self.search_history {
+                                    if ui.button(entry).clicked() {
+                                        picked = Some(entry.clone());
+                                        ui.close_menu();
+                                    }
+                                }
+                            })
+                            .response
+                            .on_hover_text("Recent Searches");
+                        });
+                        if let Some(entry) = picked {
+                            self.search_query = entry;
+                            self.search_history_pos = None;
+                            self.perform_search();
+                        }
 
-                        let count_text = if self.search_query.is_empty() {
-                            "".to_string()
-                        } else if self.search_results.is_empty() {
-                            "No results".to_string()
-                        } else {
-                            format!(
-                                "{}/{}",
-                                self.current_search_idx + 1,
-                                self.search_results.len()
+                        let options = &mut self.search_options;
+                        let toggles = [
+                            (&mut options.case_sensitive, "Aa", "Match Case"),
+                            (&mut options.whole_word, "W", "Whole Word"),
+                            (&mut options.ignore_diacritics, "ä", "Ignore Accents"),
+                            (&mut options.regex, ".*", "Regular Expression"),
+                        ];
+                        let mut options_changed = false;
+                        for (value, label, hint) in toggles {
+                            if ui
+                                .selectable_label(*value, label)
+                                .on_hover_text(hint)
+                                .clicked()
+                            {
+                                *value = !*value;
+                                options_changed = true;
+                            }
+                        }
+                        if options_changed {
+                            self.perform_search();
+                        }
+                        if ui
+                            .add_enabled(
+                                self.search_scope.is_some() || self.selection.is_some(),
+                                egui::SelectableLabel::new(self.search_scope.is_some(), "≡"),
                             )
-                        };
-                        ui.label(count_text);
+                            .on_hover_text("Find in Selection")
+                            .clicked()
+                        {
+                            self.toggle_search_scope();
+                        }
+
+                        if let Some(error) = &self.search_error {
+                            let colors = ThemeColors::for_mode(self.theme_mode, self.system_dark);
+                            ui.label(RichText::new(error).color(colors.diff_removed_emph));
+                        } else {
+                            let count_text = if self.search_query.is_empty() {
+                                "".to_string()
+                            } else if self.search_results.is_empty() {
+                                "No results".to_string()
+                            } else {
+                                format!(
+                                    "{}/{}",
+                                    self.current_search_idx + 1,
+                                    self.search_results.len()
+                                )
+                            };
+                            ui.label(count_text);
+                        }
+                        let has_query = !self.search_results.is_empty();
                         if ui.button("↑").clicked() {
                             self.prev_search_result();
                         }
                         if ui.button("↓").clicked() {
                             self.next_search_result();
                         }
+                        if ui
+                            .add_enabled(has_query, egui::Button::new("Find All"))
+                            .clicked()
+                        {
+                            self.remember_search();
+                            self.find_all_panel = true;
+                        }
+
+                        ui.separator();
+                        ui.label("Replace:");
+                        let replace = ui.add(
+                            egui::TextEdit::singleline(&mut self.replace_query)
+                                .desired_width(160.0)
+                                .id(egui::Id::new("replace_input")),
+                        );
+                        if replace.changed() {
+                            self.replace_history_pos = None;
+                        }
+                        if replace.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                            self.replace_current();
+                            replace.request_focus();
+                        }
+                        let mut picked = None;
+                        ui.add_enabled_ui(!self.replace_history.is_empty(), |ui| {
+                            ui.menu_button("⏷", |ui| {
+                                for entry in &self.replace_history {
+                                    if ui.button(entry).clicked() {
+                                        picked = Some(entry.clone());
+                                        ui.close_menu();
+                                    }
+                                }
+                            })
+                            .response
+                            .on_hover_text("Recent Replacements");
+                        });
+                        if let Some(entry) = picked {
+                            self.replace_query = entry;
+                            self.replace_history_pos = None;
+                        }
+                        if ui
+                            .selectable_label(self.search_options.preserve_case, "AB")
+                            .on_hover_text("Preserve Case")
+                            .clicked()
+                        {
+                            self.search_options.preserve_case = !self.search_options.preserve_case;
+                        }
+                        let has_results = !self.search_results.is_empty();
+                        if ui
+                            .add_enabled(has_results, egui::Button::new("Replace"))
+                            .clicked()
+                        {
+                            self.replace_current();
+                        }
+                        if ui
+                            .add_enabled(has_results, egui::Button::new("All"))
+                            .on_hover_text("Replace All")
+                            .clicked()
+                        {
+                            self.replace_all(false);
+                        }
+                        if ui
+                            .add_enabled(
+                                has_results && self.selection.is_some(),
+                                egui::Button::new("In Selection"),
+                            )
+                            .on_hover_text("Replace All in Selection")
+                            .clicked()
+                        {
+                            self.replace_all(true);
+                        }
+
+                        self.replace_input_has_focus = replace.has_focus();
+                        self.search_input_has_focus =
+                            output.response.has_focus() || replace.has_focus();
                     });
                 });
         }
@@ -922,6 +2357,15 @@ impl eframe::App for Notepad {
         if self.status_bar {
             egui::TopBottomPanel::bottom("status").show(ctx, |ui| self.status(ui, dark));
         }
+        if self.history_panel {
+            self.history_side_panel(ctx);
+        }
+        if self.file_history_panel {
+            self.file_history_side_panel(ctx);
+        }
+        if self.find_all_panel {
+            self.find_all_results_panel(ctx, dark);
+        }
         self.main_area(ctx, dark);
         if self.about {
             self.about_md(ctx);
@@ -935,6 +2379,12 @@ impl eframe::App for Notepad {
         if self.large_file_confirm {
             self.large_file_confirm_md(ctx);
         }
+        if self.goto_line {
+            self.goto_line_md(ctx);
+        }
+        if self.git_popup.is_some() {
+            self.git_hunk_popup(ctx);
+        }
 
         if !self.notification_text.is_empty() {
             self.show_notification_toast(ctx);
@@ -944,6 +2394,14 @@ impl eframe::App for Notepad {
             }
         }
 
+        if self.modified && self.file.is_some() {
+            self.history_timer += ctx.input(|i| i.stable_dt.min(0.1));
+            if self.history_timer >= HISTORY_SNAPSHOT_INTERVAL_SECS {
+                let content = self.text.clone();
+                self.record_snapshot(&content, SnapshotKind::Auto);
+            }
+        }
+
         for result in &mut self.search_results {
             if result.flash_timer > 0.0 {
                 result.flash_timer -= ctx.input(|i| i.stable_dt.min(0.1));
@@ -975,6 +2433,47 @@ impl Notepad {
                     self.save_as();
                     ui.close_menu();
                 }
+                ui.separator();
+                let m = if self.history_panel { "✓ " } else { "  " };
+                if ui.button(format!("{}Local History", m)).clicked() {
+                    self.toggle_history_panel();
+                    ui.close_menu();
+                }
+                let m = if self.file_history_panel {
+                    "✓ "
+                } else {
+                    "  "
+                };
+                if ui
+                    .add_enabled(
+                        self.git_base.is_some(),
+                        egui::Button::new(format!("{}File History", m)),
+                    )
+                    .clicked()
+                {
+                    self.toggle_file_history();
+                    ui.close_menu();
+                }
+                ui.menu_button("Compare", |ui| {
+                    if ui.button("Compare with Saved").clicked() {
+                        self.compare_with_saved();
+                        ui.close_menu();
+                    }
+                    if ui.button("Compare with File...").clicked() {
+                        self.req_compare_file();
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(
+                            self.selection.is_some(),
+                            egui::Button::new("Compare Selection with Clipboard"),
+                        )
+                        .clicked()
+                    {
+                        self.compare_selection_with_clipboard(ui.ctx());
+                        ui.close_menu();
+                    }
+                });
 
                 ui.separator();
                 if ui.button("Exit").clicked() {
@@ -1003,16 +2502,136 @@ impl Notepad {
                     self.perform_search();
                     ui.close_menu();
                 }
-                ui.separator();
-                if ui.button("Time/Date\tF5").clicked() {
-                    self.ins_time();
+                if ui
+                    .add_enabled(!self.search_query.is_empty(), egui::Button::new("Find All"))
+                    .clicked()
+                {
+                    self.find_matches();
+                    self.find_all_panel = true;
                     ui.close_menu();
                 }
-            });
-
-            ui.menu_button("View", |ui| {
-                if ui.button("Zoom In\tCtrl++").clicked() {
-                    self.z_in();
+                ui.separator();
+                let commands = [
+                    (
+                        "Add Cursor Above",
+                        format!("{}+Alt+Up", key),
+                        CaretCommand::Above,
+                    ),
+                    (
+                        "Add Cursor Below",
+                        format!("{}+Alt+Down", key),
+                        CaretCommand::Below,
+                    ),
+                    (
+                        "Add Next Occurrence",
+                        format!("{}+D", key),
+                        CaretCommand::NextOccurrence,
+                    ),
+                    (
+                        "Select All Occurrences",
+                        format!("Shift+{}+L", key),
+                        CaretCommand::AllOccurrences,
+                    ),
+                ];
+                for (label, shortcut, command) in commands {
+                    if ui.button(format!("{}\t{}", label, shortcut)).clicked() {
+                        self.caret_command(ui.ctx(), command);
+                        ui.close_menu();
+                    }
+                }
+                ui.separator();
+                if ui
+                    .button(format!("Toggle Line Comment\t{}+/", key))
+                    .clicked()
+                {
+                    self.toggle_comment(ui.ctx(), false);
+                    ui.close_menu();
+                }
+                let label = format!("Toggle Block Comment\tShift+{}+/", key);
+                if ui.button(label).clicked() {
+                    self.toggle_comment(ui.ctx(), true);
+                    ui.close_menu();
+                }
+                ui.menu_button("Lines", |ui| {
+                    let edits = [
+                        (
+                            "Duplicate Lines",
+                            format!("Shift+{}+D", key),
+                            LineCommand::Edit(LineEdit::Duplicate),
+                        ),
+                        (
+                            "Delete Lines",
+                            format!("Shift+{}+K", key),
+                            LineCommand::Edit(LineEdit::Delete),
+                        ),
+                        (
+                            "Move Lines Up",
+                            "Alt+Up".to_string(),
+                            LineCommand::Edit(LineEdit::MoveUp),
+                        ),
+                        (
+                            "Move Lines Down",
+                            "Alt+Down".to_string(),
+                            LineCommand::Edit(LineEdit::MoveDown),
+                        ),
+                        (
+                            "Join Lines",
+                            format!("{}+J", key),
+                            LineCommand::Edit(LineEdit::Join),
+                        ),
+                        (
+                            "Split Lines",
+                            format!("Shift+{}+J", key),
+                            LineCommand::Split,
+                        ),
+                    ];
+                    for (label, shortcut, command) in edits {
+                        if ui.button(format!("{}\t{}", label, shortcut)).clicked() {
+                            self.line_command(ui.ctx(), command);
+                            ui.close_menu();
+                        }
+                    }
+                    ui.separator();
+                    let seed = std::time::SystemTime::now()
+                        .duration_since(std::time::UNIX_EPOCH)
+                        .map_or(0, |d| d.as_nanos() as u64);
+                    let transforms = [
+                        ("Sort Ascending", LineTransform::SortAscending),
+                        ("Sort Descending", LineTransform::SortDescending),
+                        ("Sort Natural", LineTransform::SortNatural),
+                        ("Sort Case-Insensitive", LineTransform::SortCaseInsensitive),
+                        ("Reverse", LineTransform::Reverse),
+                        ("Shuffle", LineTransform::Shuffle(seed)),
+                        ("Remove Duplicate Lines", LineTransform::RemoveDuplicates),
+                        ("Remove Blank Lines", LineTransform::RemoveBlank),
+                    ];
+                    for (label, transform) in transforms {
+                        if ui.button(label).clicked() {
+                            self.line_command(ui.ctx(), LineCommand::Transform(transform));
+                            ui.close_menu();
+                        }
+                    }
+                });
+                ui.separator();
+                let label = format!("Go to Matching Bracket\tShift+{}+\\", key);
+                if ui.button(label).clicked() {
+                    self.jump_to_bracket(ui.ctx());
+                    ui.close_menu();
+                }
+                if ui.button(format!("Go to Line...\t{}+G", key)).clicked() {
+                    self.open_goto_line();
+                    ui.close_menu();
+                }
+                ui.separator();
+                if ui.button("Time/Date\tF5").clicked() {
+                    self.ins_time();
+                    ui.close_menu();
+                }
+            });
+
+            ui.menu_button("View", |ui| {
+                if ui.button("Zoom In\tCtrl++").clicked() {
+                    self.z_in();
                     ui.close_menu();
                 }
                 if ui.button("Zoom Out\tCtrl+-").clicked() {
@@ -1029,6 +2648,73 @@ impl Notepad {
                     self.status_bar = !self.status_bar;
                     ui.close_menu();
                 }
+                ui.menu_button("Line Numbers", |ui| {
+                    for numbers in [
+                        LineNumbers::Hidden,
+                        LineNumbers::Absolute,
+                        LineNumbers::Relative,
+                    ] {
+                        let check = if self.line_numbers == numbers {
+                            "✓ "
+                        } else {
+                            "  "
+                        };
+                        if ui.button(format!("{}{}", check, numbers.name())).clicked() {
+                            self.line_numbers = numbers;
+                            ui.close_menu();
+                        }
+                    }
+                });
+                let m = if self.highlight_line { "✓ " } else { "  " };
+                if ui.button(format!("{}Highlight Current Line", m)).clicked() {
+                    self.highlight_line = !self.highlight_line;
+                    ui.close_menu();
+                }
+                let m = if self.highlight_column { "✓ " } else { "  " };
+                if ui
+                    .button(format!("{}Highlight Current Column", m))
+                    .clicked()
+                {
+                    self.highlight_column = !self.highlight_column;
+                    ui.close_menu();
+                }
+                let m = if self.minimap { "✓ " } else { "  " };
+                if ui.button(format!("{}Minimap", m)).clicked() {
+                    self.minimap = !self.minimap;
+                    ui.close_menu();
+                }
+                let m = if self.show_blame { "✓ " } else { "  " };
+                if ui
+                    .add_enabled(
+                        self.git_base.is_some(),
+                        egui::Button::new(format!("{}Blame", m)),
+                    )
+                    .clicked()
+                {
+                    self.toggle_blame();
+                    ui.close_menu();
+                }
+                ui.menu_button("Folding", |ui| {
+                    let commands = [
+                        ("Fold", format!("Shift+{}+[", key), FoldCommand::Fold),
+                        ("Unfold", format!("Shift+{}+]", key), FoldCommand::Unfold),
+                        ("Fold All", String::new(), FoldCommand::FoldAll),
+                        ("Unfold All", String::new(), FoldCommand::UnfoldAll),
+                    ];
+                    for (label, shortcut, command) in commands {
+                        if ui.button(format!("{}\t{}", label, shortcut)).clicked() {
+                            self.fold_command(ui.ctx(), command);
+                            ui.close_menu();
+                        }
+                    }
+                    ui.separator();
+                    for level in 1..=5 {
+                        if ui.button(format!("Fold Level {}", level)).clicked() {
+                            self.fold_command(ui.ctx(), FoldCommand::Level(level));
+                            ui.close_menu();
+                        }
+                    }
+                });
 
                 if self.is_markdown() {
                     ui.separator();
@@ -1127,6 +2813,131 @@ impl Notepad {
                     self.word_wrap = !self.word_wrap;
                     ui.close_menu();
                 }
+                ui.menu_button("Indentation", |ui| {
+                    for spaces in [true, false] {
+                        let m = if self.indent.spaces == spaces {
+                            "✓ "
+                        } else {
+                            "  "
+                        };
+                        let label = if spaces {
+                            "Indent Using Spaces"
+                        } else {
+                            "Indent Using Tabs"
+                        };
+                        if ui.button(format!("{}{}", m, label)).clicked() {
+                            self.indent.spaces = spaces;
+                            self.default_indent.spaces = spaces;
+                            ui.close_menu();
+                        }
+                    }
+                    ui.separator();
+                    for width in TAB_WIDTHS {
+                        let m = if self.indent.width == width {
+                            "✓ "
+                        } else {
+                            "  "
+                        };
+                        if ui.button(format!("{}Tab Width: {}", m, width)).clicked() {
+                            self.indent.width = width;
+                            self.default_indent.width = width;
+                            ui.close_menu();
+                        }
+                    }
+                    ui.separator();
+                    let m = if self.detect_indent { "✓ " } else { "  " };
+                    if ui
+                        .button(format!("{}Detect Indentation from File", m))
+                        .clicked()
+                    {
+                        self.detect_indent = !self.detect_indent;
+                        if self.detect_indent {
+                            self.indent = detect_indent(&self.text, self.default_indent.width)
+                                .unwrap_or(self.default_indent);
+                        }
+                        ui.close_menu();
+                    }
+                });
+                ui.menu_button("Reflow", |ui| {
+                    if ui.button("Reflow Paragraph\tAlt+Q").clicked() {
+                        self.reflow(ui.ctx());
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    for column in WRAP_COLUMNS {
+                        let m = if self.wrap_column == column {
+                            "✓ "
+                        } else {
+                            "  "
+                        };
+                        if ui.button(format!("{}Wrap Column: {}", m, column)).clicked() {
+                            self.wrap_column = column;
+                            ui.close_menu();
+                        }
+                    }
+                    ui.separator();
+                    let m = if self.wrap_as_you_type { "✓ " } else { "  " };
+                    if ui.button(format!("{}Wrap as You Type", m)).clicked() {
+                        self.wrap_as_you_type = !self.wrap_as_you_type;
+                        ui.close_menu();
+                    }
+                });
+                ui.separator();
+                let width = self.indent.width;
+                let groups = [
+                    (
+                        "Convert Case",
+                        vec![
+                            ("UPPERCASE", TextTransform::Upper),
+                            ("lowercase", TextTransform::Lower),
+                            ("Title Case", TextTransform::Title),
+                            ("camelCase", TextTransform::Camel),
+                            ("snake_case", TextTransform::Snake),
+                            ("kebab-case", TextTransform::Kebab),
+                        ],
+                    ),
+                    (
+                        "Whitespace",
+                        vec![
+                            ("Trim Trailing Whitespace", TextTransform::TrimTrailing),
+                            ("Convert Tabs to Spaces", TextTransform::TabsToSpaces(width)),
+                            (
+                                "Convert Indentation to Tabs",
+                                TextTransform::SpacesToTabs(width),
+                            ),
+                        ],
+                    ),
+                    (
+                        "Unicode",
+                        vec![
+                            ("Normalize to NFC", TextTransform::Nfc),
+                            ("Normalize to NFD", TextTransform::Nfd),
+                        ],
+                    ),
+                    (
+                        "Encode / Decode",
+                        vec![
+                            ("Base64 Encode", TextTransform::Base64Encode),
+                            ("Base64 Decode", TextTransform::Base64Decode),
+                            ("URL Encode", TextTransform::UrlEncode),
+                            ("URL Decode", TextTransform::UrlDecode),
+                            ("HTML Entities Encode", TextTransform::HtmlEncode),
+                            ("HTML Entities Decode", TextTransform::HtmlDecode),
+                            ("JSON Escape", TextTransform::JsonEscape),
+                            ("JSON Unescape", TextTransform::JsonUnescape),
+                        ],
+                    ),
+                ];
+                for (title, transforms) in groups {
+                    ui.menu_button(title, |ui| {
+                        for (label, transform) in transforms {
+                            if ui.button(label).clicked() {
+                                self.transform_text(ui.ctx(), transform);
+                                ui.close_menu();
+                            }
+                        }
+                    });
+                }
             });
 
             ui.menu_button("Help", |ui| {
@@ -1139,6 +2950,32 @@ impl Notepad {
     }
 
     fn main_area(&mut self, ctx: &egui::Context, dark: bool) {
+        if let Some(view) = &mut self.diff_view {
+            let colors = ThemeColors::for_mode(self.theme_mode, self.system_dark);
+            let mut action = DiffViewAction::None;
+            egui::CentralPanel::default().show(ctx, |ui| {
+                action = view.show(ui, &self.highlighter, dark, &colors, self.font_size);
+            });
+            match action {
+                DiffViewAction::None => {}
+                DiffViewAction::Close => self.close_diff_view(),
+                DiffViewAction::BufferChanged => {
+                    if let Some(text) = view.buffer_text() {
+                        self.text = text.to_string();
+                        self.reset_carets();
+                        self.save_undo();
+                        self.upd_mod();
+                    }
+                }
+                DiffViewAction::Notify(msg) => self.show_notification(&msg),
+            }
+            return;
+        }
+        if self.revision_view.is_some() {
+            egui::CentralPanel::default().show(ctx, |ui| self.revision_area(ui, dark));
+            return;
+        }
+
         match self.preview_layout {
             PreviewLayout::Hidden => {
                 egui::CentralPanel::default().show(ctx, |ui| self.editor(ui, dark));
@@ -1183,6 +3020,12 @@ impl Notepad {
             egui::Color32::from_rgb(0x33, 0x33, 0x33)
         };
 
+        let minimap_rect = self.minimap.then(|| {
+            let avail = ui.available_rect_before_wrap();
+            let width = MINIMAP_WIDTH.min(avail.width() / 4.0);
+            ui.set_max_width(avail.width() - width);
+            egui::Rect::from_x_y_ranges(avail.right() - width..=avail.right(), avail.y_range())
+        });
         let avail_rect = ui.available_rect_before_wrap();
         let response = ui.interact(avail_rect, egui::Id::new("ed_bg"), egui::Sense::click());
         if response.secondary_clicked() {
@@ -1204,21 +3047,44 @@ impl Notepad {
                 egui::widgets::text_edit::TextEditState::load(ui.ctx(), egui::Id::new("ed"))
             {
                 if let Some(cursor_range) = state.cursor.char_range() {
-                    let cursor_pos = cursor_range.primary.index;
-                    let line_count = self.text[..cursor_pos.min(self.text.len())]
-                        .matches('\n')
-                        .count();
-                    let line_height = self.font_size * 1.5;
-                    self.editor_scroll_offset = (line_count as f32 * line_height).max(0.0);
+                    self.editor_scroll_to
+                        .get_or_insert(cursor_range.primary.index);
                 }
             }
         }
 
+        let dt = ui.input(|i| i.stable_dt.min(0.1));
+        self.update_git_hunks(dt);
+        self.update_blame(dt);
+
         let f = FontId::proportional(self.font_size);
         let f_for_layouter = f.clone();
         let wrap = self.word_wrap;
         let syntax_name = self.current_syntax.clone();
+        let typed = ui.input(|i| i.events.iter().any(|e| matches!(e, egui::Event::Text(_))));
+        let caret_changed = self.multi_cursor_input(ui);
+        let caret_before = self.editor_caret(ui.ctx());
+        let search_highlights = self.search_highlights();
+        let caret_highlights = self.caret_highlights(ui);
+        let bracket_highlights = self.bracket_highlights(caret_before);
+        self.sync_folds();
+        let folded = self.folded_ranges();
+        let chars: Vec<Range<usize>> = folded.iter().map(|(_, r)| r.clone()).collect();
+        let hidden = byte_ranges(&self.text, &chars);
+        let colors = ThemeColors::for_mode(self.theme_mode, self.system_dark);
+        let placeholder = egui::TextFormat {
+            font_id: f.clone(),
+            color: colors.dim_text,
+            background: colors.bracket_match_bg,
+            ..Default::default()
+        };
+        let placeholder_padding = self.font_size * 0.3;
         let highlighter = &self.highlighter;
+        let tab_width = self.indent.width;
+
+        let gutter_width = self.gutter_width(ui.ctx());
+        let mut gutter_left = 0.0;
+        let mut backdrop = None;
 
         let mut scroll = if self.word_wrap {
             egui::ScrollArea::vertical()
@@ -1232,73 +3098,1060 @@ impl Notepad {
             .id_salt("ed")
             .auto_shrink([false, false])
             .show(ui, |ui| {
-                let mut layouter = |ui: &egui::Ui, txt: &str, w: f32| {
-                    let mut job = if let Some(ref name) = syntax_name {
-                        if let Some(syntax) = highlighter.find_syntax_by_name(name) {
-                            highlighter.highlight(txt, syntax, dark, f_for_layouter.size)
+                ui.horizontal_top(|ui| {
+                    gutter_left = ui.cursor().left();
+                    ui.add_space(gutter_width);
+                    let mut layouter = |ui: &egui::Ui, txt: &str, w: f32| {
+                        let mut job = highlighter.highlight_or_plain(
+                            txt,
+                            syntax_name.as_deref(),
+                            dark,
+                            f_for_layouter.size,
+                            text_color,
+                        );
+                        paint_backgrounds(&mut job, &search_highlights);
+                        paint_backgrounds(&mut job, &caret_highlights);
+                        paint_backgrounds(&mut job, &bracket_highlights);
+                        let space = ui.fonts(|f| f.glyph_width(&f_for_layouter, ' '));
+                        let tab_size = egui::epaint::text::TAB_SIZE;
+                        widen_tabs(&mut job, (tab_width as f32 - tab_size as f32) * space);
+                        hide_ranges(&mut job, &hidden, &placeholder, placeholder_padding);
+                        if wrap {
+                            job.wrap.max_width = w;
                         } else {
-                            let mut j = egui::text::LayoutJob::default();
-                            j.append(
-                                txt,
-                                0.0,
-                                egui::TextFormat::simple(f_for_layouter.clone(), text_color),
+                            job.wrap.max_width = f32::INFINITY;
+                            job.wrap.break_anywhere = false;
+                        }
+                        ui.fonts(|f| f.layout_job(job))
+                    };
+
+                    let te = egui::TextEdit::multiline(&mut self.text)
+                        .id(egui::Id::new("ed"))
+                        .font(f)
+                        .desired_width(f32::INFINITY)
+                        .desired_rows(1)
+                        .lock_focus(true)
+                        .margin(EDITOR_MARGIN)
+                        .background_color(egui::Color32::TRANSPARENT)
+                        .layouter(&mut layouter);
+
+                    backdrop = Some(ui.painter().add(egui::Shape::Noop));
+                    let o = te.show(ui);
+                    changed = o.response.changed() || caret_changed;
+
+                    if let Some(range) = self.pending_selection.take() {
+                        let mut state = o.state.clone();
+                        state
+                            .cursor
+                            .set_char_range(Some(egui::text::CCursorRange::two(
+                                egui::text::CCursor::new(range.start),
+                                egui::text::CCursor::new(range.end),
+                            )));
+                        state.store(ui.ctx(), egui::Id::new("ed"));
+                    }
+
+                    o
+                })
+                .inner
+            });
+
+        if changed {
+            self.revision += 1;
+        }
+        if !self.syncing_scroll {
+            let new_offset = scroll_output.state.offset.y;
+            if (new_offset - self.editor_scroll_offset).abs() > 1.0 {
+                self.editor_scroll_offset = new_offset;
+            }
+        }
+        self.syncing_scroll = false;
+        self.editor_scroll_max = scroll_output.inner_rect.height();
+        self.update_carets(
+            ui,
+            &scroll_output.inner,
+            caret_before,
+            scroll_output.inner_rect,
+        );
+        if let Some(index) = self.editor_scroll_to.take() {
+            let o = &scroll_output.inner;
+            let row = o
+                .galley
+                .pos_from_ccursor(egui::text::CCursor::new(index))
+                .translate(o.galley_pos.to_vec2());
+            let view = scroll_output.inner_rect;
+            if row.top() < view.top() || row.bottom() > view.bottom() {
+                let y = row.center().y - view.top() + scroll_output.state.offset.y;
+                self.editor_scroll_offset = (y - view.height() / 2.0).max(0.0);
+                self.syncing_scroll = true;
+            }
+        }
+        let gutter_rect = egui::Rect::from_x_y_ranges(
+            gutter_left..=gutter_left + gutter_width,
+            scroll_output.inner_rect.y_range(),
+        );
+        self.upd_cur(&scroll_output.inner);
+        if let Some(backdrop) = backdrop {
+            let o = &scroll_output.inner;
+            let shape = self.editor_backdrop(ui, o, scroll_output.inner_rect);
+            ui.painter_at(scroll_output.inner_rect).set(backdrop, shape);
+        }
+        self.gutter(ui, &scroll_output.inner, gutter_rect);
+        self.fold_placeholders(ui, &scroll_output.inner, scroll_output.inner_rect, &folded);
+        self.scrollbar_markers(ui, &scroll_output);
+        if let Some(rect) = minimap_rect {
+            self.minimap(ui, &scroll_output, rect, dark);
+            ui.expand_to_include_rect(rect);
+        }
+        self.reveal_carets(ui.ctx());
+        if changed && typed && self.wrap_as_you_type {
+            let multi = self.caret_input.take_or(self.editor_caret(ui.ctx()));
+            let mut wrapped = multi.clone();
+            let (column, tab_width) = (self.wrap_column, self.indent.width);
+            if wrap_typed_lines(&mut wrapped, &mut self.text, column, tab_width) {
+                self.set_multi_cursor(ui.ctx(), wrapped);
+                ui.ctx().request_repaint();
+            } else {
+                self.caret_input.set(multi);
+            }
+        }
+        if changed {
+            self.save_undo();
+            self.modified = true;
+            self.refresh_search();
+            if !self.folds.is_empty() {
+                ui.ctx().request_repaint();
+            }
+        }
+    }
+
+    /// Zero-based lines holding a caret.
+    fn caret_lines(&self, ctx: &egui::Context) -> Vec<usize> {
+        let heads: Vec<usize> = match self.caret_input.cursor() {
+            Some(multi) => multi.carets().iter().map(|c| c.head).collect(),
+            None => vec![self.editor_caret(ctx).head],
+        };
+        caret_lines(&self.text, &heads)
+    }
+
+    /// The editor's background, painted behind the text since the TextEdit's own fill is
+    /// transparent: its frame fill, with the lines holding a caret and the primary caret's
+    /// column tinted.
+    fn editor_backdrop(
+        &self,
+        ui: &egui::Ui,
+        o: &egui::text_edit::TextEditOutput,
+        clip: egui::Rect,
+    ) -> egui::Shape {
+        let visuals = ui.style().interact(&o.response);
+        let frame = (o.response.rect + EDITOR_MARGIN).expand(visuals.expansion);
+        let mut shapes = vec![egui::Shape::rect_filled(
+            frame,
+            visuals.rounding,
+            ui.visuals().extreme_bg_color,
+        )];
+        let colors = ThemeColors::for_mode(self.theme_mode, self.system_dark);
+        let left = o.galley_pos.x;
+        if self.highlight_line {
+            let spans = line_spans(&o.galley);
+            for line in self.caret_lines(ui.ctx()) {
+                let Some(span) = spans.get(line).filter(|s| s.span() > 0.0) else {
+                    continue;
+                };
+                let y = o.galley_pos.y + span.min..=o.galley_pos.y + span.max;
+                shapes.push(egui::Shape::rect_filled(
+                    egui::Rect::from_x_y_ranges(left..=clip.right().max(left), y),
+                    0.0,
+                    colors.current_line_bg,
+                ));
+            }
+        }
+        if self.highlight_column {
+            let head = self.editor_caret(ui.ctx()).head;
+            let x = o
+                .galley
+                .pos_from_ccursor(egui::text::CCursor::new(head))
+                .translate(o.galley_pos.to_vec2())
+                .left();
+            let width = ui.fonts(|f| f.glyph_width(&FontId::proportional(self.font_size), '0'));
+            shapes.push(egui::Shape::rect_filled(
+                egui::Rect::from_x_y_ranges(x..=x + width, clip.y_range()),
+                0.0,
+                colors.current_line_bg,
+            ));
+        }
+        egui::Shape::Vec(shapes)
+    }
+
+    /// Makes the placeholder glyph of each fold clickable to unfold it.
+    fn fold_placeholders(
+        &mut self,
+        ui: &egui::Ui,
+        o: &egui::text_edit::TextEditOutput,
+        clip: egui::Rect,
+        folded: &[(usize, Range<usize>)],
+    ) {
+        for (header, range) in folded {
+            let cursor = o.galley.from_ccursor(egui::text::CCursor::new(range.start));
+            let Some(glyph) = o
+                .galley
+                .rows
+                .get(cursor.rcursor.row)
+                .and_then(|row| row.glyphs.get(cursor.rcursor.column))
+                .filter(|glyph| glyph.chr == FOLD_PLACEHOLDER)
+            else {
+                continue;
+            };
+            let rect = glyph.logical_rect().translate(o.galley_pos.to_vec2());
+            if !clip.intersects(rect) {
+                continue;
+            }
+            let response = ui
+                .interact(rect, egui::Id::new(("fold", *header)), egui::Sense::click())
+                .on_hover_cursor(egui::CursorIcon::PointingHand);
+            if response.clicked() {
+                self.folds.remove(header);
+            }
+        }
+    }
+
+    fn update_occurrences(&mut self) {
+        let needle = self
+            .selection
+            .clone()
+            .and_then(|sel| {
+                let start = byte_offset(&self.text, sel.start);
+                let end = byte_offset(&self.text, sel.end);
+                self.text.get(start..end)
+            })
+            .filter(|s| s.len() <= MAX_OCCURRENCE_LEN && !s.contains('\n') && !s.trim().is_empty());
+        let Some(needle) = needle else {
+            self.occurrences = None;
+            return;
+        };
+        if let Some(o) = &self.occurrences {
+            if o.needle == needle && o.text == self.text {
+                return;
+            }
+        }
+        let ranges: Vec<Range<usize>> = self
+            .text
+            .match_indices(needle)
+            .map(|(i, m)| i..i + m.len())
+            .collect();
+        self.occurrences = Some(Occurrences {
+            needle: needle.to_string(),
+            text: self.text.clone(),
+            chars: char_ranges(&self.text, &ranges),
+            ranges,
+        });
+    }
+
+    /// Paints search results, selection occurrences and git changes along the editor's
+    /// vertical scrollbar. Clicking a marker jumps to it.
+    fn scrollbar_markers(
+        &mut self,
+        ui: &egui::Ui,
+        scroll: &egui::scroll_area::ScrollAreaOutput<egui::text_edit::TextEditOutput>,
+    ) {
+        self.update_occurrences();
+        let view = scroll.inner_rect;
+        let content_height = scroll.content_size.y;
+        if content_height <= 0.0 || view.height() <= 0.0 {
+            return;
+        }
+        let o = &scroll.inner;
+        let spans = line_spans(&o.galley);
+        let galley_top = o.galley_pos.y - (view.top() - scroll.state.offset.y);
+        let scale = view.height() / content_height.max(view.height());
+        let line_y = |line: usize, end: Option<usize>| {
+            let first = spans[line.min(spans.len() - 1)];
+            let last = spans[end.unwrap_or(line).min(spans.len() - 1)];
+            let top = view.top() + (galley_top + first.min) * scale;
+            let bottom = view.top() + (galley_top + last.max) * scale;
+            egui::Rangef::new(top, bottom.max(top + MARKER_HEIGHT))
+        };
+        let colors = ThemeColors::for_mode(self.theme_mode, self.system_dark);
+        let mut markers = Vec::new();
+
+        for hunk in &self.git_hunks {
+            let color = match hunk.kind() {
+                ChangeKind::Added => colors.git_added,
+                ChangeKind::Modified => colors.git_modified,
+                ChangeKind::Deleted => colors.git_deleted,
+            };
+            let end = hunk
+                .new_lines
+                .end
+                .saturating_sub(1)
+                .max(hunk.new_lines.start);
+            let mut y = line_y(hunk.new_lines.start, Some(end));
+            if hunk.kind() == ChangeKind::Deleted {
+                y.max = y.min + MARKER_HEIGHT;
+            }
+            markers.push(ScrollMarker {
+                y,
+                color,
+                target: MarkerTarget::Line(hunk.new_lines.start),
+            });
+        }
+        if let Some(occurrences) = &self.occurrences {
+            let lines = lines_of(&self.text, occurrences.ranges.iter().map(|r| r.start));
+            for (i, line) in lines.into_iter().enumerate() {
+                let y = line_y(line, None);
+                markers.push(ScrollMarker {
+                    y: egui::Rangef::new(y.min, y.min + MARKER_HEIGHT),
+                    color: colors.dim_text,
+                    target: MarkerTarget::Occurrence(i),
+                });
+            }
+        }
+        if self.search_active {
+            for (i, result) in self.search_results.iter().enumerate() {
+                let y = line_y(result.lines.start, None);
+                let color = if i == self.current_search_idx {
+                    colors.search_flash_bg
+                } else {
+                    colors.search_current_bg
+                };
+                markers.push(ScrollMarker {
+                    y: egui::Rangef::new(y.min, y.min + MARKER_HEIGHT),
+                    color,
+                    target: MarkerTarget::Search(i),
+                });
+            }
+        }
+        if markers.is_empty() {
+            return;
+        }
+
+        let strip = egui::Rect::from_x_y_ranges(
+            view.right() - MARKER_STRIP_WIDTH..=view.right(),
+            view.y_range(),
+        );
+        let painter = ui.painter_at(strip);
+        for marker in &markers {
+            let x = match marker.target {
+                MarkerTarget::Line(_) => strip.left()..=strip.left() + MARKER_STRIP_WIDTH / 3.0,
+                _ => strip.left() + MARKER_STRIP_WIDTH / 3.0..=strip.right(),
+            };
+            painter.rect_filled(egui::Rect::from_x_y_ranges(x, marker.y), 0.0, marker.color);
+        }
+
+        let Some(pos) = ui
+            .input(|i| i.pointer.hover_pos())
+            .filter(|p| strip.contains(*p))
+        else {
+            return;
+        };
+        let hit = markers
+            .iter()
+            .rev()
+            .find(|m| m.y.expand(2.0).contains(pos.y))
+            .map(|m| m.target);
+        let Some(target) = hit else {
+            return;
+        };
+        ui.ctx().set_cursor_icon(egui::CursorIcon::PointingHand);
+        if !ui.input(|i| i.pointer.primary_clicked()) {
+            return;
+        }
+        match target {
+            MarkerTarget::Search(i) => {
+                self.current_search_idx = i;
+                self.scroll_to_search_result();
+                self.flash_current_result();
+            }
+            MarkerTarget::Occurrence(i) => {
+                if let Some(range) = self.occurrences.as_ref().and_then(|o| o.chars.get(i)) {
+                    self.editor_scroll_to = Some(range.start);
+                    self.pending_selection = Some(range.clone());
+                }
+            }
+            MarkerTarget::Line(line) => {
+                let index = line_start_char(&self.text, line);
+                self.editor_scroll_to = Some(index);
+                self.pending_selection = Some(index..index);
+            }
+        }
+    }
+
+    /// The cached minimap runs, rebuilt at most every `MINIMAP_DELAY` seconds while the
+    /// text keeps changing.
+    fn minimap_lines(&mut self, ctx: &egui::Context, dark: bool) -> &[MinimapLine] {
+        let tab_width = self.indent.width;
+        let now = ctx.input(|i| i.time);
+        let fresh = self.minimap_cache.as_ref().is_some_and(|c| {
+            let waiting = now - c.built < MINIMAP_DELAY;
+            if c.revision != self.revision && waiting {
+                ctx.request_repaint_after_secs((MINIMAP_DELAY - (now - c.built)) as f32);
+            }
+            c.dark == dark
+                && c.tab_width == tab_width
+                && c.syntax == self.current_syntax
+                && (c.revision == self.revision || waiting)
+        });
+        if !fresh {
+            let colors = ThemeColors::for_mode(self.theme_mode, self.system_dark);
+            let job = self.highlighter.highlight_or_plain(
+                &self.text,
+                self.current_syntax.as_deref(),
+                dark,
+                self.font_size,
+                colors.text,
+            );
+            let columns = (MINIMAP_WIDTH / MINIMAP_CHAR_WIDTH) as usize;
+            self.minimap_cache = Some(MinimapCache {
+                revision: self.revision,
+                built: now,
+                syntax: self.current_syntax.clone(),
+                dark,
+                tab_width,
+                lines: minimap_lines(&job, tab_width, columns),
+            });
+        }
+        self.minimap_cache
+            .as_ref()
+            .map_or(&[], |c| c.lines.as_slice())
+    }
+
+    /// Paints a scaled-down overview of the buffer with the visible part, search results
+    /// and selections marked. Clicking or dragging on it scrolls the editor.
+    fn minimap(
+        &mut self,
+        ui: &egui::Ui,
+        scroll: &egui::scroll_area::ScrollAreaOutput<egui::text_edit::TextEditOutput>,
+        rect: egui::Rect,
+        dark: bool,
+    ) {
+        let view = scroll.inner_rect;
+        let o = &scroll.inner;
+        let spans = line_spans(&o.galley);
+        let galley_top = o.galley_pos.y - (view.top() - scroll.state.offset.y);
+        let offset = scroll.state.offset.y;
+        let max_offset = (scroll.content_size.y - view.height()).max(0.0);
+
+        // One row per visible line; folded lines share the row of their header.
+        let mut rows = Vec::with_capacity(spans.len());
+        let mut visible = Vec::with_capacity(spans.len());
+        for (line, span) in spans.iter().enumerate() {
+            if span.span() > 0.0 || visible.is_empty() {
+                visible.push(line);
+            }
+            rows.push(visible.len() - 1);
+        }
+        let shown = |line: usize| {
+            let span = spans[line];
+            galley_top + span.max > offset && galley_top + span.min < offset + view.height()
+        };
+        let first = visible.iter().position(|&l| shown(l)).unwrap_or(0);
+        let last = visible.iter().rposition(|&l| shown(l)).unwrap_or(first);
+
+        // The minimap scrolls along with the editor when it is taller than its strip.
+        let height = visible.len() as f32 * MINIMAP_LINE_HEIGHT;
+        let slider_height = (last + 1 - first) as f32 * MINIMAP_LINE_HEIGHT;
+        let track = height.min(rect.height()) - slider_height;
+        let fraction = if max_offset > 0.0 {
+            (offset / max_offset).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let top = rect.top() - fraction * (height - rect.height()).max(0.0);
+        let row_y = |row: usize| top + row as f32 * MINIMAP_LINE_HEIGHT;
+        let row_rect = |first: usize, last: usize| {
+            egui::Rect::from_x_y_ranges(
+                rect.x_range(),
+                row_y(first)..=row_y(last) + MINIMAP_LINE_HEIGHT,
+            )
+        };
+        let slider = row_rect(first, last);
+
+        let colors = ThemeColors::for_mode(self.theme_mode, self.system_dark);
+        let painter = ui.painter_at(rect);
+        let response = ui.interact(
+            rect,
+            egui::Id::new("minimap"),
+            egui::Sense::click_and_drag(),
+        );
+        let slider_color = if response.hovered() || response.dragged() {
+            colors.dim_text.gamma_multiply(0.35)
+        } else {
+            colors.dim_text.gamma_multiply(0.2)
+        };
+        painter.rect_filled(slider, 0.0, slider_color);
+
+        let carets: Vec<Caret> = match self.caret_input.cursor() {
+            Some(multi) => multi.carets().to_vec(),
+            None => vec![self.editor_caret(ui.ctx())],
+        };
+        let selected: Vec<Range<usize>> = carets
+            .iter()
+            .filter(|c| !c.is_empty())
+            .map(|c| c.range())
+            .collect();
+        let bytes = byte_ranges(&self.text, &selected);
+        let starts = lines_of(&self.text, bytes.iter().map(|r| r.start));
+        let ends = lines_of(&self.text, bytes.iter().map(|r| r.end));
+        for (start, end) in starts.into_iter().zip(ends) {
+            if let (Some(&first), Some(&last)) = (rows.get(start), rows.get(end)) {
+                painter.rect_filled(row_rect(first, last), 0.0, ui.visuals().selection.bg_fill);
+            }
+        }
+        if self.search_active {
+            for (i, result) in self.search_results.iter().enumerate() {
+                let Some(&row) = rows.get(result.lines.start) else {
+                    continue;
+                };
+                let color = if i == self.current_search_idx {
+                    colors.search_flash_bg
+                } else {
+                    colors.search_current_bg
+                };
+                painter.rect_filled(row_rect(row, row), 0.0, color.gamma_multiply(0.6));
+            }
+        }
+
+        let skip = ((rect.top() - top) / MINIMAP_LINE_HEIGHT) as usize;
+        let count = (rect.height() / MINIMAP_LINE_HEIGHT) as usize + 2;
+        let left = rect.left() + 2.0;
+        let lines = self.minimap_lines(ui.ctx(), dark);
+        for (row, &line) in visible.iter().enumerate().skip(skip).take(count) {
+            let Some(runs) = lines.get(line) else {
+                continue;
+            };
+            let y = row_y(row);
+            for (columns, color) in runs {
+                let x = left + columns.start as f32 * MINIMAP_CHAR_WIDTH
+                    ..=left + columns.end as f32 * MINIMAP_CHAR_WIDTH;
+                painter.rect_filled(
+                    egui::Rect::from_x_y_ranges(x, y..=y + MINIMAP_LINE_HEIGHT * 0.75),
+                    0.0,
+                    *color,
+                );
+            }
+        }
+
+        let Some(pos) = response
+            .interact_pointer_pos()
+            .filter(|_| response.is_pointer_button_down_on())
+        else {
+            self.minimap_grab = None;
+            return;
+        };
+        // Dragging the viewport keeps the grabbed point under the pointer; pressing
+        // elsewhere centers the viewport on it.
+        let grab = *self.minimap_grab.get_or_insert(if slider.contains(pos) {
+            pos.y - slider.top()
+        } else {
+            slider.height() / 2.0
+        });
+        if track > 0.0 {
+            let fraction = ((pos.y - grab - rect.top()) / track).clamp(0.0, 1.0);
+            self.editor_scroll_offset = fraction * max_offset;
+            self.syncing_scroll = true;
+            ui.ctx().request_repaint();
+        }
+    }
+
+    fn gutter(&mut self, ui: &egui::Ui, o: &egui::text_edit::TextEditOutput, rect: egui::Rect) {
+        if rect.width() <= 0.0 {
+            return;
+        }
+        let colors = ThemeColors::for_mode(self.theme_mode, self.system_dark);
+        let painter = ui.painter_at(rect);
+        let spans = line_spans(&o.galley);
+        let origin = o.galley_pos.y;
+        let fold_width = self.fold_gutter_width();
+        let fold_x = rect.right() - fold_width;
+        let marker_x = fold_x - GIT_MARKER_WIDTH - 2.0;
+        let blame_width = self.blame_width();
+        let numbers = egui::Rect::from_x_y_ranges(
+            rect.left() + blame_width
+                ..=rect.left() + blame_width + self.line_number_width(ui.ctx()),
+            rect.y_range(),
+        );
+
+        painter.rect_filled(rect, 0.0, colors.gutter_bg);
+        if numbers.width() > 0.0 {
+            painter.rect_filled(numbers, 0.0, colors.line_number_bg);
+            let font = self.line_number_font();
+            let row_height = ui.fonts(|f| f.row_height(&FontId::proportional(self.font_size)));
+            let current = self.cur_line.saturating_sub(1);
+            let caret_lines = if self.highlight_line {
+                self.caret_lines(ui.ctx())
+            } else {
+                Vec::new()
+            };
+            for (line, span) in spans.iter().enumerate() {
+                let top = origin + span.min;
+                if span.span() <= 0.0 || top > rect.bottom() || top + span.span() < rect.top() {
+                    continue;
+                }
+                let number = self.line_numbers.number(line, current);
+                let color = if caret_lines.binary_search(&line).is_ok() {
+                    painter.rect_filled(
+                        egui::Rect::from_x_y_ranges(numbers.x_range(), top..=origin + span.max),
+                        0.0,
+                        colors.current_line_bg,
+                    );
+                    colors.text
+                } else {
+                    colors.line_number
+                };
+                painter.text(
+                    egui::pos2(
+                        numbers.right() - self.font_size * 0.5,
+                        top + row_height / 2.0,
+                    ),
+                    egui::Align2::RIGHT_CENTER,
+                    number.to_string(),
+                    font.clone(),
+                    color,
+                );
+            }
+        }
+
+        if blame_width > 0.0 {
+            let font = FontId::proportional(self.font_size * 0.85);
+            let blame_painter = ui.painter_at(egui::Rect::from_x_y_ranges(
+                rect.left()..=rect.left() + blame_width - 6.0,
+                rect.y_range(),
+            ));
+            let mut previous: Option<&str> = None;
+            for (line, span) in self.blame.iter().zip(&spans) {
+                if span.span() <= 0.0 {
+                    continue;
+                }
+                if previous == Some(line.commit.as_str()) {
+                    continue;
+                }
+                previous = Some(line.commit.as_str());
+                let top = origin + span.min;
+                if top > rect.bottom() || top + span.span() < rect.top() {
+                    continue;
+                }
+                let label = if line.is_uncommitted() {
+                    "Not committed".to_string()
+                } else {
+                    let date = chrono::DateTime::from_timestamp(line.time, 0)
+                        .map(|t| t.with_timezone(&Local).format("%Y-%m-%d").to_string())
+                        .unwrap_or_default();
+                    format!("{} {} {}", line.short_commit(), date, line.author)
+                };
+                blame_painter.text(
+                    egui::pos2(rect.left() + 2.0, top),
+                    egui::Align2::LEFT_TOP,
+                    label,
+                    font.clone(),
+                    colors.dim_text,
+                );
+            }
+        }
+
+        for hunk in &self.git_hunks {
+            let start = hunk.new_lines.start;
+            match hunk.kind() {
+                ChangeKind::Added | ChangeKind::Modified => {
+                    let (Some(first), Some(last)) =
+                        (spans.get(start), spans.get(hunk.new_lines.end - 1))
+                    else {
+                        continue;
+                    };
+                    let color = if hunk.kind() == ChangeKind::Added {
+                        colors.git_added
+                    } else {
+                        colors.git_modified
+                    };
+                    painter.rect_filled(
+                        egui::Rect::from_x_y_ranges(
+                            marker_x..=marker_x + GIT_MARKER_WIDTH,
+                            origin + first.min..=origin + last.max,
+                        ),
+                        0.0,
+                        color,
+                    );
+                }
+                ChangeKind::Deleted => {
+                    let y = spans
+                        .get(start)
+                        .map(|s| s.min)
+                        .or_else(|| spans.last().map(|s| s.max))
+                        .unwrap_or(0.0)
+                        + origin;
+                    let size = GIT_MARKER_WIDTH + 2.0;
+                    painter.add(egui::Shape::convex_polygon(
+                        vec![
+                            egui::pos2(marker_x, y - size / 2.0),
+                            egui::pos2(marker_x + size, y),
+                            egui::pos2(marker_x, y + size / 2.0),
+                        ],
+                        colors.git_deleted,
+                        egui::Stroke::NONE,
+                    ));
+                }
+            }
+        }
+
+        // Fold markers: always for folded regions, for open ones while hovering.
+        let regions = self.fold_regions();
+        let hovered = ui.rect_contains_pointer(rect);
+        let size = self.font_size * 0.3;
+        for region in &regions {
+            let folded = self.folds.contains(&region.header);
+            let Some(span) = spans.get(region.header).filter(|s| s.span() > 0.0) else {
+                continue;
+            };
+            if !folded && !hovered {
+                continue;
+            }
+            let center = egui::pos2(
+                fold_x + fold_width / 2.0,
+                origin + span.min + self.font_size * 0.65,
+            );
+            let points = if folded {
+                vec![
+                    center + egui::vec2(-size / 2.0, -size),
+                    center + egui::vec2(size, 0.0),
+                    center + egui::vec2(-size / 2.0, size),
+                ]
+            } else {
+                vec![
+                    center + egui::vec2(-size, -size / 2.0),
+                    center + egui::vec2(size, -size / 2.0),
+                    center + egui::vec2(0.0, size),
+                ]
+            };
+            let color = if folded {
+                colors.accent
+            } else {
+                colors.dim_text
+            };
+            painter.add(egui::Shape::convex_polygon(
+                points,
+                color,
+                egui::Stroke::NONE,
+            ));
+        }
+
+        let response = ui.interact(
+            rect,
+            egui::Id::new("ed_gutter"),
+            egui::Sense::click_and_drag(),
+        );
+        let line_at = |y: f32| line_at(&spans, y - origin);
+
+        // Pressing on the line numbers selects lines, dragging extends the selection.
+        let pressed = response
+            .interact_pointer_pos()
+            .filter(|_| response.is_pointer_button_down_on());
+        match (pressed, self.line_drag_anchor) {
+            (Some(pos), anchor) if anchor.is_some() || numbers.x_range().contains(pos.x) => {
+                let line = line_at(pos.y);
+                let anchor = *self.line_drag_anchor.get_or_insert(line);
+                // The start of the next visible line, past any fold below `line`.
+                let line_end = |line: usize| {
+                    let next = (line + 1..spans.len())
+                        .find(|&l| spans[l].span() > 0.0)
+                        .unwrap_or(spans.len());
+                    line_start_char(&self.text, next)
+                };
+                let caret = if line >= anchor {
+                    Caret::new(line_start_char(&self.text, anchor), line_end(line))
+                } else {
+                    Caret::new(line_end(anchor), line_start_char(&self.text, line))
+                };
+                self.set_multi_cursor(ui.ctx(), MultiCursor::new(vec![caret], caret));
+                ui.memory_mut(|m| m.request_focus(egui::Id::new("ed")));
+                return;
+            }
+            (None, _) => self.line_drag_anchor = None,
+            _ => {}
+        }
+        let in_blame = |pos: egui::Pos2| pos.x < rect.left() + blame_width;
+        if let Some(pos) = response.hover_pos().filter(|p| in_blame(*p)) {
+            if let Some(line) = self.blame.get(line_at(pos.y)) {
+                let text = format!("{}\n{}\n\n{}", line.commit, line.author, line.summary);
+                response.clone().on_hover_text_at_pointer(text);
+            }
+        }
+        if let Some(pos) = response
+            .interact_pointer_pos()
+            .filter(|_| response.clicked())
+        {
+            if numbers.x_range().contains(pos.x) {
+                return;
+            }
+            let line = line_at(pos.y);
+            if pos.x >= fold_x {
+                self.toggle_fold(ui.ctx(), line);
+                return;
+            }
+            if in_blame(pos) {
+                if let Some(commit) = self.blame.get(line).map(|b| b.commit.clone()) {
+                    if !self.file_history_panel {
+                        self.toggle_file_history();
+                    }
+                    self.selected_commit = self.file_commits.iter().position(|c| c.hash == commit);
+                }
+                return;
+            }
+            let hunk = self
+                .git_hunks
+                .iter()
+                .position(|h| h.marks_line(line, spans.len()));
+            self.git_popup = hunk.map(|hunk| GitPopup {
+                hunk,
+                pos,
+                opened: ui.ctx().cumulative_pass_nr(),
+            });
+        }
+    }
+
+    fn git_hunk_popup(&mut self, ctx: &egui::Context) {
+        let Some(popup) = self.git_popup else {
+            return;
+        };
+        let (Some(base), Some(hunk)) = (&self.git_base, self.git_hunks.get(popup.hunk)) else {
+            self.git_popup = None;
+            return;
+        };
+        let colors = ThemeColors::for_mode(self.theme_mode, self.system_dark);
+        let font = FontId::proportional(self.font_size);
+        let old_lines = hunk.old_text(base);
+        let title = hunk.title();
+
+        let mut revert = false;
+        let mut close = false;
+        let area = egui::Area::new(egui::Id::new("git_hunk_popup"))
+            .order(egui::Order::Foreground)
+            .fixed_pos(popup.pos + egui::vec2(8.0, 0.0))
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.set_max_width(ctx.screen_rect().width() * 0.6);
+                    ui.horizontal(|ui| {
+                        ui.label(RichText::new(title).strong());
+                        if ui.button("Revert Hunk").clicked() {
+                            revert = true;
+                        }
+                        if ui.button("X").clicked() {
+                            close = true;
+                        }
+                    });
+                    ui.separator();
+                    if old_lines.is_empty() {
+                        ui.label(RichText::new("These lines are not in HEAD.").weak());
+                        return;
+                    }
+                    egui::ScrollArea::vertical()
+                        .max_height(240.0)
+                        .show(ui, |ui| {
+                            ui.spacing_mut().item_spacing.y = 0.0;
+                            for line in &old_lines {
+                                ui.label(
+                                    RichText::new(*line)
+                                        .font(font.clone())
+                                        .color(colors.text)
+                                        .background_color(colors.diff_removed_bg),
+                                );
+                            }
+                        });
+                });
+            });
+
+        let clicked_outside =
+            area.response.clicked_elsewhere() && ctx.cumulative_pass_nr() != popup.opened;
+        if close || clicked_outside || ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+            self.git_popup = None;
+        }
+        if revert {
+            self.revert_git_hunk(popup.hunk);
+        }
+    }
+
+    fn revision_area(&mut self, ui: &mut egui::Ui, dark: bool) {
+        let Some(view) = &self.revision_view else {
+            return;
+        };
+        let mut close = ui.input(|i| i.key_pressed(egui::Key::Escape));
+        let mut compare = false;
+        ui.horizontal(|ui| {
+            if ui.button("X").clicked() {
+                close = true;
+            }
+            ui.label(RichText::new(&view.title).strong());
+            ui.label(RichText::new("Read-only").weak());
+            ui.separator();
+            if ui.button("Compare with Current").clicked() {
+                compare = true;
+            }
+        });
+        ui.separator();
+
+        let colors = ThemeColors::for_mode(self.theme_mode, self.system_dark);
+        let font_size = self.font_size;
+        let highlighter = &self.highlighter;
+        let mut layouter = |ui: &egui::Ui, txt: &str, _w: f32| {
+            let job = highlighter.highlight_or_plain(
+                txt,
+                view.syntax.as_deref(),
+                dark,
+                font_size,
+                colors.text,
+            );
+            ui.fonts(|f| f.layout_job(job))
+        };
+        egui::ScrollArea::both()
+            .id_salt("revision")
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                ui.add(
+                    egui::TextEdit::multiline(&mut view.text.as_str())
+                        .id(egui::Id::new("revision_text"))
+                        .desired_width(f32::INFINITY)
+                        .layouter(&mut layouter),
+                );
+            });
+
+        if compare {
+            if let Some(view) = self.revision_view.take() {
+                self.diff_view = Some(DiffView::new(
+                    format!("Compare with Revision: {}", view.title),
+                    DiffPane::new(view.title, view.text).syntax(view.syntax),
+                    self.buffer_pane(),
+                ));
+            }
+        }
+        if close {
+            self.revision_view = None;
+            self.editor_focus_request = true;
+        }
+    }
+
+    fn file_history_side_panel(&mut self, ctx: &egui::Context) {
+        let mut close = false;
+        let mut open = None;
+        let mut compare = None;
+        egui::SidePanel::right("file_history_panel")
+            .resizable(true)
+            .default_width(280.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button("X").clicked() {
+                        close = true;
+                    }
+                    ui.label(RichText::new("File History").strong());
+                    if ui.button("⟳").on_hover_text("Refresh").clicked() {
+                        self.refresh_file_history();
+                    }
+                });
+                ui.separator();
+                if self.file_commits.is_empty() {
+                    ui.label(RichText::new("No commits for this file.").weak());
+                    return;
+                }
+                egui::ScrollArea::vertical()
+                    .id_salt("file_history_list")
+                    .auto_shrink([false, false])
+                    .show(ui, |ui| {
+                        for (i, commit) in self.file_commits.iter().enumerate() {
+                            let selected = self.selected_commit == Some(i);
+                            let response = ui.selectable_label(
+                                selected,
+                                format!("{}  {}  {}", commit.short, commit.date, commit.author),
                             );
-                            j
+                            if response.clicked() {
+                                self.selected_commit = Some(i);
+                            }
+                            ui.label(RichText::new(&commit.subject).size(11.0).weak());
+                            if selected {
+                                ui.horizontal(|ui| {
+                                    if ui.small_button("Open").clicked() {
+                                        open = Some(i);
+                                    }
+                                    if ui.small_button("Compare").clicked() {
+                                        compare = Some(i);
+                                    }
+                                });
+                            }
+                            ui.separator();
                         }
-                    } else {
-                        let mut j = egui::text::LayoutJob::default();
-                        j.append(
-                            txt,
-                            0.0,
-                            egui::TextFormat::simple(f_for_layouter.clone(), text_color),
-                        );
-                        j
-                    };
-                    if wrap {
-                        job.wrap.max_width = w;
-                    } else {
-                        job.wrap.max_width = f32::INFINITY;
-                        job.wrap.break_anywhere = false;
-                    }
-                    ui.fonts(|f| f.layout_job(job))
-                };
+                    });
+            });
 
-                let te = egui::TextEdit::multiline(&mut self.text)
-                    .id(egui::Id::new("ed"))
-                    .font(f)
-                    .desired_width(f32::INFINITY)
-                    .desired_rows(1)
-                    .lock_focus(true)
-                    .layouter(&mut layouter);
-
-                let o = te.show(ui);
-                changed = o.response.changed();
-
-                if let Some(cursor_pos) = self.search_cursor_pos.take() {
-                    let mut state = o.state.clone();
-                    state
-                        .cursor
-                        .set_char_range(Some(egui::text::CCursorRange::one(
-                            egui::text::CCursor::new(cursor_pos),
-                        )));
-                    state.store(ui.ctx(), egui::Id::new("ed"));
-                }
+        if close {
+            self.file_history_panel = false;
+        }
+        if let Some(i) = open {
+            self.open_revision(i);
+        }
+        if let Some(i) = compare {
+            self.compare_revision(i);
+        }
+    }
 
-                o
+    fn history_side_panel(&mut self, ctx: &egui::Context) {
+        let mut close = false;
+        let mut compare = None;
+        let mut restore = None;
+        egui::SidePanel::right("history_panel")
+            .resizable(true)
+            .default_width(260.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    if ui.button("X").clicked() {
+                        close = true;
+                    }
+                    ui.label(RichText::new("Local History").strong());
+                    if ui.button("⟳").on_hover_text("Refresh").clicked() {
+                        self.refresh_history();
+                    }
+                });
+                ui.separator();
+                if self.file.is_none() {
+                    ui.label(RichText::new("Save the file to start recording history.").weak());
+                    return;
+                }
+                if self.history_entries.is_empty() {
+                    ui.label(RichText::new("No snapshots yet.").weak());
+                    return;
+                }
+                egui::ScrollArea::vertical()
+                    .id_salt("history_list")
+                    .auto_shrink([false, false])
+                    .show(ui, |ui| {
+                        for (i, snapshot) in self.history_entries.iter().enumerate() {
+                            ui.horizontal(|ui| {
+                                ui.label(snapshot.time.format("%Y-%m-%d %H:%M:%S").to_string());
+                                ui.label(
+                                    RichText::new(format!(
+                                        "{} · {:.1} KB",
+                                        snapshot.kind.name(),
+                                        snapshot.size as f32 / 1024.0
+                                    ))
+                                    .size(11.0)
+                                    .weak(),
+                                );
+                            });
+                            ui.horizontal(|ui| {
+                                if ui.small_button("Compare").clicked() {
+                                    compare = Some(i);
+                                }
+                                if ui.small_button("Restore").clicked() {
+                                    restore = Some(i);
+                                }
+                            });
+                            ui.separator();
+                        }
+                    });
             });
 
-        if !self.syncing_scroll {
-            let new_offset = scroll_output.state.offset.y;
-            if (new_offset - self.editor_scroll_offset).abs() > 1.0 {
-                self.editor_scroll_offset = new_offset;
-            }
+        if close {
+            self.history_panel = false;
         }
-        self.syncing_scroll = false;
-        self.editor_scroll_max = scroll_output.inner_rect.height();
-        self.upd_cur(&scroll_output.inner);
-        if changed {
-            self.save_undo();
-            self.modified = true;
+        if let Some(snapshot) = compare.and_then(|i| self.history_entries.get(i).cloned()) {
+            self.compare_with_snapshot(&snapshot);
+        }
+        if let Some(snapshot) = restore.and_then(|i| self.history_entries.get(i).cloned()) {
+            self.restore_snapshot(&snapshot);
         }
     }
 
@@ -1336,9 +4189,19 @@ impl Notepad {
                 ui.separator();
                 ui.label(format!("Preview: {}", self.preview_layout.name()));
             }
+            if let Some(count) = self.replace_count {
+                ui.separator();
+                ui.label(replaced_message(count));
+            }
             ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
                 ui.label("UTF-8");
                 ui.add_space(8.0);
+                if self.indent.spaces {
+                    ui.label(format!("Spaces: {}", self.indent.width));
+                } else {
+                    ui.label(format!("Tab Width: {}", self.indent.width));
+                }
+                ui.add_space(8.0);
                 ui.label(format!("{}%", self.zoom));
             });
         });
@@ -1477,6 +4340,7 @@ impl Notepad {
         let mut sv = false;
         let mut ns = false;
         let mut ca = false;
+        let mut sc = false;
         egui::Area::new(egui::Id::new("cbg"))
             .order(egui::Order::Foreground)
             .interactable(true)
@@ -1513,8 +4377,16 @@ impl Notepad {
                             ca = true;
                         }
                     });
+                    ui.add_space(4.0);
+                    if ui.button("Show Changes").clicked() {
+                        sc = true;
+                    }
                 });
             });
+        if sc {
+            self.confirm = false;
+            self.compare_with_saved();
+        }
         if sv {
             self.conf_save();
         }
@@ -1527,6 +4399,566 @@ impl Notepad {
         }
     }
 
+    fn editor_caret(&self, ctx: &egui::Context) -> Caret {
+        egui::widgets::text_edit::TextEditState::load(ctx, egui::Id::new("ed"))
+            .and_then(|s| s.cursor.char_range())
+            .map_or(Caret::at(0), |r| {
+                Caret::new(r.secondary.index, r.primary.index)
+            })
+    }
+
+    /// Makes `multi`'s primary caret the editor cursor, leaving multi-caret mode when
+    /// only one caret is left.
+    fn set_multi_cursor(&mut self, ctx: &egui::Context, multi: MultiCursor) {
+        let id = egui::Id::new("ed");
+        let primary = multi.primary();
+        let mut state = egui::widgets::text_edit::TextEditState::load(ctx, id).unwrap_or_default();
+        state
+            .cursor
+            .set_char_range(Some(egui::text::CCursorRange::two(
+                egui::text::CCursor::new(primary.anchor),
+                egui::text::CCursor::new(primary.head),
+            )));
+        state.store(ctx, id);
+        self.editor_scroll_to = Some(primary.head);
+        self.caret_input.set(multi);
+    }
+
+    fn caret_command(&mut self, ctx: &egui::Context, command: CaretCommand) {
+        let primary = self.editor_caret(ctx);
+        let multi = self.caret_input.command(&self.text, primary, command);
+        self.set_multi_cursor(ctx, multi);
+        self.active_tab = EditorTab::Editor;
+        self.editor_focus_request = true;
+    }
+
+    /// Handles the caret commands and, while several carets exist, the editing keys that
+    /// the single-cursor `TextEdit` would otherwise apply to the primary caret only.
+    /// Returns whether the text changed.
+    fn multi_cursor_input(&mut self, ui: &egui::Ui) -> bool {
+        if !ui.memory(|m| m.has_focus(egui::Id::new("ed"))) {
+            return false;
+        }
+        let ctx = ui.ctx();
+        let events = ui.input(|i| i.events.clone());
+        let mut handled = vec![false; events.len()];
+        let mut changed = false;
+
+        let paired = edits_pairs(&events);
+        let (pairs, literals) = if paired {
+            (self.auto_pairs(), self.literal_ranges())
+        } else {
+            (Vec::new(), Vec::new())
+        };
+        // Enter and Tab indent, so with a single caret the whole frame goes through the
+        // caret edits to keep the events in order.
+        if indents(&events) || paired {
+            self.caret_input.begin(self.editor_caret(ctx));
+        }
+
+        for (i, event) in events.iter().enumerate() {
+            if let Some(key) = caret_key(event) {
+                match key {
+                    CaretKey::Line(edit) => self.line_command(ctx, LineCommand::Edit(edit)),
+                    CaretKey::Split => self.line_command(ctx, LineCommand::Split),
+                    CaretKey::Reflow => self.reflow(ctx),
+                    CaretKey::Command(command) => self.caret_command(ctx, command),
+                }
+                handled[i] = true;
+                continue;
+            }
+            let primary = self.editor_caret(ctx);
+            let (highlighter, syntax) = (&self.highlighter, self.current_syntax.as_deref());
+            let cx = CaretContext {
+                indent: self.indent,
+                pairs: &pairs,
+                literals: &literals,
+                opens: &|line| highlighter.opens_block(syntax, line),
+            };
+            let text = &mut self.text;
+            if let Some(edited) = self.caret_input.event(ctx, text, event, primary, &cx) {
+                changed |= edited;
+                handled[i] = true;
+            }
+        }
+
+        if handled.contains(&true) {
+            let mut index = 0;
+            ui.input_mut(|i| {
+                i.events.retain(|_| {
+                    index += 1;
+                    !handled[index - 1]
+                })
+            });
+            if let Some(multi) = self.caret_input.take() {
+                self.set_multi_cursor(ctx, multi);
+            }
+        }
+        if changed {
+            self.revision += 1;
+        }
+        changed
+    }
+
+    /// Background ranges for the selections of all carets but the primary one, which the
+    /// `TextEdit` paints itself.
+    fn caret_highlights(&self, ui: &egui::Ui) -> Vec<(Range<usize>, egui::Color32)> {
+        let Some(multi) = self.caret_input.cursor() else {
+            return Vec::new();
+        };
+        let primary = multi.primary();
+        let ranges: Vec<Range<usize>> = multi
+            .carets()
+            .iter()
+            .filter(|c| !c.is_empty() && **c != primary)
+            .map(|c| c.range())
+            .collect();
+        let color = ui.visuals().selection.bg_fill;
+        byte_ranges(&self.text, &ranges)
+            .into_iter()
+            .map(|r| (r, color))
+            .collect()
+    }
+
+    /// Leaves multi-caret mode when the cursor was moved by the mouse, except for
+    /// Alt+click which adds a caret and Alt+drag which selects a column. Then paints the
+    /// secondary carets.
+    fn update_carets(
+        &mut self,
+        ui: &egui::Ui,
+        o: &egui::text_edit::TextEditOutput,
+        before: Caret,
+        clip: egui::Rect,
+    ) {
+        let after = o
+            .state
+            .cursor
+            .char_range()
+            .map(|r| Caret::new(r.secondary.index, r.primary.index));
+        let column_drag = o.response.dragged()
+            && ui.input(|i| i.modifiers.alt && i.pointer.is_decidedly_dragging());
+        let drag = ui.input(|i| Some((i.pointer.press_origin()?, i.pointer.interact_pos()?)));
+        if let Some((from, to)) = drag.filter(|_| column_drag) {
+            let multi = column_carets(&o.galley, o.galley_pos, from, to);
+            self.set_multi_cursor(ui.ctx(), multi);
+        } else if let Some(after) = after.filter(|a| *a != before) {
+            if ui.input(|i| i.modifiers.alt && i.pointer.primary_pressed()) {
+                let mut multi = self.caret_input.take_or(before);
+                multi.add(after);
+                self.caret_input.set(multi);
+            } else {
+                self.caret_input.take();
+            }
+        }
+
+        let Some(multi) = self.caret_input.cursor() else {
+            return;
+        };
+        let painter = ui.painter_at(clip);
+        let stroke = ui.visuals().text_cursor.stroke;
+        let primary = multi.primary();
+        for caret in multi.carets().iter().filter(|c| **c != primary) {
+            let rect = o
+                .galley
+                .pos_from_ccursor(egui::text::CCursor::new(caret.head))
+                .translate(o.galley_pos.to_vec2());
+            painter.line_segment([rect.center_top(), rect.center_bottom()], stroke);
+        }
+    }
+
+    fn fold_regions(&mut self) -> Vec<FoldRegion> {
+        let width = self.indent.width;
+        let fresh = self
+            .fold_cache
+            .as_ref()
+            .is_some_and(|(revision, syntax, w, _)| {
+                *w == width && *syntax == self.current_syntax && *revision == self.revision
+            });
+        if !fresh {
+            let literals = self.literal_ranges();
+            let regions = fold_regions(&self.text, self.is_markdown(), width, |i| {
+                in_ranges(&literals, i)
+            });
+            self.fold_cache = Some((self.revision, self.current_syntax.clone(), width, regions));
+        }
+        self.fold_cache
+            .as_ref()
+            .map(|(.., regions)| regions.clone())
+            .unwrap_or_default()
+    }
+
+    /// Moves the folds along with edits made since they were last synced, dropping those
+    /// that no longer start a region.
+    fn sync_folds(&mut self) {
+        if self.fold_revision == self.revision {
+            return;
+        }
+        if !self.folds.is_empty() {
+            self.folds = remap_lines(&self.fold_source, &self.text, &self.folds);
+            let regions = self.fold_regions();
+            self.folds
+                .retain(|header| regions.iter().any(|r| r.header == *header));
+        }
+        self.fold_source.clone_from(&self.text);
+        self.fold_revision = self.revision;
+    }
+
+    /// The char range hidden by each outermost fold, with its header line.
+    fn folded_ranges(&mut self) -> Vec<(usize, Range<usize>)> {
+        if self.folds.is_empty() {
+            return Vec::new();
+        }
+        let regions = self.fold_regions();
+        let hidden = hidden_ranges(&self.text, &regions, &self.folds);
+        let bytes: Vec<Range<usize>> = hidden.iter().map(|(_, r)| r.clone()).collect();
+        hidden
+            .iter()
+            .map(|(header, _)| *header)
+            .zip(char_ranges(&self.text, &bytes))
+            .collect()
+    }
+
+    fn fold_command(&mut self, ctx: &egui::Context, command: FoldCommand) {
+        self.sync_folds();
+        let regions = self.fold_regions();
+        let line = self.cur_line.saturating_sub(1);
+        match command {
+            FoldCommand::Fold => {
+                let innermost = regions
+                    .iter()
+                    .filter(|r| r.contains(line) && !self.folds.contains(&r.header))
+                    .max_by_key(|r| r.header);
+                if let Some(region) = innermost {
+                    self.folds.insert(region.header);
+                }
+            }
+            FoldCommand::Unfold => {
+                let innermost = regions
+                    .iter()
+                    .filter(|r| r.contains(line) && self.folds.contains(&r.header))
+                    .max_by_key(|r| r.header);
+                if let Some(region) = innermost {
+                    self.folds.remove(&region.header);
+                }
+            }
+            FoldCommand::FoldAll => self.folds = regions.iter().map(|r| r.header).collect(),
+            FoldCommand::UnfoldAll => self.folds.clear(),
+            FoldCommand::Level(level) => {
+                self.folds = regions
+                    .iter()
+                    .filter(|r| r.level >= level)
+                    .map(|r| r.header)
+                    .collect();
+            }
+        }
+        self.hide_carets(ctx);
+    }
+
+    fn toggle_fold(&mut self, ctx: &egui::Context, line: usize) {
+        self.sync_folds();
+        let regions = self.fold_regions();
+        if regions.iter().any(|r| r.header == line) && !self.folds.remove(&line) {
+            self.folds.insert(line);
+            self.hide_carets(ctx);
+        }
+    }
+
+    /// Moves carets that a fold has hidden to the end of the fold's header line.
+    fn hide_carets(&mut self, ctx: &egui::Context) {
+        let hidden = self.folded_ranges();
+        let hide = |pos: usize| {
+            hidden
+                .iter()
+                .find(|(_, r)| r.start < pos && pos <= r.end)
+                .map_or(pos, |(_, r)| r.start)
+        };
+        let multi = self.caret_input.take_or(self.editor_caret(ctx));
+        let carets = multi
+            .carets()
+            .iter()
+            .map(|c| Caret::new(hide(c.anchor), hide(c.head)))
+            .collect();
+        let primary = multi.primary();
+        let primary = Caret::new(hide(primary.anchor), hide(primary.head));
+        self.set_multi_cursor(ctx, MultiCursor::new(carets, primary));
+        self.active_tab = EditorTab::Editor;
+        self.editor_focus_request = true;
+    }
+
+    /// Unfolds the folds that a caret has moved into.
+    fn reveal_carets(&mut self, ctx: &egui::Context) {
+        let mut heads = vec![self.editor_caret(ctx).head];
+        if let Some(multi) = self.caret_input.cursor() {
+            heads.extend(multi.carets().iter().map(|c| c.head));
+        }
+        loop {
+            let hidden = self.folded_ranges();
+            let revealed: Vec<usize> = hidden
+                .iter()
+                .filter(|(_, r)| heads.iter().any(|&h| r.start < h && h <= r.end))
+                .map(|(header, _)| *header)
+                .collect();
+            if revealed.is_empty() {
+                break;
+            }
+            for header in revealed {
+                self.folds.remove(&header);
+            }
+        }
+    }
+
+    fn remember_folds(&mut self) {
+        let Some(path) = self.file.clone() else {
+            return;
+        };
+        self.sync_folds();
+        self.saved_folds.retain(|(p, _)| *p != path);
+        if !self.folds.is_empty() {
+            self.saved_folds
+                .insert(0, (path, self.folds.iter().copied().collect()));
+            self.saved_folds.truncate(MAX_FOLDED_FILES);
+        }
+    }
+
+    fn restore_folds(&mut self) {
+        self.folds.clear();
+        self.fold_source.clone_from(&self.text);
+        self.fold_revision = self.revision;
+        let saved = self
+            .saved_folds
+            .iter()
+            .find(|(p, _)| Some(p) == self.file.as_ref())
+            .map(|(_, lines)| lines.clone());
+        if let Some(lines) = saved {
+            let regions = self.fold_regions();
+            self.folds = lines
+                .into_iter()
+                .filter(|line| regions.iter().any(|r| r.header == *line))
+                .collect();
+        }
+    }
+
+    fn literal_ranges(&mut self) -> Vec<Range<usize>> {
+        let fresh = self
+            .literal_cache
+            .as_ref()
+            .is_some_and(|(revision, syntax, _)| {
+                *syntax == self.current_syntax && *revision == self.revision
+            });
+        if !fresh {
+            let bytes = self
+                .highlighter
+                .literal_ranges(&self.text, self.current_syntax.as_deref());
+            let chars = char_ranges(&self.text, &bytes);
+            self.literal_cache = Some((self.revision, self.current_syntax.clone(), chars));
+        }
+        self.literal_cache
+            .as_ref()
+            .map(|(_, _, ranges)| ranges.clone())
+            .unwrap_or_default()
+    }
+
+    fn auto_pairs(&mut self) -> Pairs {
+        if self
+            .pairs_cache
+            .as_ref()
+            .is_none_or(|(syntax, _)| *syntax != self.current_syntax)
+        {
+            let pairs = self.highlighter.auto_pairs(self.current_syntax.as_deref());
+            self.pairs_cache = Some((self.current_syntax.clone(), pairs));
+        }
+        self.pairs_cache
+            .as_ref()
+            .map(|(_, pairs)| pairs.clone())
+            .unwrap_or_default()
+    }
+
+    /// The bracket next to the caret and its match, outside strings and comments.
+    fn bracket_pair(&mut self, caret: Caret) -> Option<(usize, usize)> {
+        let chars: Vec<char> = self.text.chars().collect();
+        let is_bracket = |i: Option<usize>| {
+            i.and_then(|i| chars.get(i))
+                .is_some_and(|c| BRACKETS.iter().any(|&(o, cl)| *c == o || *c == cl))
+        };
+        if !is_bracket(Some(caret.head)) && !is_bracket(caret.head.checked_sub(1)) {
+            return None;
+        }
+        let literals = self.literal_ranges();
+        bracket_near(&chars, caret.head, |i| in_ranges(&literals, i))
+    }
+
+    fn bracket_highlights(&mut self, caret: Caret) -> Vec<(Range<usize>, egui::Color32)> {
+        let Some((a, b)) = self.bracket_pair(caret) else {
+            return Vec::new();
+        };
+        let color = ThemeColors::for_mode(self.theme_mode, self.system_dark).bracket_match_bg;
+        let ranges = [a.min(b)..a.min(b) + 1, a.max(b)..a.max(b) + 1];
+        byte_ranges(&self.text, &ranges)
+            .into_iter()
+            .map(|r| (r, color))
+            .collect()
+    }
+
+    fn jump_to_bracket(&mut self, ctx: &egui::Context) {
+        let caret = self.editor_caret(ctx);
+        if let Some((_, target)) = self.bracket_pair(caret) {
+            let caret = Caret::at(target);
+            self.set_multi_cursor(ctx, MultiCursor::new(vec![caret], caret));
+            self.active_tab = EditorTab::Editor;
+            self.editor_focus_request = true;
+        }
+    }
+
+    /// Applies `edit` to the editor's carets as a single undo step. `edit` returns
+    /// whether it changed the text.
+    fn edit_carets(
+        &mut self,
+        ctx: &egui::Context,
+        edit: impl FnOnce(&mut MultiCursor, &mut String) -> bool,
+    ) {
+        let mut multi = self.caret_input.take_or(self.editor_caret(ctx));
+        let changed = edit(&mut multi, &mut self.text);
+        self.set_multi_cursor(ctx, multi);
+        if changed {
+            self.save_undo();
+            self.modified = true;
+            self.refresh_search();
+        }
+        self.active_tab = EditorTab::Editor;
+        self.editor_focus_request = true;
+    }
+
+    fn line_command(&mut self, ctx: &egui::Context, command: LineCommand) {
+        self.edit_carets(ctx, |multi, text| match command {
+            LineCommand::Edit(edit) => edit_line_blocks(multi, text, edit),
+            LineCommand::Split => split_lines(multi, text),
+            LineCommand::Transform(transform) => transform_lines(multi, text, transform),
+        });
+    }
+
+    fn reflow(&mut self, ctx: &egui::Context) {
+        let (column, tab_width) = (self.wrap_column, self.indent.width);
+        self.edit_carets(ctx, |multi, text| {
+            reflow_paragraphs(multi, text, column, tab_width)
+        });
+    }
+
+    fn transform_text(&mut self, ctx: &egui::Context, transform: TextTransform) {
+        let mut error = None;
+        self.edit_carets(ctx, |multi, text| {
+            transform_selections(multi, text, transform).unwrap_or_else(|e| {
+                error = Some(e);
+                false
+            })
+        });
+        if let Some(e) = error {
+            self.show_notification(&e);
+        }
+    }
+
+    fn toggle_comment(&mut self, ctx: &egui::Context, block: bool) {
+        let tokens = self
+            .highlighter
+            .comment_tokens(self.current_syntax.as_deref());
+        if tokens.line.is_none() && tokens.block.is_none() {
+            let lang = self.current_syntax.as_deref().unwrap_or("Plain Text");
+            self.show_notification(&format!("{} has no comments", lang));
+            return;
+        }
+        self.edit_carets(ctx, |multi, text| {
+            if block && tokens.block.is_some() {
+                toggle_block_comment(multi, text, &tokens)
+            } else {
+                toggle_line_comment(multi, text, &tokens)
+            }
+        });
+    }
+
+    fn open_goto_line(&mut self) {
+        self.goto_line = true;
+        self.goto_focus = true;
+        self.goto_input.clear();
+    }
+
+    fn go_to(&mut self, target: GotoTarget) {
+        let start = line_start_char(&self.text, target.line);
+        let line_len = self
+            .text
+            .split('\n')
+            .nth(target.line)
+            .map_or(0, |l| l.trim_end_matches('\r').chars().count());
+        let index = start + target.column.unwrap_or(0).min(line_len);
+        self.pending_selection = Some(index..index);
+        self.editor_scroll_to = Some(index);
+        self.active_tab = EditorTab::Editor;
+        self.editor_focus_request = true;
+    }
+
+    fn goto_line_md(&mut self, ctx: &egui::Context) {
+        let line_count = self.text.split('\n').count();
+        let current = self.cur_line.saturating_sub(1);
+        let mut go = false;
+        let mut close = false;
+
+        egui::Area::new(egui::Id::new("gotodlg"))
+            .order(egui::Order::Foreground)
+            .anchor(egui::Align2::CENTER_TOP, egui::vec2(0.0, 60.0))
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.set_min_width(320.0);
+                    ui.label(RichText::new("Go to Line").size(14.0).strong());
+                    ui.add_space(6.0);
+                    let response = ui.add(
+                        egui::TextEdit::singleline(&mut self.goto_input)
+                            .id(egui::Id::new("goto_input"))
+                            .hint_text("line, line:column, +N, -N or N%")
+                            .desired_width(f32::INFINITY),
+                    );
+                    if self.goto_focus {
+                        response.request_focus();
+                        self.goto_focus = false;
+                    }
+                    if response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                        go = true;
+                    }
+                    let hint = if self.goto_input.trim().is_empty()
+                        || parse_goto(&self.goto_input, current, line_count).is_some()
+                    {
+                        format!(
+                            "Current line {}, column {}. Type a line between 1 and {}.",
+                            self.cur_line, self.cur_col, line_count
+                        )
+                    } else {
+                        "Not a valid line number".to_string()
+                    };
+                    ui.label(RichText::new(hint).size(11.0).weak());
+                    ui.add_space(6.0);
+                    ui.horizontal(|ui| {
+                        if ui.button("Go").clicked() {
+                            go = true;
+                        }
+                        if ui.button("Cancel [Esc]").clicked() {
+                            close = true;
+                        }
+                    });
+                });
+            });
+
+        if ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+            close = true;
+        }
+        if go {
+            if let Some(target) = parse_goto(&self.goto_input, current, line_count) {
+                self.go_to(target);
+                self.goto_line = false;
+            }
+        }
+        if close {
+            self.goto_line = false;
+            self.editor_focus_request = true;
+        }
+    }
+
     fn large_file_confirm_md(&mut self, ctx: &egui::Context) {
         let mut open = false;
         let mut cancel = false;
@@ -1604,3 +5036,95 @@ impl Notepad {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaced_message_counts() {
+        assert_eq!(replaced_message(0), "Replaced 0 occurrences");
+        assert_eq!(replaced_message(1), "Replaced 1 occurrence");
+        assert_eq!(replaced_message(12), "Replaced 12 occurrences");
+    }
+
+    #[test]
+    fn match_highlights_fade_the_current_match() {
+        let colors = ThemeColors::for_mode(ThemeMode::Dark, true);
+        let result = |range: Range<usize>, flash_timer: f32| SearchResult {
+            chars: range.clone(),
+            range,
+            lines: 0..0,
+            flash_timer,
+        };
+        let results = [result(0..2, 0.0), result(4..7, 1.0), result(9..10, 0.0)];
+        let colored = |current| -> Vec<egui::Color32> {
+            let highlights = match_highlights(&results, current, &colors);
+            highlights.into_iter().map(|(_, c)| c).collect()
+        };
+        let match_bg = colors.search_match_bg;
+        assert_eq!(colored(1), [match_bg, colors.search_flash_bg, match_bg]);
+        assert_eq!(colored(2), [match_bg, match_bg, colors.search_current_bg]);
+        let ranges: Vec<Range<usize>> = match_highlights(&results, 0, &colors)
+            .into_iter()
+            .map(|(r, _)| r)
+            .collect();
+        assert_eq!(ranges, [0..2, 4..7, 9..10]);
+    }
+
+    #[test]
+    fn relative_line_numbers() {
+        let numbers =
+            |mode: LineNumbers| -> Vec<usize> { (0..5).map(|line| mode.number(line, 2)).collect() };
+        assert_eq!(numbers(LineNumbers::Absolute), [1, 2, 3, 4, 5]);
+        assert_eq!(numbers(LineNumbers::Relative), [2, 1, 3, 1, 2]);
+    }
+
+    #[test]
+    fn take_paste_leaves_later_pastes() {
+        let mut events = vec![
+            egui::Event::Text("a".into()),
+            egui::Event::Paste("ours".into()),
+            egui::Event::Paste("user's".into()),
+        ];
+        assert_eq!(take_paste(&mut events).as_deref(), Some("ours"));
+        assert_eq!(
+            events,
+            [
+                egui::Event::Text("a".into()),
+                egui::Event::Paste("user's".into())
+            ]
+        );
+        assert_eq!(take_paste(&mut vec![egui::Event::Copy]), None);
+    }
+
+    #[test]
+    fn remember_moves_entries_to_front() {
+        let mut history = vec!["b".to_string(), "a".to_string()];
+        remember(&mut history, "a");
+        remember(&mut history, "");
+        remember(&mut history, "x\ny");
+        assert_eq!(history, ["a", "b"]);
+        for i in 0..SEARCH_HISTORY_MAX_ENTRIES {
+            remember(&mut history, &i.to_string());
+        }
+        assert_eq!(history.len(), SEARCH_HISTORY_MAX_ENTRIES);
+        assert_eq!(history[0], (SEARCH_HISTORY_MAX_ENTRIES - 1).to_string());
+    }
+
+    #[test]
+    fn browse_history_steps_and_returns_to_empty() {
+        let history = vec!["new".to_string(), "old".to_string()];
+        let (mut pos, mut field) = (None, "typed".to_string());
+        let mut step = |up: bool| {
+            browse_history(&history, &mut pos, &mut field, up);
+            field.clone()
+        };
+        assert_eq!(step(false), "typed");
+        assert_eq!([step(true), step(true), step(true)], ["new", "old", "old"]);
+        assert_eq!([step(false), step(false)], ["new", ""]);
+        let mut field = "typed".to_string();
+        browse_history(&[], &mut None, &mut field, true);
+        assert_eq!(field, "typed");
+    }
+}
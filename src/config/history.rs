@@ -0,0 +1,3 @@
+pub const HISTORY_MAX_ENTRIES: usize = 50;
+pub const HISTORY_MAX_AGE_DAYS: i64 = 30;
+pub const HISTORY_SNAPSHOT_INTERVAL_SECS: f32 = 300.0;
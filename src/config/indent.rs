@@ -0,0 +1,2 @@
+pub const DEFAULT_TAB_WIDTH: usize = 4;
+pub const TAB_WIDTHS: [usize; 4] = [2, 3, 4, 8];
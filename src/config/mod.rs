@@ -1,5 +1,13 @@
 pub mod font;
+pub mod history;
+pub mod indent;
+pub mod reflow;
+pub mod search;
 
 pub use font::*;
+pub use history::*;
+pub use indent::*;
+pub use reflow::*;
+pub use search::*;
 
 pub const APP_ICON: &[u8] = include_bytes!("../../assets/images/appicon.png");
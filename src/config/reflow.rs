@@ -0,0 +1,2 @@
+pub const DEFAULT_WRAP_COLUMN: usize = 80;
+pub const WRAP_COLUMNS: [usize; 4] = [72, 80, 100, 120];
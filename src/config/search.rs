@@ -0,0 +1 @@
+pub const SEARCH_HISTORY_MAX_ENTRIES: usize = 20;
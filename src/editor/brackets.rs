@@ -0,0 +1,237 @@
+use super::{Caret, CaretEdit, MultiCursor};
+use std::ops::Range;
+
+pub const BRACKETS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+
+/// Char index of the bracket matching the one at `i`, ignoring chars for which `skip`
+/// returns true.
+pub fn matching_bracket(chars: &[char], i: usize, skip: impl Fn(usize) -> bool) -> Option<usize> {
+    let c = *chars.get(i)?;
+    let (other, forward) = BRACKETS.iter().find_map(|&(open, close)| {
+        if c == open {
+            Some((close, true))
+        } else if c == close {
+            Some((open, false))
+        } else {
+            None
+        }
+    })?;
+    let mut depth = 0usize;
+    let mut step = |j: &usize| {
+        if skip(*j) {
+            return false;
+        }
+        if chars[*j] == c {
+            depth += 1;
+        } else if chars[*j] == other {
+            if depth == 0 {
+                return true;
+            }
+            depth -= 1;
+        }
+        false
+    };
+    if forward {
+        (i + 1..chars.len()).find(|j| step(j))
+    } else {
+        (0..i).rev().find(|j| step(j))
+    }
+}
+
+/// Whether `i` lies in one of the sorted, non-overlapping `ranges`.
+pub fn in_ranges(ranges: &[Range<usize>], i: usize) -> bool {
+    let k = ranges.partition_point(|r| r.end <= i);
+    ranges.get(k).is_some_and(|r| r.start <= i)
+}
+
+/// The bracket after or before the caret at `pos`, with its match.
+pub fn bracket_near(
+    chars: &[char],
+    pos: usize,
+    skip: impl Fn(usize) -> bool,
+) -> Option<(usize, usize)> {
+    [Some(pos), pos.checked_sub(1)]
+        .into_iter()
+        .flatten()
+        .filter(|&i| !skip(i))
+        .find_map(|i| Some((i, matching_bracket(chars, i, &skip)?)))
+}
+
+/// Types `c` at every caret. Openers of `pairs` also insert their closer, or wrap the
+/// selection, unless the caret is in a string or comment (`in_literal`) or next to a
+/// word; a closer typed in front of the same char steps over it.
+pub fn type_paired(
+    cursor: &mut MultiCursor,
+    text: &mut String,
+    c: char,
+    pairs: &[(char, char)],
+    in_literal: impl Fn(usize) -> bool,
+) {
+    let chars: Vec<char> = text.chars().collect();
+    let is_word = |c: Option<&char>| c.is_some_and(|c| c.is_alphanumeric() || *c == '_');
+    let closes = pairs.iter().any(|&(_, close)| close == c);
+    let primary = cursor.primary();
+
+    let mut carets = Vec::with_capacity(cursor.carets().len());
+    let mut pieces = Vec::with_capacity(cursor.carets().len());
+    let mut new_primary = primary;
+    for &caret in cursor.carets() {
+        let range = caret.range();
+        let (prev, next) = (
+            range.start.checked_sub(1).and_then(|i| chars.get(i)),
+            chars.get(range.end),
+        );
+        let close = pairs
+            .iter()
+            .find(|&&(open, _)| open == c)
+            .map(|&(_, close)| close);
+        let (replaced, piece) = if caret.is_empty() && closes && next == Some(&c) {
+            (
+                Caret::new(range.start, range.start + 1),
+                (c.to_string(), String::new()),
+            )
+        } else if let Some(close) = close.filter(|_| !caret.is_empty()) {
+            let selected: String = chars[range.clone()].iter().collect();
+            (caret, (format!("{}{}", c, selected), close.to_string()))
+        } else if let Some(close) = close.filter(|&close| {
+            let quote = close == c;
+            !(in_literal(range.start) || is_word(next) || (quote && is_word(prev)))
+        }) {
+            (caret, (c.to_string(), close.to_string()))
+        } else {
+            (caret, (c.to_string(), String::new()))
+        };
+        if caret == primary {
+            new_primary = replaced;
+        }
+        carets.push(replaced);
+        pieces.push(piece);
+    }
+    *cursor = MultiCursor::new(carets, new_primary);
+    cursor.insert_around(text, &pieces);
+}
+
+/// Backspace that also removes the closer of an empty pair like `(|)`.
+/// Returns whether any text was deleted.
+pub fn backspace_paired(
+    cursor: &mut MultiCursor,
+    text: &mut String,
+    pairs: &[(char, char)],
+) -> bool {
+    let len = text.len();
+    let chars: Vec<char> = text.chars().collect();
+    let primary = cursor.primary();
+    let widen = |caret: Caret| {
+        let pos = caret.head;
+        let pair = (
+            pos.checked_sub(1).and_then(|i| chars.get(i)),
+            chars.get(pos),
+        );
+        let empty_pair = pairs
+            .iter()
+            .any(|(open, close)| pair == (Some(open), Some(close)));
+        if caret.is_empty() && empty_pair {
+            Caret::new(pos - 1, pos + 1)
+        } else {
+            caret
+        }
+    };
+    let carets = cursor.carets().iter().map(|&c| widen(c)).collect();
+    *cursor = MultiCursor::new(carets, widen(primary));
+    cursor.edit(text, CaretEdit::Backspace);
+    text.len() != len
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAIRS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('"', '"')];
+
+    fn chars(text: &str) -> Vec<char> {
+        text.chars().collect()
+    }
+
+    fn typed(text: &str, caret: Caret, c: char, in_literal: bool) -> (String, Caret) {
+        let mut text = text.to_string();
+        let mut cursor = MultiCursor::new(vec![caret], caret);
+        type_paired(&mut cursor, &mut text, c, &PAIRS, |_| in_literal);
+        (text, cursor.primary())
+    }
+
+    #[test]
+    fn matching_brackets_nest_and_skip() {
+        let text = chars("f(a[é]) {}");
+        let found: Vec<Option<usize>> = [1, 6, 3, 5, 8, 9, 0]
+            .into_iter()
+            .map(|i| matching_bracket(&text, i, |_| false))
+            .collect();
+        assert_eq!(
+            found,
+            [Some(6), Some(1), Some(5), Some(3), Some(9), Some(8), None]
+        );
+        let text = chars("(\")\")");
+        assert_eq!(matching_bracket(&text, 0, |_| false), Some(2));
+        assert_eq!(matching_bracket(&text, 0, |i| i == 2), Some(4));
+        assert_eq!(bracket_near(&chars("(a)"), 3, |_| false), Some((2, 0)));
+        assert_eq!(bracket_near(&chars("(a)"), 0, |_| false), Some((0, 2)));
+    }
+
+    #[test]
+    fn in_ranges_is_half_open() {
+        let ranges = [2..4, 6..8];
+        let inside: Vec<usize> = (0..9).filter(|&i| in_ranges(&ranges, i)).collect();
+        assert_eq!(inside, [2, 3, 6, 7]);
+    }
+
+    #[test]
+    fn type_paired_inserts_wraps_and_steps_over() {
+        assert_eq!(
+            typed("ab", Caret::at(2), '(', false),
+            ("ab()".into(), Caret::at(3))
+        );
+        assert_eq!(
+            typed("ab", Caret::at(0), '(', false),
+            ("(ab".into(), Caret::at(1))
+        );
+        assert_eq!(
+            typed("é", Caret::at(1), '"', false),
+            ("é\"".into(), Caret::at(2))
+        );
+        assert_eq!(
+            typed("é ", Caret::at(2), '"', false),
+            ("é \"\"".into(), Caret::at(3))
+        );
+        assert_eq!(
+            typed("ab", Caret::new(0, 2), '[', false),
+            ("[ab]".into(), Caret::at(3))
+        );
+        assert_eq!(
+            typed("()", Caret::at(1), ')', false),
+            ("()".into(), Caret::at(2))
+        );
+        assert_eq!(
+            typed("", Caret::at(0), '(', true),
+            ("(".into(), Caret::at(1))
+        );
+    }
+
+    #[test]
+    fn backspace_removes_empty_pairs() {
+        let mut text = "() [x]".to_string();
+        let carets = vec![Caret::at(1), Caret::at(5)];
+        let mut cursor = MultiCursor::new(carets, Caret::at(1));
+        assert!(backspace_paired(&mut cursor, &mut text, &PAIRS));
+        assert_eq!(text, " []");
+        assert_eq!(cursor.carets(), [Caret::at(0), Caret::at(2)]);
+    }
+
+    #[test]
+    fn backspace_at_start_changes_nothing() {
+        let mut text = "ab\ncd".to_string();
+        let carets = vec![Caret::at(0), Caret::at(0)];
+        let mut cursor = MultiCursor::new(carets, Caret::at(0));
+        assert!(!backspace_paired(&mut cursor, &mut text, &PAIRS));
+        assert_eq!(text, "ab\ncd");
+    }
+}
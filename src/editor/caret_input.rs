@@ -0,0 +1,455 @@
+use super::{
+    backspace_paired, in_ranges, line_start, newline_indent, newline_of, paste_column, type_paired,
+    Caret, CaretEdit, ColumnBlock, IndentStyle, LineEdit, Motion, MultiCursor,
+};
+use egui::{Event, ImeEvent, Key, Modifiers};
+use std::ops::Range;
+
+/// Chars typed through `type_paired`: the brackets and quotes that may be auto-closed.
+const PAIR_CHARS: &[char] = &['(', ')', '[', ']', '{', '}', '"', '\'', '`'];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CaretCommand {
+    Above,
+    Below,
+    NextOccurrence,
+    AllOccurrences,
+    /// Grows the column selection by one line or column.
+    Column(Motion),
+}
+
+/// A key bound to a line edit or caret command, which the app carries out since it
+/// also moves focus and scrolls.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CaretKey {
+    Line(LineEdit),
+    Split,
+    Reflow,
+    Command(CaretCommand),
+}
+
+/// The command bound to `event`, if it presses one of the line or caret keys.
+pub fn caret_key(event: &Event) -> Option<CaretKey> {
+    let Event::Key {
+        key,
+        pressed: true,
+        modifiers,
+        ..
+    } = event
+    else {
+        return None;
+    };
+    let cmd = modifiers.command;
+    let alt_only = modifiers.alt && !modifiers.shift && !cmd;
+    let key = match key {
+        Key::D if cmd && modifiers.shift => CaretKey::Line(LineEdit::Duplicate),
+        Key::K if cmd && modifiers.shift => CaretKey::Line(LineEdit::Delete),
+        Key::J if cmd && modifiers.shift => CaretKey::Split,
+        Key::J if cmd => CaretKey::Line(LineEdit::Join),
+        Key::ArrowUp if alt_only => CaretKey::Line(LineEdit::MoveUp),
+        Key::ArrowDown if alt_only => CaretKey::Line(LineEdit::MoveDown),
+        Key::Q if alt_only => CaretKey::Reflow,
+        Key::D if cmd => CaretKey::Command(CaretCommand::NextOccurrence),
+        Key::L if cmd && modifiers.shift => CaretKey::Command(CaretCommand::AllOccurrences),
+        Key::ArrowUp if cmd && modifiers.alt => CaretKey::Command(CaretCommand::Above),
+        Key::ArrowDown if cmd && modifiers.alt => CaretKey::Command(CaretCommand::Below),
+        _ if cmd || !(modifiers.shift && modifiers.alt) => return None,
+        Key::ArrowUp => CaretKey::Command(CaretCommand::Column(Motion::Up)),
+        Key::ArrowDown => CaretKey::Command(CaretCommand::Column(Motion::Down)),
+        Key::ArrowLeft => CaretKey::Command(CaretCommand::Column(Motion::Left)),
+        Key::ArrowRight => CaretKey::Command(CaretCommand::Column(Motion::Right)),
+        _ => return None,
+    };
+    Some(key)
+}
+
+/// Whether `events` type or backspace over what may be half of a bracket pair.
+pub fn edits_pairs(events: &[Event]) -> bool {
+    events.iter().any(|e| match e {
+        Event::Text(s) => is_pair_char(s),
+        Event::Key {
+            key: Key::Backspace,
+            pressed: true,
+            modifiers,
+            ..
+        } => modifiers.is_none(),
+        _ => false,
+    })
+}
+
+/// Whether `events` press Enter or Tab, which indent.
+pub fn indents(events: &[Event]) -> bool {
+    events.iter().any(|e| {
+        matches!(e, Event::Key {
+            key: Key::Enter | Key::Tab,
+            pressed: true,
+            modifiers,
+            ..
+        } if !modifiers.command)
+    })
+}
+
+fn is_pair_char(s: &str) -> bool {
+    s.chars().count() == 1 && s.contains(PAIR_CHARS)
+}
+
+/// What the caret edits need to know about the document.
+pub struct CaretContext<'a> {
+    pub indent: IndentStyle,
+    pub pairs: &'a [(char, char)],
+    /// Char ranges inside strings and comments, where quotes are not paired.
+    pub literals: &'a [Range<usize>],
+    /// Whether a line opens a block, so that Enter indents one level deeper.
+    pub opens: &'a dyn Fn(&str) -> bool,
+}
+
+/// The editor's extra carets and the input that edits through them. The `TextEdit`
+/// owns the primary caret; the others are kept here while there are several.
+#[derive(Default)]
+pub struct CaretInput {
+    multi: Option<MultiCursor>,
+    /// The last column selection, kept for its columns past the end of short lines.
+    column_block: Option<ColumnBlock>,
+    /// Text last copied from several carets, pasted back as a column.
+    column_copy: Option<String>,
+}
+
+impl CaretInput {
+    /// The carets, while there are several.
+    pub fn cursor(&self) -> Option<&MultiCursor> {
+        self.multi.as_ref()
+    }
+
+    pub fn take(&mut self) -> Option<MultiCursor> {
+        self.multi.take()
+    }
+
+    /// Takes the carets, or the single `primary` caret when there are no others.
+    pub fn take_or(&mut self, primary: Caret) -> MultiCursor {
+        self.multi
+            .take()
+            .unwrap_or_else(|| MultiCursor::new(vec![primary], primary))
+    }
+
+    /// Keeps `multi` if it has more than one caret.
+    pub fn set(&mut self, multi: MultiCursor) {
+        self.multi = (multi.carets().len() > 1).then_some(multi);
+    }
+
+    /// Routes the following events through the caret edits even with the single
+    /// `primary` caret.
+    pub fn begin(&mut self, primary: Caret) {
+        self.multi
+            .get_or_insert_with(|| MultiCursor::new(vec![primary], primary));
+    }
+
+    /// Drops the extra carets and the column block, which point into the text being
+    /// replaced.
+    pub fn reset(&mut self) {
+        self.multi = None;
+        self.column_block = None;
+    }
+
+    /// Applies `command` to the carets, starting from `primary` when there are no others.
+    pub fn command(&mut self, text: &str, primary: Caret, command: CaretCommand) -> MultiCursor {
+        let mut multi = self.take_or(primary);
+        match command {
+            CaretCommand::Above => multi.add_vertical(text, false),
+            CaretCommand::Below => multi.add_vertical(text, true),
+            CaretCommand::NextOccurrence => multi.add_next_occurrence(text),
+            CaretCommand::AllOccurrences => multi.select_all_occurrences(text),
+            CaretCommand::Column(motion) => {
+                let mut block = self
+                    .column_block
+                    .filter(|b| b.cursor(text).carets() == multi.carets())
+                    .unwrap_or_else(|| ColumnBlock::from_cursor(text, &multi));
+                block.extend(text, motion);
+                multi = block.cursor(text);
+                self.column_block = Some(block);
+            }
+        }
+        multi
+    }
+
+    /// Applies `event` at every caret. Returns `None` when the event is left to the
+    /// `TextEdit`, otherwise whether the text changed. A paste of the last column copy
+    /// at the single `primary` caret is pasted back as a column.
+    pub fn event(
+        &mut self,
+        ctx: &egui::Context,
+        text: &mut String,
+        event: &Event,
+        primary: Caret,
+        cx: &CaretContext,
+    ) -> Option<bool> {
+        if let Event::Paste(s) = event {
+            let block = s.replace("\r\n", "\n");
+            if self.multi.is_none() && self.column_copy.as_ref() == Some(&block) {
+                let mut single = MultiCursor::new(vec![primary], primary);
+                single.edit(text, CaretEdit::Insert(""));
+                let pos = single.primary().head;
+                self.multi = Some(paste_column(text, pos, &block));
+                return Some(true);
+            }
+        }
+
+        let multi = self.multi.as_mut()?;
+        match event {
+            Event::Text(s) if is_pair_char(s) => {
+                let c = s.chars().next().unwrap_or_default();
+                let literals = cx.literals;
+                type_paired(multi, text, c, cx.pairs, |pos| {
+                    pos > 0 && in_ranges(literals, pos - 1) && in_ranges(literals, pos)
+                });
+            }
+            Event::Text(s) | Event::Ime(ImeEvent::Commit(s)) => {
+                multi.edit(text, CaretEdit::Insert(s));
+            }
+            Event::Paste(s) => multi.edit(text, CaretEdit::Paste(s)),
+            Event::Copy | Event::Cut => {
+                let copied = multi.selected_texts(text).join("\n");
+                ctx.copy_text(copied.clone());
+                self.column_copy = (multi.carets().len() > 1).then_some(copied);
+                if *event == Event::Copy {
+                    return Some(false);
+                }
+                multi.edit(text, CaretEdit::Insert(""));
+            }
+            Event::Key {
+                key,
+                pressed: true,
+                modifiers,
+                ..
+            } => return press(multi, text, *key, modifiers, cx),
+            _ => return None,
+        }
+        Some(true)
+    }
+}
+
+fn press(
+    multi: &mut MultiCursor,
+    text: &mut String,
+    key: Key,
+    modifiers: &Modifiers,
+    cx: &CaretContext,
+) -> Option<bool> {
+    let word = modifiers.ctrl || modifiers.alt;
+    let motion = match key {
+        Key::ArrowLeft if word => Some(Motion::WordLeft),
+        Key::ArrowRight if word => Some(Motion::WordRight),
+        Key::ArrowLeft => Some(Motion::Left),
+        Key::ArrowRight => Some(Motion::Right),
+        Key::ArrowUp => Some(Motion::Up),
+        Key::ArrowDown => Some(Motion::Down),
+        Key::Home => Some(Motion::Home),
+        Key::End => Some(Motion::End),
+        _ => None,
+    };
+    let indent = cx.indent;
+    let changed = if key == Key::Enter && !modifiers.command {
+        let chars: Vec<char> = text.chars().collect();
+        let newline = newline_of(text);
+        let pieces: Vec<(String, String)> = multi
+            .carets()
+            .iter()
+            .map(|c| newline_indent(&chars, c.range().start, indent, newline, cx.opens))
+            .collect();
+        multi.insert_around(text, &pieces);
+        true
+    } else if key == Key::Tab && modifiers.shift {
+        multi.edit_lines(text, |line| {
+            let n = indent.outdent_len(line);
+            (n > 0).then(|| (0, n, String::new()))
+        })
+    } else if key == Key::Tab {
+        let chars: Vec<char> = text.chars().collect();
+        let spans_lines = multi
+            .carets()
+            .iter()
+            .any(|c| chars[c.clamped(chars.len()).range()].contains(&'\n'));
+        if spans_lines {
+            multi.edit_lines(text, |line| {
+                (!line.trim().is_empty()).then(|| (0, 0, indent.unit()))
+            })
+        } else {
+            let pieces: Vec<String> = multi
+                .carets()
+                .iter()
+                .map(|c| {
+                    let start = c.range().start;
+                    indent.tab_at(start - line_start(&chars, start))
+                })
+                .collect();
+            multi.insert_each(text, &pieces);
+            true
+        }
+    } else if key == Key::Backspace && modifiers.is_none() {
+        backspace_paired(multi, text, cx.pairs)
+    } else if let Some(motion) = motion {
+        multi.motion(text, motion, modifiers.shift);
+        false
+    } else if key == Key::Backspace {
+        multi.edit(text, CaretEdit::Backspace);
+        true
+    } else if key == Key::Delete {
+        multi.edit(text, CaretEdit::Delete);
+        true
+    } else if key == Key::Escape {
+        let primary = multi.primary();
+        *multi = MultiCursor::new(vec![primary], primary);
+        false
+    } else {
+        return None;
+    };
+    Some(changed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPACES: IndentStyle = IndentStyle {
+        spaces: true,
+        width: 4,
+    };
+
+    fn key(key: Key, modifiers: Modifiers) -> Event {
+        Event::Key {
+            key,
+            physical_key: None,
+            pressed: true,
+            repeat: false,
+            modifiers,
+        }
+    }
+
+    fn cx<'a>(pairs: &'a [(char, char)]) -> CaretContext<'a> {
+        CaretContext {
+            indent: SPACES,
+            pairs,
+            literals: &[],
+            opens: &|line| line.ends_with('{'),
+        }
+    }
+
+    fn input(carets: &[Caret]) -> CaretInput {
+        let mut input = CaretInput::default();
+        input.set(MultiCursor::new(carets.to_vec(), carets[0]));
+        input
+    }
+
+    #[test]
+    fn caret_keys() {
+        let ctrl_shift = Modifiers::COMMAND | Modifiers::SHIFT;
+        let found: Vec<Option<CaretKey>> = [
+            key(Key::D, Modifiers::COMMAND),
+            key(Key::D, ctrl_shift),
+            key(Key::J, ctrl_shift),
+            key(Key::ArrowUp, Modifiers::ALT),
+            key(Key::ArrowLeft, Modifiers::ALT | Modifiers::SHIFT),
+            key(Key::Q, Modifiers::ALT),
+            key(Key::ArrowLeft, Modifiers::ALT),
+            Event::Text("d".into()),
+        ]
+        .iter()
+        .map(caret_key)
+        .collect();
+        assert_eq!(
+            found,
+            [
+                Some(CaretKey::Command(CaretCommand::NextOccurrence)),
+                Some(CaretKey::Line(LineEdit::Duplicate)),
+                Some(CaretKey::Split),
+                Some(CaretKey::Line(LineEdit::MoveUp)),
+                Some(CaretKey::Command(CaretCommand::Column(Motion::Left))),
+                Some(CaretKey::Reflow),
+                None,
+                None,
+            ]
+        );
+    }
+
+    #[test]
+    fn events_edit_at_every_caret() {
+        let ctx = egui::Context::default();
+        let pairs = [('(', ')')];
+        let mut text = "a {\nb {".to_string();
+        let mut input = input(&[Caret::at(3), Caret::at(7)]);
+        let mut send = |event: Event, text: &mut String| {
+            input.event(&ctx, text, &event, Caret::at(0), &cx(&pairs))
+        };
+        assert_eq!(send(Event::Text("(".into()), &mut text), Some(true));
+        assert_eq!(text, "a {()\nb {()");
+        assert_eq!(
+            send(key(Key::Backspace, Modifiers::NONE), &mut text),
+            Some(true)
+        );
+        assert_eq!(
+            send(key(Key::Enter, Modifiers::NONE), &mut text),
+            Some(true)
+        );
+        assert_eq!(text, "a {\n    \nb {\n    ");
+        assert_eq!(
+            send(key(Key::Home, Modifiers::NONE), &mut text),
+            Some(false)
+        );
+        assert_eq!(send(Event::PointerGone, &mut text), None);
+        assert_eq!(
+            send(key(Key::Escape, Modifiers::NONE), &mut text),
+            Some(false)
+        );
+        assert_eq!(input.cursor().map(|m| m.carets().len()), Some(1));
+    }
+
+    #[test]
+    fn single_caret_events_go_to_the_text_edit() {
+        let ctx = egui::Context::default();
+        let mut text = "ab".to_string();
+        let mut input = CaretInput::default();
+        let event = Event::Text("x".into());
+        assert_eq!(
+            input.event(&ctx, &mut text, &event, Caret::at(0), &cx(&[])),
+            None
+        );
+        input.begin(Caret::at(1));
+        assert_eq!(
+            input.event(&ctx, &mut text, &event, Caret::at(0), &cx(&[])),
+            Some(true)
+        );
+        assert_eq!(text, "axb");
+    }
+
+    #[test]
+    fn column_copy_pastes_back_as_a_column() {
+        let ctx = egui::Context::default();
+        let mut text = "ab\ncd\n".to_string();
+        let mut input = input(&[Caret::new(0, 1), Caret::new(3, 4)]);
+        let copy = input.event(&ctx, &mut text, &Event::Copy, Caret::at(0), &cx(&[]));
+        assert_eq!(copy, Some(false));
+        input.reset();
+        let paste = Event::Paste("a\nc".into());
+        let pasted = input.event(&ctx, &mut text, &paste, Caret::at(6), &cx(&[]));
+        assert_eq!(pasted, Some(true));
+        assert_eq!(text, "ab\ncd\na\nc");
+        assert_eq!(input.cursor().map(|m| m.carets().len()), Some(2));
+    }
+
+    #[test]
+    fn column_command_keeps_its_block() {
+        let text = "abc\nx\nabc";
+        let mut input = CaretInput::default();
+        // The app hands back the primary caret it synced into the `TextEdit`.
+        let mut primary = Caret::at(2);
+        let mut run = |motion: Motion| {
+            let multi = input.command(text, primary, CaretCommand::Column(motion));
+            primary = multi.primary();
+            let carets: Vec<Range<usize>> = multi.carets().iter().map(|c| c.range()).collect();
+            input.set(multi);
+            carets
+        };
+        assert_eq!(run(Motion::Right).len(), 1);
+        assert_eq!(run(Motion::Down), [2..3, 5..5]);
+        assert_eq!(run(Motion::Down), [2..3, 5..5, 8..9]);
+    }
+}
@@ -0,0 +1,745 @@
+use super::line_ranges;
+use std::ops::Range;
+
+/// A caret with an optional selection, in char indices. `anchor == head` means no selection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Caret {
+    pub anchor: usize,
+    pub head: usize,
+}
+
+impl Caret {
+    pub fn at(pos: usize) -> Self {
+        Self {
+            anchor: pos,
+            head: pos,
+        }
+    }
+
+    pub fn new(anchor: usize, head: usize) -> Self {
+        Self { anchor, head }
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.anchor.min(self.head)..self.anchor.max(self.head)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.anchor == self.head
+    }
+
+    /// The caret moved by `delta` chars.
+    pub fn shifted(self, delta: isize) -> Self {
+        Self::new(
+            (self.anchor as isize + delta) as usize,
+            (self.head as isize + delta) as usize,
+        )
+    }
+
+    /// The caret kept within a text of `len` chars.
+    pub fn clamped(self, len: usize) -> Self {
+        Self::new(self.anchor.min(len), self.head.min(len))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CaretEdit<'a> {
+    Insert(&'a str),
+    /// Like `Insert`, but text with one line per caret is split across the carets.
+    Paste(&'a str),
+    Backspace,
+    Delete,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Motion {
+    Left,
+    Right,
+    WordLeft,
+    WordRight,
+    Up,
+    Down,
+    Home,
+    End,
+}
+
+/// Several carets over one text. Carets are kept sorted and never overlap.
+#[derive(Clone, Debug)]
+pub struct MultiCursor {
+    carets: Vec<Caret>,
+    primary: usize,
+}
+
+impl MultiCursor {
+    pub fn new(carets: Vec<Caret>, primary: Caret) -> Self {
+        let mut cursor = Self { carets, primary: 0 };
+        cursor.normalize(primary);
+        cursor
+    }
+
+    pub fn carets(&self) -> &[Caret] {
+        &self.carets
+    }
+
+    pub fn primary(&self) -> Caret {
+        self.carets[self.primary]
+    }
+
+    /// Adds `caret` and makes it the primary caret.
+    pub fn add(&mut self, caret: Caret) {
+        self.carets.push(caret);
+        self.normalize(caret);
+    }
+
+    pub fn selected_texts(&self, text: &str) -> Vec<String> {
+        let chars: Vec<char> = text.chars().collect();
+        self.carets
+            .iter()
+            .map(|c| chars[c.clamped(chars.len()).range()].iter().collect())
+            .collect()
+    }
+
+    /// Sorts the carets and merges overlapping ones, keeping track of the primary caret.
+    fn normalize(&mut self, primary: Caret) {
+        self.carets
+            .sort_by_key(|c| (c.range().start, c.range().end));
+        let mut merged: Vec<Caret> = Vec::with_capacity(self.carets.len());
+        let mut primary_index = 0;
+        for caret in self.carets.drain(..) {
+            let is_primary = caret == primary;
+            match merged.last_mut() {
+                Some(last)
+                    if caret.range().start < last.range().end
+                        || (caret.range().start == last.range().end
+                            && (caret.is_empty() || last.is_empty())) =>
+                {
+                    let range = last.range().start..last.range().end.max(caret.range().end);
+                    *last = if last.anchor <= last.head {
+                        Caret::new(range.start, range.end)
+                    } else {
+                        Caret::new(range.end, range.start)
+                    };
+                }
+                _ => merged.push(caret),
+            }
+            if is_primary {
+                primary_index = merged.len() - 1;
+            }
+        }
+        self.carets = merged;
+        self.primary = primary_index;
+    }
+
+    /// Applies `edit` at every caret in a single pass over `text`.
+    pub fn edit(&mut self, text: &mut String, edit: CaretEdit) {
+        let pasted: Option<Vec<(&str, &str)>> = match edit {
+            CaretEdit::Paste(s) => {
+                let lines: Vec<&str> = s.trim_end_matches('\n').split('\n').collect();
+                (self.carets.len() > 1 && lines.len() == self.carets.len()).then(|| {
+                    lines
+                        .iter()
+                        .map(|l| (l.strip_suffix('\r').unwrap_or(l), ""))
+                        .collect()
+                })
+            }
+            _ => None,
+        };
+        self.splice(text, edit, pasted.as_deref());
+    }
+
+    /// Inserts `pieces[i]` at the i-th caret, replacing its selection.
+    pub fn insert_each(&mut self, text: &mut String, pieces: &[String]) {
+        let pieces: Vec<(&str, &str)> = pieces.iter().map(|p| (p.as_str(), "")).collect();
+        self.splice(text, CaretEdit::Insert(""), Some(&pieces));
+    }
+
+    /// Inserts both halves of `pieces[i]` at the i-th caret, leaving it between them.
+    pub fn insert_around(&mut self, text: &mut String, pieces: &[(String, String)]) {
+        let pieces: Vec<(&str, &str)> = pieces
+            .iter()
+            .map(|(before, after)| (before.as_str(), after.as_str()))
+            .collect();
+        self.splice(text, CaretEdit::Insert(""), Some(&pieces));
+    }
+
+    fn splice(&mut self, text: &mut String, edit: CaretEdit, pieces: Option<&[(&str, &str)]>) {
+        let chars: Vec<char> = text.chars().collect();
+        let primary = self.primary;
+        let mut offsets = ByteOffsets::new(text);
+        let mut out = String::with_capacity(text.len());
+        let (mut last_byte, mut last_char) = (0, 0);
+        let mut delta = 0isize;
+        for (i, caret) in self.carets.iter_mut().enumerate() {
+            let mut range = caret.range();
+            if range.is_empty() {
+                let crlf =
+                    |i: usize| chars.get(i) == Some(&'\r') && chars.get(i + 1) == Some(&'\n');
+                match edit {
+                    CaretEdit::Backspace if range.start >= 2 && crlf(range.start - 2) => {
+                        range.start -= 2
+                    }
+                    CaretEdit::Backspace => range.start = range.start.saturating_sub(1),
+                    CaretEdit::Delete if crlf(range.end) => range.end += 2,
+                    CaretEdit::Delete => range.end = (range.end + 1).min(chars.len()),
+                    _ => {}
+                }
+            }
+            range.start = range.start.max(last_char);
+            range.end = range.end.max(range.start);
+            let (before, after) = match (edit, pieces) {
+                (_, Some(pieces)) => pieces[i],
+                (CaretEdit::Insert(s) | CaretEdit::Paste(s), None) => (s, ""),
+                _ => ("", ""),
+            };
+
+            let (start, end) = (offsets.get(range.start), offsets.get(range.end));
+            out.push_str(&text[last_byte..start]);
+            out.push_str(before);
+            out.push_str(after);
+            let before = before.chars().count() as isize;
+            let inserted = before + after.chars().count() as isize;
+            let pos = range.start as isize + delta + before;
+            *caret = Caret::at(pos as usize);
+            delta += inserted - range.len() as isize;
+            (last_byte, last_char) = (end, range.end);
+        }
+        out.push_str(&text[last_byte..]);
+        *text = out;
+        let primary = self.carets[primary];
+        self.normalize(primary);
+    }
+
+    /// The text of every line touched by a caret, without line endings.
+    pub fn lines(&self, text: &str) -> Vec<String> {
+        let chars: Vec<char> = text.chars().collect();
+        self.touched_lines(&chars)
+            .into_iter()
+            .map(|start| chars[start..line_end(&chars, start)].iter().collect())
+            .collect()
+    }
+
+    /// Start of every line touched by a caret, in order. A selection ending at the start
+    /// of a line does not touch it.
+    fn touched_lines(&self, chars: &[char]) -> Vec<usize> {
+        let mut lines: Vec<usize> = Vec::new();
+        for caret in &self.carets {
+            let range = caret.range();
+            let first = line_start(chars, range.start);
+            let mut last = line_start(chars, range.end);
+            if last > first && last == range.end {
+                last = line_start(chars, last - 1);
+            }
+            let mut start = first;
+            loop {
+                if lines.last() != Some(&start) {
+                    lines.push(start);
+                }
+                if start >= last {
+                    break;
+                }
+                start = line_end(chars, start) + 1;
+                if chars.get(start - 1) == Some(&'\r') {
+                    start += 1;
+                }
+            }
+        }
+        lines
+    }
+
+    /// Edits every line touched by a caret. `edit` gets the line without its line ending
+    /// and returns the char column to edit at, the number of chars to remove there and the
+    /// text to insert, or `None` to leave the line alone. Carets keep their place in the
+    /// surrounding text. Returns whether any line was edited.
+    pub fn edit_lines(
+        &mut self,
+        text: &mut String,
+        mut edit: impl FnMut(&str) -> Option<(usize, usize, String)>,
+    ) -> bool {
+        let chars: Vec<char> = text.chars().collect();
+        // (char position, chars removed, text inserted), in text order.
+        let mut edits: Vec<(usize, usize, String)> = Vec::new();
+        for start in self.touched_lines(&chars) {
+            let line: String = chars[start..line_end(&chars, start)].iter().collect();
+            if let Some((column, removed, inserted)) = edit(&line) {
+                edits.push((start + column, removed, inserted));
+            }
+        }
+        if edits.is_empty() {
+            return false;
+        }
+
+        let mut offsets = ByteOffsets::new(text);
+        let mut out = String::with_capacity(text.len());
+        let mut last = 0;
+        for (pos, removed, inserted) in &edits {
+            let start = offsets.get(*pos);
+            out.push_str(&text[last..start]);
+            out.push_str(inserted);
+            last = offsets.get(pos + removed);
+        }
+        out.push_str(&text[last..]);
+        *text = out;
+
+        let map = |p: usize, empty: bool| {
+            let mut moved = p as isize;
+            for (pos, removed, inserted) in &edits {
+                if *pos > p || (*pos == p && !(empty && *removed == 0)) {
+                    break;
+                }
+                let gone = (*removed).min(p - pos);
+                moved += inserted.chars().count() as isize - gone as isize;
+            }
+            moved as usize
+        };
+        let primary = self.primary;
+        for caret in &mut self.carets {
+            let empty = caret.is_empty();
+            *caret = Caret::new(map(caret.anchor, empty), map(caret.head, empty));
+        }
+        let primary = self.carets[primary];
+        self.normalize(primary);
+        true
+    }
+
+    /// Moves every caret; with `extend` the selections grow instead of collapsing.
+    pub fn motion(&mut self, text: &str, motion: Motion, extend: bool) {
+        let chars: Vec<char> = text.chars().collect();
+        let primary = self.primary;
+        for caret in &mut self.carets {
+            let range = caret.range();
+            let head = match motion {
+                Motion::Left if !extend && !caret.is_empty() => range.start,
+                Motion::Right if !extend && !caret.is_empty() => range.end,
+                Motion::Left => caret.head.saturating_sub(1),
+                Motion::Right => (caret.head + 1).min(chars.len()),
+                Motion::WordLeft => word_left(&chars, caret.head),
+                Motion::WordRight => word_right(&chars, caret.head),
+                Motion::Up => vertical(&chars, caret.head, false),
+                Motion::Down => vertical(&chars, caret.head, true),
+                Motion::Home => {
+                    let start = line_start(&chars, caret.head);
+                    let indent = start
+                        + chars[start..]
+                            .iter()
+                            .take_while(|c| **c == ' ' || **c == '\t')
+                            .count();
+                    if caret.head == indent {
+                        start
+                    } else {
+                        indent
+                    }
+                }
+                Motion::End => line_end(&chars, caret.head),
+            };
+            *caret = if extend {
+                Caret::new(caret.anchor, head)
+            } else {
+                Caret::at(head)
+            };
+        }
+        let primary = self.carets[primary];
+        self.normalize(primary);
+    }
+
+    /// Adds a caret on the line above the topmost caret, or below the bottommost one.
+    pub fn add_vertical(&mut self, text: &str, down: bool) {
+        let chars: Vec<char> = text.chars().collect();
+        let from = if down {
+            self.carets.last()
+        } else {
+            self.carets.first()
+        };
+        let Some(from) = from.map(|c| c.head) else {
+            return;
+        };
+        let pos = vertical(&chars, from, down);
+        if line_start(&chars, pos) != line_start(&chars, from) {
+            self.add(Caret::at(pos));
+        }
+    }
+
+    /// Selects the word at the primary caret if it has no selection, otherwise adds the
+    /// next occurrence of its selected text after it, wrapping around.
+    pub fn add_next_occurrence(&mut self, text: &str) {
+        let primary = self.primary();
+        if primary.is_empty() {
+            let chars: Vec<char> = text.chars().collect();
+            let range = word_at(&chars, primary.head);
+            if !range.is_empty() {
+                self.carets[self.primary] = Caret::new(range.start, range.end);
+            }
+            return;
+        }
+        let occurrences = self.occurrences(text);
+        let after = primary.range().end;
+        let next = occurrences
+            .iter()
+            .filter(|r| !self.carets.iter().any(|c| c.range() == **r))
+            .min_by_key(|r| (r.start < after, r.start));
+        if let Some(range) = next {
+            self.add(Caret::new(range.start, range.end));
+        }
+    }
+
+    /// Replaces the carets with a selection of every occurrence of the primary selection.
+    pub fn select_all_occurrences(&mut self, text: &str) {
+        if self.primary().is_empty() {
+            self.add_next_occurrence(text);
+        }
+        let primary = self.primary();
+        if primary.is_empty() {
+            return;
+        }
+        self.carets = self
+            .occurrences(text)
+            .into_iter()
+            .map(|r| Caret::new(r.start, r.end))
+            .collect();
+        self.normalize(Caret::new(primary.range().start, primary.range().end));
+    }
+
+    /// Char ranges of every occurrence of the primary caret's selected text.
+    fn occurrences(&self, text: &str) -> Vec<Range<usize>> {
+        let range = self.primary().range();
+        let needle: String = text.chars().skip(range.start).take(range.len()).collect();
+        if needle.is_empty() {
+            return Vec::new();
+        }
+        let len = range.len();
+        let mut chars = 0;
+        let mut last = 0;
+        text.match_indices(needle.as_str())
+            .map(|(i, _)| {
+                chars += text[last..i].chars().count();
+                last = i;
+                chars..chars + len
+            })
+            .collect()
+    }
+}
+
+/// A rectangular selection between two corners given as (line, char column).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ColumnBlock {
+    pub anchor: (usize, usize),
+    pub head: (usize, usize),
+}
+
+impl ColumnBlock {
+    /// The block spanned by `cursor`: the lines of its carets, with the columns of its
+    /// primary caret.
+    pub fn from_cursor(text: &str, cursor: &MultiCursor) -> Self {
+        let chars: Vec<char> = text.chars().collect();
+        let primary = cursor.primary();
+        let head = line_col(&chars, primary.head);
+        let mut anchor = line_col(&chars, primary.anchor);
+        if let [first, .., last] = cursor.carets() {
+            let first = line_col(&chars, first.head).0;
+            let last = line_col(&chars, last.head).0;
+            anchor.0 = if head.0 == last { first } else { last };
+        }
+        Self { anchor, head }
+    }
+
+    /// Moves the head corner by one line or column.
+    pub fn extend(&mut self, text: &str, motion: Motion) {
+        let lines = line_ranges(text);
+        match motion {
+            Motion::Up => self.head.0 = self.head.0.saturating_sub(1),
+            Motion::Down => self.head.0 = (self.head.0 + 1).min(lines.len() - 1),
+            Motion::Left => self.head.1 = self.head.1.saturating_sub(1),
+            Motion::Right => {
+                let widest = lines
+                    .iter()
+                    .map(|r| text[r.clone()].chars().count())
+                    .max()
+                    .unwrap_or(0);
+                self.head.1 = (self.head.1 + 1).min(widest);
+            }
+            _ => {}
+        }
+    }
+
+    /// One caret per line of the block, clamped to the line lengths.
+    pub fn cursor(&self, text: &str) -> MultiCursor {
+        let chars: Vec<char> = text.chars().collect();
+        let starts: Vec<usize> = std::iter::once(0)
+            .chain(
+                chars
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, c)| **c == '\n')
+                    .map(|(i, _)| i + 1),
+            )
+            .collect();
+        let caret_on = |line: usize| {
+            let start = starts[line.min(starts.len() - 1)];
+            let len = line_end(&chars, start) - start;
+            Caret::new(start + self.anchor.1.min(len), start + self.head.1.min(len))
+        };
+        let lines = self.anchor.0.min(self.head.0)..=self.anchor.0.max(self.head.0);
+        MultiCursor::new(lines.map(caret_on).collect(), caret_on(self.head.0))
+    }
+}
+
+/// Inserts the lines of `block` as a column at the position of char index `pos`, one per
+/// line, padding short lines with spaces and adding lines past the end of the text.
+/// Returns the carets after each inserted piece.
+pub fn paste_column(text: &mut String, pos: usize, block: &str) -> MultiCursor {
+    let count = block.trim_end_matches('\n').split('\n').count();
+    let chars: Vec<char> = text.chars().collect();
+    let (line, column) = line_col(&chars, pos);
+    let newline = if text.contains("\r\n") { "\r\n" } else { "\n" };
+
+    let ranges = line_ranges(text);
+    let mut out = String::with_capacity(text.len() + block.len());
+    let mut last = 0;
+    for range in ranges.iter().skip(line).take(count) {
+        let len = text[range.clone()].chars().count();
+        out.push_str(&text[last..range.end]);
+        out.push_str(&" ".repeat(column.saturating_sub(len)));
+        last = range.end;
+    }
+    out.push_str(&text[last..]);
+    for _ in ranges.len()..line + count {
+        out.push_str(newline);
+        out.push_str(&" ".repeat(column));
+    }
+    *text = out;
+
+    let mut cursor = ColumnBlock {
+        anchor: (line, column),
+        head: (line + count - 1, column),
+    }
+    .cursor(text);
+    cursor.edit(text, CaretEdit::Paste(block));
+    cursor
+}
+
+/// Maps ascending char indices of a text to byte offsets in a single forward pass.
+struct ByteOffsets<'a> {
+    text: &'a str,
+    chars: usize,
+    byte: usize,
+}
+
+impl<'a> ByteOffsets<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            text,
+            chars: 0,
+            byte: 0,
+        }
+    }
+
+    fn get(&mut self, index: usize) -> usize {
+        for c in self.text[self.byte..].chars() {
+            if self.chars >= index {
+                break;
+            }
+            self.byte += c.len_utf8();
+            self.chars += 1;
+        }
+        self.byte
+    }
+}
+
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+pub fn line_start(chars: &[char], pos: usize) -> usize {
+    chars[..pos.min(chars.len())]
+        .iter()
+        .rposition(|c| *c == '\n')
+        .map_or(0, |i| i + 1)
+}
+
+pub fn line_end(chars: &[char], pos: usize) -> usize {
+    let pos = pos.min(chars.len());
+    let end = chars[pos..]
+        .iter()
+        .position(|c| *c == '\n')
+        .map_or(chars.len(), |i| pos + i);
+    if end > pos && chars.get(end.wrapping_sub(1)) == Some(&'\r') {
+        end - 1
+    } else {
+        end
+    }
+}
+
+fn line_col(chars: &[char], pos: usize) -> (usize, usize) {
+    let start = line_start(chars, pos);
+    let line = chars[..start].iter().filter(|c| **c == '\n').count();
+    (line, pos - start)
+}
+
+/// The position in the same column on the next or previous line, clamped to its length.
+fn vertical(chars: &[char], pos: usize, down: bool) -> usize {
+    let start = line_start(chars, pos);
+    let column = pos - start;
+    let target = if down {
+        let end = line_end(chars, pos);
+        match chars[end..].iter().position(|c| *c == '\n') {
+            Some(i) => end + i + 1,
+            None => return chars.len(),
+        }
+    } else if start == 0 {
+        return 0;
+    } else {
+        line_start(chars, start - 1)
+    };
+    (target + column).min(line_end(chars, target))
+}
+
+fn word_left(chars: &[char], pos: usize) -> usize {
+    let mut pos = pos.min(chars.len());
+    while pos > 0 && !is_word(chars[pos - 1]) {
+        pos -= 1;
+    }
+    while pos > 0 && is_word(chars[pos - 1]) {
+        pos -= 1;
+    }
+    pos
+}
+
+fn word_right(chars: &[char], pos: usize) -> usize {
+    let mut pos = pos.min(chars.len());
+    while pos < chars.len() && !is_word(chars[pos]) {
+        pos += 1;
+    }
+    while pos < chars.len() && is_word(chars[pos]) {
+        pos += 1;
+    }
+    pos
+}
+
+fn word_at(chars: &[char], pos: usize) -> Range<usize> {
+    let pos = pos.min(chars.len());
+    let start = pos
+        - chars[..pos]
+            .iter()
+            .rev()
+            .take_while(|c| is_word(**c))
+            .count();
+    let end = pos + chars[pos..].iter().take_while(|c| is_word(**c)).count();
+    start..end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cursor(carets: &[Caret]) -> MultiCursor {
+        MultiCursor::new(carets.to_vec(), carets[0])
+    }
+
+    fn heads(cursor: &MultiCursor) -> Vec<usize> {
+        cursor.carets().iter().map(|c| c.head).collect()
+    }
+
+    #[test]
+    fn carets_are_sorted_and_merged() {
+        let primary = Caret::new(3, 6);
+        let carets = vec![Caret::new(4, 2), Caret::at(0), primary];
+        let cursor = MultiCursor::new(carets, primary);
+        assert_eq!(cursor.carets(), [Caret::at(0), Caret::new(6, 2)]);
+        assert_eq!(cursor.primary(), Caret::new(6, 2));
+    }
+
+    #[test]
+    fn selected_texts_are_clamped() {
+        let cursor = cursor(&[Caret::new(1, 3), Caret::new(9, 4)]);
+        assert_eq!(cursor.selected_texts("aé😀\nb"), ["é😀", "b"]);
+    }
+
+    #[test]
+    fn edit_inserts_at_every_caret() {
+        let mut text = "ab\ncd".to_string();
+        let mut cursor = cursor(&[Caret::at(1), Caret::at(4)]);
+        cursor.edit(&mut text, CaretEdit::Insert("é"));
+        assert_eq!(text, "aéb\ncéd");
+        assert_eq!(heads(&cursor), [2, 6]);
+    }
+
+    #[test]
+    fn backspace_and_delete_remove_crlf_whole() {
+        let mut text = "a\r\nb\r\nc".to_string();
+        let mut carets = cursor(&[Caret::at(3), Caret::at(6)]);
+        carets.edit(&mut text, CaretEdit::Backspace);
+        assert_eq!((text.as_str(), heads(&carets)), ("abc", vec![1, 2]));
+
+        let mut text = "a\r\nb\r\nc".to_string();
+        let mut carets = cursor(&[Caret::at(1), Caret::at(4)]);
+        carets.edit(&mut text, CaretEdit::Delete);
+        assert_eq!((text.as_str(), heads(&carets)), ("abc", vec![1, 2]));
+    }
+
+    #[test]
+    fn paste_splits_lines_across_carets() {
+        let mut text = "x\ny".to_string();
+        let mut carets = cursor(&[Caret::at(1), Caret::at(3)]);
+        carets.edit(&mut text, CaretEdit::Paste("1\r\n2\r\n"));
+        assert_eq!((text.as_str(), heads(&carets)), ("x1\ny2", vec![2, 5]));
+
+        let mut text = "x\ny".to_string();
+        let mut carets = cursor(&[Caret::at(1), Caret::at(3)]);
+        carets.edit(&mut text, CaretEdit::Paste("1\n2\n3"));
+        assert_eq!(text, "x1\n2\n3\ny1\n2\n3");
+    }
+
+    #[test]
+    fn occurrences_count_chars() {
+        let text = "é x é xé";
+        let mut carets = cursor(&[Caret::at(0)]);
+        carets.add_next_occurrence(text);
+        assert_eq!(carets.carets(), [Caret::new(0, 1)]);
+        carets.add_next_occurrence(text);
+        assert_eq!(carets.primary(), Caret::new(4, 5));
+        carets.select_all_occurrences(text);
+        let ranges: Vec<Range<usize>> = carets.carets().iter().map(Caret::range).collect();
+        assert_eq!(ranges, [0..1, 4..5, 7..8]);
+        assert_eq!(carets.primary(), Caret::new(4, 5));
+    }
+
+    #[test]
+    fn column_block_round_trip() {
+        let text = "abcd\nx\nabcdef";
+        let block = ColumnBlock {
+            anchor: (0, 1),
+            head: (2, 3),
+        };
+        let carets = block.cursor(text);
+        assert_eq!(
+            carets.carets(),
+            [Caret::new(1, 3), Caret::at(6), Caret::new(8, 10)]
+        );
+        assert_eq!(carets.primary(), Caret::new(8, 10));
+        assert_eq!(ColumnBlock::from_cursor(text, &carets), block);
+    }
+
+    #[test]
+    fn paste_column_pads_and_appends_lines() {
+        let mut text = "abc\r\nd\r\nef".to_string();
+        let carets = paste_column(&mut text, 2, "1\n2\n3\n4\n");
+        assert_eq!(text, "ab1c\r\nd 2\r\nef3\r\n  4");
+        assert_eq!(heads(&carets), [3, 9, 14, 19]);
+        assert_eq!(carets.primary(), Caret::at(19));
+
+        let mut text = "é".to_string();
+        let carets = paste_column(&mut text, 1, "x\ny");
+        assert_eq!((text.as_str(), heads(&carets)), ("éx\n y", vec![2, 5]));
+    }
+
+    #[test]
+    fn home_and_end_motions() {
+        let text = "  ab\r\ncd";
+        let mut carets = cursor(&[Caret::at(3)]);
+        carets.motion(text, Motion::Home, false);
+        assert_eq!(heads(&carets), [2]);
+        carets.motion(text, Motion::Home, false);
+        assert_eq!(heads(&carets), [0]);
+        carets.motion(text, Motion::End, true);
+        assert_eq!(carets.primary(), Caret::new(0, 4));
+    }
+}
@@ -0,0 +1,255 @@
+use super::{leading_whitespace, line_end, line_start, Caret, MultiCursor};
+
+/// Comment tokens of a syntax, as found in its metadata. Tokens may carry the space
+/// that goes between them and the text, like `"// "`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CommentTokens {
+    pub line: Option<String>,
+    pub block: Option<(String, String)>,
+}
+
+/// Comments out the lines touched by the carets, or uncomments them when all of them
+/// are commented. Syntaxes without line comments wrap each line in a block comment.
+/// Returns whether the text changed.
+pub fn toggle_line_comment(
+    cursor: &mut MultiCursor,
+    text: &mut String,
+    tokens: &CommentTokens,
+) -> bool {
+    let lines = cursor.lines(text);
+    let code: Vec<&str> = lines
+        .iter()
+        .map(String::as_str)
+        .filter(|l| !l.trim().is_empty())
+        .collect();
+    if code.is_empty() {
+        return false;
+    }
+
+    if let Some(token) = &tokens.line {
+        let mark = token.trim_end();
+        if code.iter().all(|l| l.trim_start().starts_with(mark)) {
+            return cursor.edit_lines(text, |line| {
+                let indent = leading_whitespace(line);
+                let after = line[indent.len()..].strip_prefix(mark)?;
+                let space = token.len() > mark.len() && after.starts_with(' ');
+                let removed = mark.chars().count() + usize::from(space);
+                Some((indent.chars().count(), removed, String::new()))
+            });
+        }
+        let column = code
+            .iter()
+            .map(|l| leading_whitespace(l).chars().count())
+            .min()
+            .unwrap_or(0);
+        return cursor.edit_lines(text, |line| {
+            (!line.trim().is_empty()).then(|| (column, 0, token.clone()))
+        });
+    }
+
+    let Some((open, close)) = &tokens.block else {
+        return false;
+    };
+    let uncomment = code.iter().all(|l| strip_block(l, open, close).is_some());
+    cursor.edit_lines(text, |line| {
+        let indent = leading_whitespace(line);
+        let content = &line[indent.len()..];
+        if content.trim().is_empty() {
+            return None;
+        }
+        let replaced = if uncomment {
+            strip_block(content, open, close)?
+        } else {
+            wrap_block(content, open, close)
+        };
+        Some((indent.chars().count(), content.chars().count(), replaced))
+    })
+}
+
+/// Wraps each selection in a block comment, or unwraps it when it already is one. A
+/// caret without selection toggles its line, or starts an empty comment on a blank line.
+/// Returns whether the text changed.
+pub fn toggle_block_comment(
+    cursor: &mut MultiCursor,
+    text: &mut String,
+    tokens: &CommentTokens,
+) -> bool {
+    let Some((open, close)) = &tokens.block else {
+        return false;
+    };
+    let chars: Vec<char> = text.chars().collect();
+    let primary = cursor.primary();
+    let mut carets = Vec::with_capacity(cursor.carets().len());
+    let mut pieces = Vec::with_capacity(cursor.carets().len());
+    let mut selected = Vec::with_capacity(cursor.carets().len());
+    let mut primary_index = 0;
+    for (i, &caret) in cursor.carets().iter().enumerate() {
+        if caret == primary {
+            primary_index = i;
+        }
+        let range = if caret.is_empty() {
+            line_start(&chars, caret.head)..line_end(&chars, caret.head)
+        } else {
+            caret.range()
+        };
+        let content: String = chars[range.clone()].iter().collect();
+        if content.trim().is_empty() {
+            let (open, close) = spaced(open, close);
+            carets.push(caret);
+            pieces.push((open, close));
+            selected.push(0);
+            continue;
+        }
+        let replaced =
+            strip_block(&content, open, close).unwrap_or_else(|| wrap_block(&content, open, close));
+        carets.push(Caret::new(range.start, range.end));
+        selected.push(if caret.is_empty() {
+            0
+        } else {
+            replaced.chars().count()
+        });
+        pieces.push((replaced, String::new()));
+    }
+
+    *cursor = MultiCursor::new(carets, primary);
+    cursor.insert_around(text, &pieces);
+    if cursor.carets().len() == selected.len() {
+        let carets: Vec<Caret> = cursor
+            .carets()
+            .iter()
+            .zip(&selected)
+            .map(|(c, &len)| Caret::new(c.head - len, c.head))
+            .collect();
+        let primary = carets[primary_index];
+        *cursor = MultiCursor::new(carets, primary);
+    }
+    true
+}
+
+/// The block tokens with a space between them and the text.
+fn spaced(open: &str, close: &str) -> (String, String) {
+    let open = if open.ends_with(' ') {
+        open.to_string()
+    } else {
+        format!("{} ", open)
+    };
+    let close = if close.starts_with(' ') {
+        close.to_string()
+    } else {
+        format!(" {}", close)
+    };
+    (open, close)
+}
+
+fn wrap_block(s: &str, open: &str, close: &str) -> String {
+    let start = s.len() - s.trim_start().len();
+    let trimmed = s.trim();
+    let (open, close) = spaced(open, close);
+    format!(
+        "{}{}{}{}{}",
+        &s[..start],
+        open,
+        trimmed,
+        close,
+        &s[start + trimmed.len()..]
+    )
+}
+
+/// `s` without the block comment around its trimmed content, if it has one.
+fn strip_block(s: &str, open: &str, close: &str) -> Option<String> {
+    let trimmed = s.trim();
+    let inner = trimmed
+        .strip_prefix(open.trim_end())?
+        .strip_suffix(close.trim_start())?;
+    let inner = inner.strip_prefix(' ').unwrap_or(inner);
+    let inner = inner.strip_suffix(' ').unwrap_or(inner);
+    let start = s.len() - s.trim_start().len();
+    Some(format!(
+        "{}{}{}",
+        &s[..start],
+        inner,
+        &s[start + trimmed.len()..]
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(line: Option<&str>, block: Option<(&str, &str)>) -> CommentTokens {
+        CommentTokens {
+            line: line.map(String::from),
+            block: block.map(|(open, close)| (open.to_string(), close.to_string())),
+        }
+    }
+
+    fn select_all(text: &str) -> MultiCursor {
+        let caret = Caret::new(0, text.chars().count());
+        MultiCursor::new(vec![caret], caret)
+    }
+
+    #[test]
+    fn line_comments_toggle_at_common_indent() {
+        let tokens = tokens(Some("// "), None);
+        let original = "  a\r\n\r\n    é";
+        let mut text = original.to_string();
+        assert!(toggle_line_comment(
+            &mut select_all(&text),
+            &mut text,
+            &tokens
+        ));
+        assert_eq!(text, "  // a\r\n\r\n  //   é");
+        assert!(toggle_line_comment(
+            &mut select_all(&text),
+            &mut text,
+            &tokens
+        ));
+        assert_eq!(text, original);
+        assert!(!toggle_line_comment(
+            &mut select_all(" \n"),
+            &mut " \n".into(),
+            &tokens
+        ));
+    }
+
+    #[test]
+    fn line_comments_fall_back_to_blocks() {
+        let tokens = tokens(None, Some(("<!--", "-->")));
+        let mut text = "a\n  b".to_string();
+        assert!(toggle_line_comment(
+            &mut select_all(&text),
+            &mut text,
+            &tokens
+        ));
+        assert_eq!(text, "<!-- a -->\n  <!-- b -->");
+        assert!(toggle_line_comment(
+            &mut select_all(&text),
+            &mut text,
+            &tokens
+        ));
+        assert_eq!(text, "a\n  b");
+    }
+
+    #[test]
+    fn block_comments_wrap_and_unwrap_selections() {
+        let tokens = tokens(Some("//"), Some(("/*", "*/")));
+        let mut text = "let é = 1;".to_string();
+        let mut cursor = MultiCursor::new(vec![Caret::new(4, 9)], Caret::new(4, 9));
+        assert!(toggle_block_comment(&mut cursor, &mut text, &tokens));
+        assert_eq!(text, "let /* é = 1 */;");
+        assert_eq!(cursor.primary(), Caret::new(4, 15));
+        assert!(toggle_block_comment(&mut cursor, &mut text, &tokens));
+        assert_eq!(text, "let é = 1;");
+        assert_eq!(cursor.primary(), Caret::new(4, 9));
+    }
+
+    #[test]
+    fn block_comment_on_blank_line() {
+        let tokens = tokens(None, Some(("/*", "*/")));
+        let mut text = "a\n".to_string();
+        let mut cursor = MultiCursor::new(vec![Caret::at(2)], Caret::at(2));
+        assert!(toggle_block_comment(&mut cursor, &mut text, &tokens));
+        assert_eq!(text, "a\n/*  */");
+        assert_eq!(cursor.primary(), Caret::at(5));
+    }
+}
@@ -0,0 +1,428 @@
+use similar::{DiffOp, TextDiff};
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+/// Lines longer than this are not diffed character by character.
+const MAX_INLINE_DIFF_LEN: usize = 2000;
+/// Below this similarity a changed line is shown as fully replaced.
+const MIN_INLINE_RATIO: f32 = 0.3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineTag {
+    Equal,
+    Removed,
+    Added,
+}
+
+#[derive(Clone, Debug)]
+pub struct DiffLine {
+    pub tag: LineTag,
+    pub old_line: Option<usize>,
+    pub new_line: Option<usize>,
+    pub text: String,
+    /// Byte ranges within `text` that differ from the paired line.
+    pub emphasis: Vec<Range<usize>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Modified,
+    Deleted,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Hunk {
+    pub old_lines: Range<usize>,
+    pub new_lines: Range<usize>,
+    pub inline_row: usize,
+    pub split_row: usize,
+}
+
+impl Hunk {
+    pub fn kind(&self) -> ChangeKind {
+        if self.old_lines.is_empty() {
+            ChangeKind::Added
+        } else if self.new_lines.is_empty() {
+            ChangeKind::Deleted
+        } else {
+            ChangeKind::Modified
+        }
+    }
+
+    /// Whether the gutter marker of this hunk covers `line` of a `lines`-line text. A
+    /// deletion is marked at the line after it, or at the last line when nothing follows.
+    pub fn marks_line(&self, line: usize, lines: usize) -> bool {
+        match self.kind() {
+            ChangeKind::Deleted => {
+                self.new_lines.start == line || (self.new_lines.start >= lines && line + 1 == lines)
+            }
+            _ => self.new_lines.contains(&line),
+        }
+    }
+
+    /// "Modified 2 line(s)", counting the lines on the side that has them.
+    pub fn title(&self) -> String {
+        match self.kind() {
+            ChangeKind::Added => format!("Added {} line(s)", self.new_lines.len()),
+            ChangeKind::Modified => format!("Modified {} line(s)", self.new_lines.len()),
+            ChangeKind::Deleted => format!("Deleted {} line(s)", self.old_lines.len()),
+        }
+    }
+
+    /// The hunk's lines of `old`, without line endings.
+    pub fn old_text<'a>(&self, old: &'a str) -> Vec<&'a str> {
+        old.split_inclusive('\n')
+            .skip(self.old_lines.start)
+            .take(self.old_lines.len())
+            .map(strip_newline)
+            .collect()
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct LineDiff {
+    /// Lines in unified order: removed lines precede the added lines of a hunk.
+    pub lines: Vec<DiffLine>,
+    /// Side-by-side rows as indices into `lines` for the old and new side.
+    pub rows: Vec<(Option<usize>, Option<usize>)>,
+    pub hunks: Vec<Hunk>,
+}
+
+pub fn diff_lines(old: &str, new: &str) -> LineDiff {
+    let diff = TextDiff::from_lines(old, new);
+    let old_slices = diff.old_slices();
+    let new_slices = diff.new_slices();
+    let mut out = LineDiff::default();
+
+    for op in diff.ops() {
+        match *op {
+            DiffOp::Equal {
+                old_index,
+                new_index,
+                len,
+            } => {
+                for i in 0..len {
+                    let idx = out.lines.len();
+                    out.lines.push(DiffLine {
+                        tag: LineTag::Equal,
+                        old_line: Some(old_index + i),
+                        new_line: Some(new_index + i),
+                        text: strip_newline(old_slices[old_index + i]).to_string(),
+                        emphasis: Vec::new(),
+                    });
+                    out.rows.push((Some(idx), Some(idx)));
+                }
+            }
+            DiffOp::Delete {
+                old_index,
+                old_len,
+                new_index,
+            } => push_change(
+                &mut out, old_slices, new_slices, old_index, old_len, new_index, 0,
+            ),
+            DiffOp::Insert {
+                old_index,
+                new_index,
+                new_len,
+            } => push_change(
+                &mut out, old_slices, new_slices, old_index, 0, new_index, new_len,
+            ),
+            DiffOp::Replace {
+                old_index,
+                old_len,
+                new_index,
+                new_len,
+            } => push_change(
+                &mut out, old_slices, new_slices, old_index, old_len, new_index, new_len,
+            ),
+        }
+    }
+
+    out
+}
+
+/// Computes only the changed line ranges, without per-line detail.
+pub fn diff_hunks(old: &str, new: &str) -> Vec<Hunk> {
+    let diff = TextDiff::from_lines(old, new);
+    let mut hunks = Vec::new();
+    let (mut inline_row, mut split_row) = (0, 0);
+    for op in diff.ops() {
+        let (_, old_lines, new_lines) = op.as_tag_tuple();
+        if matches!(op, DiffOp::Equal { .. }) {
+            inline_row += old_lines.len();
+            split_row += old_lines.len();
+            continue;
+        }
+        let (old_len, new_len) = (old_lines.len(), new_lines.len());
+        hunks.push(Hunk {
+            old_lines,
+            new_lines,
+            inline_row,
+            split_row,
+        });
+        inline_row += old_len + new_len;
+        split_row += old_len.max(new_len);
+    }
+    hunks
+}
+
+fn push_change(
+    out: &mut LineDiff,
+    old_slices: &[&str],
+    new_slices: &[&str],
+    old_index: usize,
+    old_len: usize,
+    new_index: usize,
+    new_len: usize,
+) {
+    out.hunks.push(Hunk {
+        old_lines: old_index..old_index + old_len,
+        new_lines: new_index..new_index + new_len,
+        inline_row: out.lines.len(),
+        split_row: out.rows.len(),
+    });
+
+    let first_removed = out.lines.len();
+    for i in 0..old_len {
+        out.lines.push(DiffLine {
+            tag: LineTag::Removed,
+            old_line: Some(old_index + i),
+            new_line: None,
+            text: strip_newline(old_slices[old_index + i]).to_string(),
+            emphasis: Vec::new(),
+        });
+    }
+    let first_added = out.lines.len();
+    for i in 0..new_len {
+        out.lines.push(DiffLine {
+            tag: LineTag::Added,
+            old_line: None,
+            new_line: Some(new_index + i),
+            text: strip_newline(new_slices[new_index + i]).to_string(),
+            emphasis: Vec::new(),
+        });
+    }
+
+    for i in 0..old_len.max(new_len) {
+        let left = (i < old_len).then_some(first_removed + i);
+        let right = (i < new_len).then_some(first_added + i);
+        if let (Some(l), Some(r)) = (left, right) {
+            let (old_emph, new_emph) = inline_emphasis(&out.lines[l].text, &out.lines[r].text);
+            out.lines[l].emphasis = old_emph;
+            out.lines[r].emphasis = new_emph;
+        }
+        out.rows.push((left, right));
+    }
+}
+
+/// Computes the changed byte ranges of two paired lines.
+pub fn inline_emphasis(old: &str, new: &str) -> (Vec<Range<usize>>, Vec<Range<usize>>) {
+    if old.len() > MAX_INLINE_DIFF_LEN || new.len() > MAX_INLINE_DIFF_LEN {
+        return (Vec::new(), Vec::new());
+    }
+    let diff = TextDiff::configure()
+        .deadline(Instant::now() + Duration::from_millis(20))
+        .diff_chars(old, new);
+    if diff.ratio() < MIN_INLINE_RATIO {
+        return (Vec::new(), Vec::new());
+    }
+
+    let old_offsets = slice_offsets(diff.old_slices());
+    let new_offsets = slice_offsets(diff.new_slices());
+    let mut old_ranges: Vec<Range<usize>> = Vec::new();
+    let mut new_ranges: Vec<Range<usize>> = Vec::new();
+
+    for op in diff.ops() {
+        let (_, old_range, new_range) = op.as_tag_tuple();
+        if matches!(op, DiffOp::Equal { .. }) {
+            continue;
+        }
+        if !old_range.is_empty() {
+            push_merged(
+                &mut old_ranges,
+                old_offsets[old_range.start]..old_offsets[old_range.end],
+            );
+        }
+        if !new_range.is_empty() {
+            push_merged(
+                &mut new_ranges,
+                new_offsets[new_range.start]..new_offsets[new_range.end],
+            );
+        }
+    }
+
+    (old_ranges, new_ranges)
+}
+
+fn slice_offsets(slices: &[&str]) -> Vec<usize> {
+    let mut offsets = Vec::with_capacity(slices.len() + 1);
+    let mut pos = 0;
+    offsets.push(0);
+    for s in slices {
+        pos += s.len();
+        offsets.push(pos);
+    }
+    offsets
+}
+
+fn push_merged(ranges: &mut Vec<Range<usize>>, range: Range<usize>) {
+    if let Some(last) = ranges.last_mut() {
+        if last.end >= range.start {
+            last.end = last.end.max(range.end);
+            return;
+        }
+    }
+    ranges.push(range);
+}
+
+pub fn strip_newline(line: &str) -> &str {
+    line.strip_suffix('\n')
+        .map(|l| l.strip_suffix('\r').unwrap_or(l))
+        .unwrap_or(line)
+}
+
+/// Replaces `target_lines` of `target` with `source_lines` of `source`.
+pub fn splice_lines(
+    target: &str,
+    target_lines: Range<usize>,
+    source: &str,
+    source_lines: Range<usize>,
+) -> String {
+    let target: Vec<&str> = target.split_inclusive('\n').collect();
+    let source: Vec<&str> = source.split_inclusive('\n').collect();
+    let start = target_lines.start.min(target.len());
+    let end = target_lines.end.min(target.len());
+
+    let mut out = String::new();
+    for line in &target[..start] {
+        out.push_str(line);
+    }
+    if !out.is_empty() && !out.ends_with('\n') {
+        out.push('\n');
+    }
+    for line in &source[source_lines.start.min(source.len())..source_lines.end.min(source.len())] {
+        out.push_str(line);
+    }
+    if end < target.len() {
+        if !out.is_empty() && !out.ends_with('\n') {
+            out.push('\n');
+        }
+        for line in &target[end..] {
+            out.push_str(line);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splice_lines_replaces_inserts_and_deletes() {
+        assert_eq!(
+            splice_lines("a\nB\nc\n", 1..2, "a\nb\nc\n", 1..2),
+            "a\nb\nc\n"
+        );
+        assert_eq!(splice_lines("a\nc\n", 1..1, "a\nb\nc\n", 1..2), "a\nb\nc\n");
+        assert_eq!(splice_lines("a\nx\nc\n", 1..2, "a\nc\n", 1..1), "a\nc\n");
+        assert_eq!(splice_lines("x\ny\n", 0..2, "", 0..0), "");
+    }
+
+    #[test]
+    fn splice_lines_without_final_newline() {
+        assert_eq!(splice_lines("a\nX\nc", 1..2, "a\nb", 1..2), "a\nb\nc");
+        assert_eq!(splice_lines("a", 1..1, "a\nb", 1..2), "a\nb");
+        assert_eq!(splice_lines("a\nb", 1..2, "a", 1..1), "a\n");
+    }
+
+    #[test]
+    fn splice_lines_crlf_and_multibyte() {
+        let target = "ä\r\nX\r\nc\r\n";
+        assert_eq!(
+            splice_lines(target, 1..2, "ä\r\nü\r\nc\r\n", 1..2),
+            "ä\r\nü\r\nc\r\n"
+        );
+        assert_eq!(
+            splice_lines(target, 5..9, "a\r\n", 0..1),
+            format!("{target}a\r\n")
+        );
+    }
+
+    #[test]
+    fn hunk_markers_and_popup_text() {
+        let old = "a\r\nb\r\nc\r\n";
+        let hunks = diff_hunks(old, "a\r\nB\r\nc\r\nd\r\n");
+        let marked = |line: usize| hunks.iter().position(|h| h.marks_line(line, 4));
+        assert_eq!(
+            (0..4).map(marked).collect::<Vec<_>>(),
+            [None, Some(0), None, Some(1)]
+        );
+        assert_eq!(hunks[0].title(), "Modified 1 line(s)");
+        assert_eq!(hunks[0].old_text(old), ["b"]);
+        assert_eq!(hunks[1].title(), "Added 1 line(s)");
+        assert!(hunks[1].old_text(old).is_empty());
+
+        let hunks = diff_hunks(old, "b\r\n");
+        let marked: Vec<bool> = (0..2).map(|l| hunks[1].marks_line(l, 2)).collect();
+        assert_eq!(marked, [false, true]);
+        assert!(hunks[0].marks_line(0, 2));
+        let end = Hunk {
+            old_lines: 2..3,
+            new_lines: 2..2,
+            inline_row: 0,
+            split_row: 0,
+        };
+        assert!(end.marks_line(1, 2));
+        assert_eq!(hunks[1].title(), "Deleted 1 line(s)");
+        assert_eq!(hunks[1].old_text(old), ["c"]);
+    }
+
+    #[test]
+    fn diff_hunks_rows() {
+        let hunks = diff_hunks("a\nb\nc\n", "a\nB\nc\nd\n");
+        assert_eq!(
+            hunks,
+            vec![
+                Hunk {
+                    old_lines: 1..2,
+                    new_lines: 1..2,
+                    inline_row: 1,
+                    split_row: 1,
+                },
+                Hunk {
+                    old_lines: 3..3,
+                    new_lines: 3..4,
+                    inline_row: 4,
+                    split_row: 3,
+                },
+            ]
+        );
+        assert_eq!(hunks[0].kind(), ChangeKind::Modified);
+        assert_eq!(hunks[1].kind(), ChangeKind::Added);
+        assert_eq!(diff_hunks("a\nb\n", "a\n")[0].kind(), ChangeKind::Deleted);
+        assert_eq!(
+            diff_lines("a\nb\n", "a\nB\n").hunks,
+            diff_hunks("a\nb\n", "a\nB\n")
+        );
+    }
+
+    #[test]
+    fn diff_lines_strip_crlf() {
+        let diff = diff_lines("a\r\nb\r\n", "a\r\nc\r\n");
+        let texts: Vec<&str> = diff.lines.iter().map(|l| l.text.as_str()).collect();
+        assert_eq!(texts, ["a", "b", "c"]);
+        assert_eq!(diff.rows, vec![(Some(0), Some(0)), (Some(1), Some(2))]);
+        assert_eq!(strip_newline("x\r\n"), "x");
+        assert_eq!(strip_newline("x\r"), "x\r");
+    }
+
+    #[test]
+    fn inline_emphasis_byte_ranges() {
+        let (old, new) = inline_emphasis("héllo wörld", "hello world");
+        assert_eq!((old, new), (vec![1..3, 8..10], vec![1..2, 7..8]));
+        let (old, new) = inline_emphasis("abc", "xyz");
+        assert!(old.is_empty() && new.is_empty());
+    }
+}
@@ -0,0 +1,644 @@
+use super::diff::{diff_lines, splice_lines, DiffLine, LineDiff, LineTag};
+use super::SyntaxHighlighter;
+use crate::theme::ThemeColors;
+use egui::text::LayoutJob;
+use egui::{Color32, FontId, RichText, Sense, TextFormat};
+use std::ops::Range;
+use std::path::PathBuf;
+
+const SIDE_GAP: f32 = 4.0;
+const HUNK_CONTEXT_ROWS: f32 = 3.0;
+
+type LineColors = Vec<(Range<usize>, Color32)>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum DiffLayout {
+    #[default]
+    SideBySide,
+    Inline,
+}
+
+impl DiffLayout {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DiffLayout::SideBySide => "Side by Side",
+            DiffLayout::Inline => "Inline",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiffSide {
+    Left,
+    Right,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DiffViewAction {
+    None,
+    Close,
+    BufferChanged,
+    Notify(String),
+}
+
+pub struct DiffPane {
+    label: String,
+    text: String,
+    syntax: Option<String>,
+    path: Option<PathBuf>,
+    editable: bool,
+    buffer: bool,
+    dirty: bool,
+}
+
+impl DiffPane {
+    pub fn new(label: impl Into<String>, text: impl Into<String>) -> Self {
+        Self {
+            label: label.into(),
+            text: text.into(),
+            syntax: None,
+            path: None,
+            editable: false,
+            buffer: false,
+            dirty: false,
+        }
+    }
+
+    /// Marks the pane as the editor buffer; hunks copied into it change the buffer.
+    pub fn buffer(mut self) -> Self {
+        self.buffer = true;
+        self.editable = true;
+        self
+    }
+
+    /// Allows hunks to be copied into the pane and saved back to `path`.
+    pub fn file(mut self, path: PathBuf) -> Self {
+        self.path = Some(path);
+        self.editable = true;
+        self
+    }
+
+    pub fn syntax(mut self, syntax: Option<String>) -> Self {
+        self.syntax = syntax;
+        self
+    }
+}
+
+pub struct DiffView {
+    title: String,
+    panes: [DiffPane; 2],
+    diff: LineDiff,
+    layout: DiffLayout,
+    sync_scroll: bool,
+    offsets: [f32; 2],
+    current_hunk: Option<usize>,
+    colors: Option<(bool, [Vec<LineColors>; 2])>,
+}
+
+impl DiffView {
+    pub fn new(title: impl Into<String>, left: DiffPane, right: DiffPane) -> Self {
+        let diff = diff_lines(&left.text, &right.text);
+        Self {
+            title: title.into(),
+            current_hunk: (!diff.hunks.is_empty()).then_some(0),
+            panes: [left, right],
+            diff,
+            layout: DiffLayout::default(),
+            sync_scroll: true,
+            offsets: [0.0; 2],
+            colors: None,
+        }
+    }
+
+    pub fn buffer_text(&self) -> Option<&str> {
+        self.panes
+            .iter()
+            .find(|p| p.buffer)
+            .map(|p| p.text.as_str())
+    }
+
+    fn recompute(&mut self) {
+        self.diff = diff_lines(&self.panes[0].text, &self.panes[1].text);
+        self.colors = None;
+        self.current_hunk = match self.current_hunk {
+            _ if self.diff.hunks.is_empty() => None,
+            Some(i) => Some(i.min(self.diff.hunks.len() - 1)),
+            None => Some(0),
+        };
+    }
+
+    /// Copies the current hunk from one pane into the other.
+    fn copy_hunk(&mut self, to: DiffSide) -> DiffViewAction {
+        let Some(hunk) = self
+            .current_hunk
+            .and_then(|i| self.diff.hunks.get(i))
+            .cloned()
+        else {
+            return DiffViewAction::None;
+        };
+        let (source, target, source_lines, target_lines) = match to {
+            DiffSide::Left => (1, 0, hunk.new_lines, hunk.old_lines),
+            DiffSide::Right => (0, 1, hunk.old_lines, hunk.new_lines),
+        };
+        if !self.panes[target].editable {
+            return DiffViewAction::None;
+        }
+        self.panes[target].text = splice_lines(
+            &self.panes[target].text,
+            target_lines,
+            &self.panes[source].text,
+            source_lines,
+        );
+        self.panes[target].dirty = true;
+        self.recompute();
+        if self.panes[target].buffer {
+            DiffViewAction::BufferChanged
+        } else {
+            DiffViewAction::None
+        }
+    }
+
+    fn save_pane(&mut self, index: usize) -> DiffViewAction {
+        let pane = &mut self.panes[index];
+        let Some(path) = pane.path.clone() else {
+            return DiffViewAction::None;
+        };
+        match std::fs::write(&path, &pane.text) {
+            Ok(()) => {
+                pane.dirty = false;
+                DiffViewAction::Notify(format!("Saved {}", pane.label))
+            }
+            Err(e) => DiffViewAction::Notify(format!("Cannot save file: {}", e)),
+        }
+    }
+
+    fn goto_hunk(&mut self, index: usize, row_height: f32) {
+        let Some(hunk) = self.diff.hunks.get(index) else {
+            return;
+        };
+        self.current_hunk = Some(index);
+        let row = match self.layout {
+            DiffLayout::SideBySide => hunk.split_row,
+            DiffLayout::Inline => hunk.inline_row,
+        };
+        let offset = ((row as f32 - HUNK_CONTEXT_ROWS) * row_height).max(0.0);
+        self.offsets = [offset; 2];
+    }
+
+    fn step_hunk(&mut self, forward: bool, row_height: f32) {
+        let count = self.diff.hunks.len();
+        if count == 0 {
+            return;
+        }
+        let next = match (self.current_hunk, forward) {
+            (None, _) => 0,
+            (Some(i), true) => (i + 1) % count,
+            (Some(i), false) => (i + count - 1) % count,
+        };
+        self.goto_hunk(next, row_height);
+    }
+
+    fn hunk_at_row(&self, row: usize) -> Option<usize> {
+        self.diff.hunks.iter().position(|h| {
+            let (start, len) = match self.layout {
+                DiffLayout::SideBySide => (h.split_row, h.old_lines.len().max(h.new_lines.len())),
+                DiffLayout::Inline => (h.inline_row, h.old_lines.len() + h.new_lines.len()),
+            };
+            (start..start + len).contains(&row)
+        })
+    }
+
+    fn is_current_row(&self, row: usize) -> bool {
+        self.current_hunk.is_some() && self.current_hunk == self.hunk_at_row(row)
+    }
+
+    fn ensure_colors(&mut self, highlighter: &SyntaxHighlighter, dark: bool, font_size: f32) {
+        if matches!(self.colors, Some((d, _)) if d == dark) {
+            return;
+        }
+        let colors = [0, 1].map(|i| {
+            let pane = &self.panes[i];
+            pane.syntax
+                .as_deref()
+                .and_then(|name| highlighter.find_syntax_by_name(name))
+                .map(|syntax| {
+                    split_line_colors(&highlighter.highlight(&pane.text, syntax, dark, font_size))
+                })
+                .unwrap_or_default()
+        });
+        self.colors = Some((dark, colors));
+    }
+
+    fn line_colors(&self, line: Option<&DiffLine>) -> &[(Range<usize>, Color32)] {
+        let (Some((_, colors)), Some(line)) = (&self.colors, line) else {
+            return &[];
+        };
+        let found = match line.tag {
+            LineTag::Added => line.new_line.and_then(|n| colors[1].get(n)),
+            _ => line.old_line.and_then(|n| colors[0].get(n)),
+        };
+        found.map(|c| c.as_slice()).unwrap_or(&[])
+    }
+
+    pub fn show(
+        &mut self,
+        ui: &mut egui::Ui,
+        highlighter: &SyntaxHighlighter,
+        dark: bool,
+        colors: &ThemeColors,
+        font_size: f32,
+    ) -> DiffViewAction {
+        let mut action = DiffViewAction::None;
+        let view = ui.max_rect();
+        let font = FontId::proportional(font_size);
+        let row_height = ui.fonts(|f| f.row_height(&font));
+        self.ensure_colors(highlighter, dark, font_size);
+
+        ui.horizontal(|ui| {
+            if ui.button("X").clicked() {
+                action = DiffViewAction::Close;
+            }
+            ui.label(RichText::new(&self.title).strong());
+            ui.separator();
+            for layout in [DiffLayout::SideBySide, DiffLayout::Inline] {
+                if ui
+                    .selectable_label(self.layout == layout, layout.name())
+                    .clicked()
+                {
+                    self.layout = layout;
+                    if let Some(i) = self.current_hunk {
+                        self.goto_hunk(i, row_height);
+                    }
+                }
+            }
+            if self.layout == DiffLayout::SideBySide {
+                ui.checkbox(&mut self.sync_scroll, "Sync Scroll");
+            }
+            ui.separator();
+            let count = self.diff.hunks.len();
+            ui.label(match (count, self.current_hunk) {
+                (0, _) => "No differences".to_string(),
+                (n, Some(i)) => format!("Change {}/{}", i + 1, n),
+                (n, None) => format!("{} changes", n),
+            });
+            if ui
+                .button("↑")
+                .on_hover_text("Previous Change (Shift+F7)")
+                .clicked()
+            {
+                self.step_hunk(false, row_height);
+            }
+            if ui.button("↓").on_hover_text("Next Change (F7)").clicked() {
+                self.step_hunk(true, row_height);
+            }
+            ui.separator();
+            let has_hunk = self.current_hunk.is_some();
+            if ui
+                .add_enabled(
+                    has_hunk && self.panes[1].editable,
+                    egui::Button::new("Copy →"),
+                )
+                .on_hover_text("Copy the current change to the right")
+                .clicked()
+            {
+                action = self.copy_hunk(DiffSide::Right);
+            }
+            if ui
+                .add_enabled(
+                    has_hunk && self.panes[0].editable,
+                    egui::Button::new("← Copy"),
+                )
+                .on_hover_text("Copy the current change to the left")
+                .clicked()
+            {
+                action = self.copy_hunk(DiffSide::Left);
+            }
+        });
+
+        // The keys belong to the view unless a widget outside it, like the search
+        // field, has keyboard focus.
+        let focused = ui.memory(|m| m.focused()).is_none_or(|id| {
+            ui.ctx()
+                .read_response(id)
+                .is_some_and(|r| view.contains_rect(r.rect))
+        });
+        if focused {
+            ui.input_mut(|i| {
+                if i.consume_key(egui::Modifiers::NONE, egui::Key::Escape) {
+                    action = DiffViewAction::Close;
+                }
+                if i.consume_key(egui::Modifiers::SHIFT, egui::Key::F7) {
+                    self.step_hunk(false, row_height);
+                } else if i.consume_key(egui::Modifiers::NONE, egui::Key::F7) {
+                    self.step_hunk(true, row_height);
+                }
+            });
+        }
+
+        ui.horizontal(|ui| {
+            let width = (ui.available_width() - SIDE_GAP) / 2.0;
+            for i in 0..2 {
+                ui.allocate_ui(egui::vec2(width, row_height), |ui| {
+                    ui.set_width(width);
+                    let pane = &self.panes[i];
+                    let label = if pane.dirty {
+                        format!("{} (modified)", pane.label)
+                    } else {
+                        pane.label.clone()
+                    };
+                    let can_save = pane.dirty && pane.path.is_some();
+                    ui.add(egui::Label::new(label).truncate());
+                    if can_save && ui.small_button("Save").clicked() {
+                        action = self.save_pane(i);
+                    }
+                });
+            }
+        });
+        ui.separator();
+
+        let number_width = ui.fonts(|f| {
+            let digits = self.diff.lines.len().max(1).to_string().len().max(3);
+            f.glyph_width(&font, '0') * digits as f32 + 8.0
+        });
+        let mut clicked_row = None;
+        ui.spacing_mut().item_spacing.y = 0.0;
+
+        match self.layout {
+            DiffLayout::SideBySide => {
+                let forced = self.offsets;
+                let mut new_offsets = forced;
+                ui.horizontal_top(|ui| {
+                    ui.spacing_mut().item_spacing.x = SIDE_GAP;
+                    let width = (ui.available_width() - SIDE_GAP) / 2.0;
+                    let height = ui.available_height();
+                    for (side, salt) in [(0, "diff_left"), (1, "diff_right")] {
+                        ui.allocate_ui(egui::vec2(width, height), |ui| {
+                            let output = egui::ScrollArea::vertical()
+                                .id_salt(salt)
+                                .vertical_scroll_offset(forced[side])
+                                .auto_shrink([false, false])
+                                .show_rows(ui, row_height, self.diff.rows.len(), |ui, range| {
+                                    for row in range {
+                                        let (left, right) = self.diff.rows[row];
+                                        let line = if side == 0 { left } else { right }
+                                            .map(|l| &self.diff.lines[l]);
+                                        let number = line.and_then(|l| {
+                                            if side == 0 {
+                                                l.old_line
+                                            } else {
+                                                l.new_line
+                                            }
+                                        });
+                                        let response = paint_row(
+                                            ui,
+                                            RowParams {
+                                                size: [ui.available_width(), row_height],
+                                                line,
+                                                numbers: &[number],
+                                                number_width,
+                                                current: self.is_current_row(row),
+                                                syntax: self.line_colors(line),
+                                                colors,
+                                                font: &font,
+                                            },
+                                        );
+                                        if response.clicked() {
+                                            clicked_row = Some(row);
+                                        }
+                                    }
+                                });
+                            new_offsets[side] = output.state.offset.y;
+                        });
+                    }
+                });
+                if self.sync_scroll {
+                    let moved = if (new_offsets[0] - forced[0]).abs() > 0.5 {
+                        new_offsets[0]
+                    } else {
+                        new_offsets[1]
+                    };
+                    self.offsets = [moved; 2];
+                } else {
+                    self.offsets = new_offsets;
+                }
+            }
+            DiffLayout::Inline => {
+                let output = egui::ScrollArea::vertical()
+                    .id_salt("diff_inline")
+                    .vertical_scroll_offset(self.offsets[0])
+                    .auto_shrink([false, false])
+                    .show_rows(ui, row_height, self.diff.lines.len(), |ui, range| {
+                        let width = ui.available_width();
+                        for row in range {
+                            let line = &self.diff.lines[row];
+                            let response = paint_row(
+                                ui,
+                                RowParams {
+                                    size: [width, row_height],
+                                    line: Some(line),
+                                    numbers: &[line.old_line, line.new_line],
+                                    number_width,
+                                    current: self.is_current_row(row),
+                                    syntax: self.line_colors(Some(line)),
+                                    colors,
+                                    font: &font,
+                                },
+                            );
+                            if response.clicked() {
+                                clicked_row = Some(row);
+                            }
+                        }
+                    });
+                self.offsets = [output.state.offset.y; 2];
+            }
+        }
+
+        if let Some(hunk) = clicked_row.and_then(|row| self.hunk_at_row(row)) {
+            self.current_hunk = Some(hunk);
+        }
+
+        action
+    }
+}
+
+/// Splits a highlighted layout job into per-line color runs relative to each line.
+fn split_line_colors(job: &LayoutJob) -> Vec<LineColors> {
+    let mut starts = vec![0];
+    starts.extend(job.text.match_indices('\n').map(|(i, _)| i + 1));
+    let mut lines: Vec<LineColors> = vec![Vec::new(); starts.len()];
+
+    for section in &job.sections {
+        let range = &section.byte_range;
+        let line = starts.partition_point(|&s| s <= range.start) - 1;
+        let line_start = starts[line];
+        let line_end = starts
+            .get(line + 1)
+            .map(|&s| s - 1)
+            .unwrap_or(job.text.len());
+        let end = range.end.min(line_end);
+        if end > range.start {
+            lines[line].push((
+                range.start - line_start..end - line_start,
+                section.format.color,
+            ));
+        }
+    }
+    lines
+}
+
+struct RowParams<'a> {
+    size: [f32; 2],
+    line: Option<&'a DiffLine>,
+    numbers: &'a [Option<usize>],
+    number_width: f32,
+    current: bool,
+    syntax: &'a [(Range<usize>, Color32)],
+    colors: &'a ThemeColors,
+    font: &'a FontId,
+}
+
+fn paint_row(ui: &mut egui::Ui, p: RowParams<'_>) -> egui::Response {
+    let (rect, response) = ui.allocate_exact_size(p.size.into(), Sense::click());
+    let painter = ui.painter_at(rect);
+
+    let Some(line) = p.line else {
+        painter.rect_filled(rect, 0.0, p.colors.gutter_bg);
+        return response;
+    };
+
+    let (bg, emph, sign) = match line.tag {
+        LineTag::Equal => (Color32::TRANSPARENT, Color32::TRANSPARENT, " "),
+        LineTag::Removed => (p.colors.diff_removed_bg, p.colors.diff_removed_emph, "-"),
+        LineTag::Added => (p.colors.diff_added_bg, p.colors.diff_added_emph, "+"),
+    };
+    painter.rect_filled(rect, 0.0, bg);
+    if p.current {
+        painter.rect_filled(
+            egui::Rect::from_min_size(rect.min, egui::vec2(3.0, rect.height())),
+            0.0,
+            p.colors.accent,
+        );
+    }
+
+    let mut x = rect.left();
+    for number in p.numbers {
+        x += p.number_width;
+        if let Some(n) = number {
+            painter.text(
+                egui::pos2(x - 6.0, rect.top()),
+                egui::Align2::RIGHT_TOP,
+                (n + 1).to_string(),
+                p.font.clone(),
+                p.colors.line_number,
+            );
+        }
+    }
+    painter.text(
+        egui::pos2(x, rect.top()),
+        egui::Align2::LEFT_TOP,
+        sign,
+        p.font.clone(),
+        p.colors.dim_text,
+    );
+    x += p.font.size;
+
+    let job = line_job(line, p.syntax, p.font, p.colors.text, emph);
+    let galley = ui.fonts(|f| f.layout_job(job));
+    painter.galley(egui::pos2(x, rect.top()), galley, p.colors.text);
+    response
+}
+
+fn line_job(
+    line: &DiffLine,
+    syntax: &[(Range<usize>, Color32)],
+    font: &FontId,
+    color: Color32,
+    emph: Color32,
+) -> LayoutJob {
+    let len = line.text.len();
+    let mut cuts: Vec<usize> = vec![0, len];
+    for (range, _) in syntax {
+        cuts.extend([range.start.min(len), range.end.min(len)]);
+    }
+    for range in &line.emphasis {
+        cuts.extend([range.start, range.end]);
+    }
+    cuts.sort_unstable();
+    cuts.dedup();
+
+    let mut job = LayoutJob::default();
+    for pair in cuts.windows(2) {
+        let (start, end) = (pair[0], pair[1]);
+        let Some(piece) = line.text.get(start..end) else {
+            continue;
+        };
+        let piece_color = syntax
+            .iter()
+            .find(|(r, _)| r.contains(&start))
+            .map(|(_, c)| *c)
+            .unwrap_or(color);
+        let mut format = TextFormat::simple(font.clone(), piece_color);
+        if line.emphasis.iter().any(|r| r.contains(&start)) {
+            format.background = emph;
+        }
+        job.append(piece, 0.0, format);
+    }
+    job
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OLD: &str = "a\nb\nc\nd\n";
+    const NEW: &str = "a\nB\nc\nd\ne\n";
+
+    #[test]
+    fn step_hunk_wraps_and_scrolls() {
+        let mut view = DiffView::new("t", DiffPane::new("l", OLD), DiffPane::new("r", NEW));
+        assert_eq!(view.current_hunk, Some(0));
+        view.step_hunk(true, 10.0);
+        assert_eq!(view.current_hunk, Some(1));
+        assert_eq!(view.offsets, [10.0; 2]);
+        view.step_hunk(true, 10.0);
+        assert_eq!(view.current_hunk, Some(0));
+        assert_eq!(view.offsets, [0.0; 2]);
+        view.step_hunk(false, 10.0);
+        assert_eq!(view.current_hunk, Some(1));
+        assert_eq!(view.hunk_at_row(4), Some(1));
+        assert!(view.is_current_row(4) && !view.is_current_row(1));
+
+        let mut same = DiffView::new("t", DiffPane::new("l", OLD), DiffPane::new("r", OLD));
+        same.step_hunk(true, 10.0);
+        assert_eq!(same.current_hunk, None);
+    }
+
+    #[test]
+    fn copy_hunk_into_editable_panes() {
+        let left = DiffPane::new("l", OLD);
+        let mut view = DiffView::new("t", left, DiffPane::new("r", NEW).buffer());
+        assert_eq!(view.copy_hunk(DiffSide::Left), DiffViewAction::None);
+        assert_eq!(view.panes[0].text, OLD);
+
+        assert_eq!(
+            view.copy_hunk(DiffSide::Right),
+            DiffViewAction::BufferChanged
+        );
+        assert_eq!(view.buffer_text(), Some("a\nb\nc\nd\ne\n"));
+        assert!(view.panes[1].dirty);
+        assert_eq!(view.diff.hunks.len(), 1);
+        assert_eq!(view.current_hunk, Some(0));
+
+        let path = std::env::temp_dir().join("memochan-diff-view.txt");
+        let mut view = DiffView::new(
+            "t",
+            DiffPane::new("l", OLD).file(path),
+            DiffPane::new("r", NEW),
+        );
+        view.step_hunk(true, 10.0);
+        assert_eq!(view.copy_hunk(DiffSide::Left), DiffViewAction::None);
+        assert_eq!(view.panes[0].text, "a\nb\nc\nd\ne\n");
+        assert_eq!(view.current_hunk, Some(0));
+        assert!(view.panes[0].dirty);
+    }
+}
@@ -0,0 +1,271 @@
+use super::line_ranges;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
+
+/// A foldable block of lines. The header line stays visible; the lines after it up to
+/// `end` (exclusive) are hidden when folded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FoldRegion {
+    pub header: usize,
+    pub end: usize,
+    /// Nesting depth, 1 for regions that no other region contains.
+    pub level: usize,
+}
+
+impl FoldRegion {
+    pub fn contains(&self, line: usize) -> bool {
+        (self.header..self.end).contains(&line)
+    }
+}
+
+/// Finds the foldable regions of `text`: indented blocks, bracket pairs spanning several
+/// lines and, in Markdown, sections under headings. Brackets at char indices for which
+/// `skip` returns true are ignored. At most one region starts on each line.
+pub fn fold_regions(
+    text: &str,
+    markdown: bool,
+    tab_width: usize,
+    skip: impl Fn(usize) -> bool,
+) -> Vec<FoldRegion> {
+    let lines: Vec<&str> = line_ranges(text).into_iter().map(|r| &text[r]).collect();
+    let mut ends: BTreeMap<usize, usize> = BTreeMap::new();
+    let mut add = |header: usize, end: usize| {
+        if end > header + 1 {
+            let e = ends.entry(header).or_insert(end);
+            *e = (*e).max(end);
+        }
+    };
+
+    // Indentation: a line followed by more deeply indented lines.
+    let indent = |line: &str| {
+        line.chars()
+            .take_while(|c| c.is_whitespace())
+            .fold(0, |w, c| match c {
+                '\t' => w + tab_width - w % tab_width,
+                _ => w + 1,
+            })
+    };
+    let mut stack: Vec<(usize, usize)> = Vec::new();
+    let mut last = 0;
+    for (i, line) in lines.iter().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let width = indent(line);
+        while let Some(&(w, header)) = stack.last() {
+            if w < width {
+                break;
+            }
+            stack.pop();
+            add(header, last + 1);
+        }
+        stack.push((width, i));
+        last = i;
+    }
+    for (_, header) in stack {
+        add(header, last + 1);
+    }
+
+    if markdown {
+        // Headings, outside fenced code blocks.
+        let mut headings: Vec<(usize, usize)> = Vec::new();
+        let mut fenced = false;
+        for (i, line) in lines.iter().enumerate() {
+            let trimmed = line.trim_start();
+            if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+                fenced = !fenced;
+            }
+            let level = trimmed.len() - trimmed.trim_start_matches('#').len();
+            if !fenced && (1..=6).contains(&level) && trimmed[level..].starts_with(' ') {
+                headings.push((i, level));
+            }
+        }
+        for (n, &(header, level)) in headings.iter().enumerate() {
+            let mut end = headings[n + 1..]
+                .iter()
+                .find(|&&(_, l)| l <= level)
+                .map_or(lines.len(), |&(line, _)| line);
+            while end > header + 1 && lines[end - 1].trim().is_empty() {
+                end -= 1;
+            }
+            add(header, end);
+        }
+    } else {
+        // Brackets: the lines between an opener and its closer, which stays visible.
+        let mut open: Vec<(char, usize)> = Vec::new();
+        let mut line = 0;
+        for (i, c) in text.chars().enumerate() {
+            match c {
+                '\n' => line += 1,
+                _ if skip(i) => {}
+                '{' | '[' | '(' => open.push((c, line)),
+                '}' | ']' | ')' => {
+                    let opener = match c {
+                        '}' => '{',
+                        ']' => '[',
+                        _ => '(',
+                    };
+                    if let Some(at) = open.iter().rposition(|&(o, _)| o == opener) {
+                        let (_, header) = open[at];
+                        open.truncate(at);
+                        add(header, line);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut regions: Vec<FoldRegion> = Vec::with_capacity(ends.len());
+    let mut enclosing: Vec<usize> = Vec::new();
+    for (header, end) in ends {
+        while enclosing.last().is_some_and(|&e| e <= header) {
+            enclosing.pop();
+        }
+        regions.push(FoldRegion {
+            header,
+            end,
+            level: enclosing.len() + 1,
+        });
+        enclosing.push(end);
+    }
+    regions
+}
+
+/// Byte ranges of `text` hidden by the folded regions, with their header lines: from the
+/// end of each header line to the end of the region's last line, including a `\r` before
+/// its line break. Folds inside other folds are covered by them.
+pub fn hidden_ranges(
+    text: &str,
+    regions: &[FoldRegion],
+    folded: &BTreeSet<usize>,
+) -> Vec<(usize, Range<usize>)> {
+    let lines = line_ranges(text);
+    let mut hidden: Vec<(usize, Range<usize>)> = Vec::new();
+    let mut covered = 0;
+    for region in regions.iter().filter(|r| folded.contains(&r.header)) {
+        if region.header < covered || region.end > lines.len() {
+            continue;
+        }
+        let end = lines[region.end - 1].end;
+        let end = end + usize::from(text[end..].starts_with('\r'));
+        hidden.push((region.header, lines[region.header].end..end));
+        covered = region.end;
+    }
+    hidden
+}
+
+/// Moves the zero-based `lines` of `old` to where they are in `new`, assuming a single
+/// edited stretch of lines. The first edited line keeps its number; lines after it
+/// inside the edit are dropped.
+pub fn remap_lines(old: &str, new: &str, lines: &BTreeSet<usize>) -> BTreeSet<usize> {
+    let (old_lines, new_lines): (Vec<&str>, Vec<&str>) =
+        (old.split('\n').collect(), new.split('\n').collect());
+    let prefix = old_lines
+        .iter()
+        .zip(&new_lines)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let max_suffix = old_lines.len().min(new_lines.len()) - prefix;
+    let suffix = old_lines
+        .iter()
+        .rev()
+        .zip(new_lines.iter().rev())
+        .take(max_suffix)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let edited_end = old_lines.len() - suffix;
+    let delta = new_lines.len() as isize - old_lines.len() as isize;
+    lines
+        .iter()
+        .filter_map(|&line| {
+            if line >= edited_end {
+                Some((line as isize + delta) as usize)
+            } else if line <= prefix {
+                Some(line)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region(header: usize, end: usize, level: usize) -> FoldRegion {
+        FoldRegion { header, end, level }
+    }
+
+    const CODE: &str = "fn a() {\r\n    if x {\r\n        y();\r\n    }\r\n}\r\nz\r\n";
+
+    #[test]
+    fn fold_regions_by_indent_and_brackets_crlf() {
+        let regions = fold_regions(CODE, false, 4, |_| false);
+        assert_eq!(regions, vec![region(0, 4, 1), region(1, 3, 2)]);
+    }
+
+    #[test]
+    fn fold_regions_skips_brackets() {
+        let text = "a = \"{\"\nb\nc\n}";
+        assert_eq!(
+            fold_regions(text, false, 4, |_| false),
+            vec![region(0, 3, 1)]
+        );
+        assert!(fold_regions(text, false, 4, |i| i == 5).is_empty());
+    }
+
+    #[test]
+    fn fold_regions_tabs_count_to_tab_stops() {
+        let text = "a\n\t  b\n        c\nd";
+        assert_eq!(
+            fold_regions(text, false, 4, |_| false),
+            vec![region(0, 3, 1), region(1, 3, 2)]
+        );
+        assert_eq!(
+            fold_regions(text, false, 8, |_| false),
+            vec![region(0, 3, 1)]
+        );
+    }
+
+    #[test]
+    fn fold_regions_markdown_headings() {
+        let text = "# A\ntext\n## B\n```\n# not\n```\nmore\n\n# C\nx";
+        assert_eq!(
+            fold_regions(text, true, 4, |_| false),
+            vec![region(0, 7, 1), region(2, 7, 2), region(8, 10, 1)]
+        );
+    }
+
+    #[test]
+    fn hidden_ranges_cover_crlf_and_nested_folds() {
+        let regions = fold_regions(CODE, false, 4, |_| false);
+        let hidden = hidden_ranges(CODE, &regions, &BTreeSet::from([1]));
+        assert_eq!(hidden, vec![(1, 20..35)]);
+        let hidden = hidden_ranges(CODE, &regions, &BTreeSet::from([0, 1]));
+        assert_eq!(hidden, vec![(0, 8..42)]);
+        assert_eq!(&CODE[42..], "\n}\r\nz\r\n");
+    }
+
+    #[test]
+    fn remap_lines_follows_edits() {
+        let old = "a\nb {\nc\n}\nd {\ne\n}";
+        let folds = BTreeSet::from([1, 4]);
+        let remap = |new: &str| remap_lines(old, new, &folds);
+        assert_eq!(remap(&format!("x\n{old}")), BTreeSet::from([2, 5]));
+        assert_eq!(remap("a\nb {\ncc\n}\nd {\ne\n}"), folds);
+        assert_eq!(remap("a\nbb {\nc\n}\nd {\ne\n}"), folds);
+        assert_eq!(remap("a\nb {\nc\n}\nd {\ne\n}\nf"), folds);
+        assert_eq!(remap("a\nd {\ne\n}"), BTreeSet::from([1]));
+        assert_eq!(remap("a\nb {\nc\nd {\ne\n}"), BTreeSet::from([1, 3]));
+    }
+
+    #[test]
+    fn remap_lines_crlf() {
+        let folds = BTreeSet::from([1]);
+        let new = remap_lines("a\r\nb\r\nc", "a\r\nx\r\nb\r\nc", &folds);
+        assert_eq!(new, BTreeSet::from([2]));
+        assert_eq!(remap_lines("a\r\nb\r\nc", "a\r\nb\r\n", &folds), folds);
+    }
+}
@@ -0,0 +1,83 @@
+/// Zero-based destination of a "Go to Line" request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct GotoTarget {
+    pub line: usize,
+    pub column: Option<usize>,
+}
+
+/// Parses "42", "42:7", "+5", "-3", "50%" or ":7". Input is one-based, `current_line` is
+/// zero-based, and the resulting line is clamped to the document.
+pub fn parse_goto(input: &str, current_line: usize, line_count: usize) -> Option<GotoTarget> {
+    let input = input.trim();
+    let (line_part, column_part) = match input.split_once(':') {
+        Some((line, column)) => (line.trim(), Some(column.trim())),
+        None => (input, None),
+    };
+    let last = line_count.saturating_sub(1);
+
+    let line = if line_part.is_empty() {
+        column_part?;
+        current_line
+    } else if let Some(n) = line_part.strip_prefix('+') {
+        current_line.saturating_add(n.parse().ok()?)
+    } else if let Some(n) = line_part.strip_prefix('-') {
+        current_line.saturating_sub(n.parse().ok()?)
+    } else if let Some(n) = line_part.strip_suffix('%') {
+        let percent: f64 = n.trim().parse().ok()?;
+        (last as f64 * percent.clamp(0.0, 100.0) / 100.0).round() as usize
+    } else {
+        line_part.parse::<usize>().ok()?.saturating_sub(1)
+    };
+
+    let column = match column_part {
+        Some(c) => Some(c.parse::<usize>().ok()?.saturating_sub(1)),
+        None => None,
+    };
+    Some(GotoTarget {
+        line: line.min(last),
+        column,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn goto(input: &str) -> Option<(usize, Option<usize>)> {
+        parse_goto(input, 10, 101).map(|t| (t.line, t.column))
+    }
+
+    #[test]
+    fn parse_goto_lines_and_columns() {
+        assert_eq!(goto("42"), Some((41, None)));
+        assert_eq!(goto(" 42 : 7 "), Some((41, Some(6))));
+        assert_eq!(goto(":7"), Some((10, Some(6))));
+        assert_eq!(goto("0"), Some((0, None)));
+        assert_eq!(goto("1000"), Some((100, None)));
+    }
+
+    #[test]
+    fn parse_goto_relative_and_percent() {
+        assert_eq!(goto("+5"), Some((15, None)));
+        assert_eq!(goto("-3"), Some((7, None)));
+        assert_eq!(goto("-30"), Some((0, None)));
+        assert_eq!(goto("+500"), Some((100, None)));
+        assert_eq!(goto("50%"), Some((50, None)));
+        assert_eq!(goto("150%"), Some((100, None)));
+        assert_eq!(goto("25.5%:2"), Some((26, Some(1))));
+    }
+
+    #[test]
+    fn parse_goto_rejects_garbage() {
+        for input in ["", "  ", ":", "abc", "4:x", "+", "%", "１２"] {
+            assert_eq!(goto(input), None, "{input:?}");
+        }
+        assert_eq!(
+            parse_goto("5", 0, 0),
+            Some(GotoTarget {
+                line: 0,
+                column: None
+            })
+        );
+    }
+}
@@ -0,0 +1,123 @@
+use super::byte_ranges;
+use egui::{Galley, Rangef};
+use std::ops::Range;
+
+/// Returns the vertical extent of every logical line in `galley`, covering all of its
+/// wrapped rows. Positions are relative to the galley origin.
+pub fn line_spans(galley: &Galley) -> Vec<Rangef> {
+    let mut spans = Vec::new();
+    let mut current: Option<Rangef> = None;
+    for row in &galley.rows {
+        let span = Rangef::new(row.min_y(), row.max_y());
+        current = Some(match current {
+            Some(c) => Rangef::new(c.min, span.max),
+            None => span,
+        });
+        if row.ends_with_newline {
+            spans.extend(current.take());
+        }
+    }
+    spans.extend(current);
+    if spans.is_empty() {
+        spans.push(Rangef::new(0.0, galley.rect.height()));
+    }
+    spans
+}
+
+/// Returns the line whose rows cover galley-relative `y`, passing over folded lines, or
+/// the last line below the text.
+pub fn line_at(spans: &[Rangef], y: f32) -> usize {
+    spans
+        .iter()
+        .position(|s| s.span() > 0.0 && s.max > y)
+        .unwrap_or(spans.len().saturating_sub(1))
+}
+
+/// Returns the zero-based line of each byte offset in `offsets`, which must be sorted.
+pub fn lines_of(text: &str, offsets: impl IntoIterator<Item = usize>) -> Vec<usize> {
+    let (mut line, mut pos) = (0, 0);
+    offsets
+        .into_iter()
+        .map(|offset| {
+            let offset = offset.clamp(pos, text.len());
+            line += text.as_bytes()[pos..offset]
+                .iter()
+                .filter(|&&b| b == b'\n')
+                .count();
+            pos = offset;
+            line
+        })
+        .collect()
+}
+
+/// Returns the zero-based lines holding the sorted char indices `heads`, each once.
+pub fn caret_lines(text: &str, heads: &[usize]) -> Vec<usize> {
+    let ranges: Vec<Range<usize>> = heads.iter().map(|&h| h..h).collect();
+    let bytes = byte_ranges(text, &ranges);
+    let mut lines = lines_of(text, bytes.into_iter().map(|r| r.start));
+    lines.dedup();
+    lines
+}
+
+/// Returns the char index at which zero-based `line` starts, or the end of `text`.
+pub fn line_start_char(text: &str, line: usize) -> usize {
+    text.split_inclusive('\n')
+        .take(line)
+        .map(|l| l.chars().count())
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEXT: &str = "a\r\né\nb";
+
+    #[test]
+    fn line_at_wrapped_and_folded_lines() {
+        let ctx = egui::Context::default();
+        let mut spans = Vec::new();
+        let _ = ctx.run(Default::default(), |ctx| {
+            let font = egui::FontId::proportional(14.0);
+            let galley = ctx.fonts(|f| {
+                f.layout(
+                    "aaaa aaaa aaaa\nb\nc".into(),
+                    font,
+                    egui::Color32::WHITE,
+                    40.0,
+                )
+            });
+            spans = line_spans(&galley);
+        });
+        let row = spans[1].span();
+        assert_eq!(spans.len(), 3);
+        assert!(spans[0].span() > 2.0 * row);
+        assert_eq!(line_at(&spans, spans[0].max - 1.0), 0);
+        assert_eq!(line_at(&spans, spans[1].min + 1.0), 1);
+        assert_eq!(line_at(&spans, spans[2].max + 100.0), 2);
+
+        spans[1] = Rangef::new(spans[1].min, spans[1].min);
+        assert_eq!(line_at(&spans, spans[1].min + 1.0), 2);
+    }
+
+    #[test]
+    fn lines_of_sorted_offsets() {
+        assert_eq!(lines_of(TEXT, [0, 3, 5, 6, 100]), [0, 1, 1, 2, 2]);
+        assert_eq!(lines_of(TEXT, []), Vec::<usize>::new());
+    }
+
+    #[test]
+    fn caret_lines_dedup() {
+        assert_eq!(caret_lines(TEXT, &[0, 2, 3, 4, 6]), [0, 1, 2]);
+        assert_eq!(caret_lines(TEXT, &[5]), [2]);
+        assert_eq!(caret_lines("", &[0]), [0]);
+    }
+
+    #[test]
+    fn line_start_char_counts_chars() {
+        assert_eq!(line_start_char(TEXT, 0), 0);
+        assert_eq!(line_start_char(TEXT, 1), 3);
+        assert_eq!(line_start_char(TEXT, 2), 5);
+        assert_eq!(line_start_char(TEXT, 9), 6);
+    }
+}
@@ -1,7 +1,9 @@
-use egui::text::LayoutJob;
+use egui::text::{LayoutJob, LayoutSection, TextFormat};
+use egui::Color32;
+use std::ops::Range;
 use syntect::easy::HighlightLines;
 use syntect::highlighting::{FontStyle, Theme as SyntectTheme, ThemeSet};
-use syntect::parsing::{SyntaxReference, SyntaxSet};
+use syntect::parsing::{ParseState, Scope, ScopeStack, SyntaxReference, SyntaxSet};
 use syntect::util::LinesWithEndings;
 
 pub struct SyntaxHighlighter {
@@ -72,6 +74,128 @@ impl SyntaxHighlighter {
         job
     }
 
+    /// Highlights `text` with the named syntax, or lays it out in `plain` color when the
+    /// name is `None` or unknown.
+    pub fn highlight_or_plain(
+        &self,
+        text: &str,
+        syntax_name: Option<&str>,
+        dark: bool,
+        font_size: f32,
+        plain: egui::Color32,
+    ) -> LayoutJob {
+        match syntax_name.and_then(|name| self.find_syntax_by_name(name)) {
+            Some(syntax) => self.highlight(text, syntax, dark, font_size),
+            None => {
+                let mut job = LayoutJob::default();
+                job.append(
+                    text,
+                    0.0,
+                    egui::TextFormat::simple(egui::FontId::proportional(font_size), plain),
+                );
+                job
+            }
+        }
+    }
+
+    /// Whether a line ending in `line` should indent the next one. Uses the indentation
+    /// rules of the named syntax, or a trailing opening bracket when it has none.
+    pub fn opens_block(&self, syntax_name: Option<&str>, line: &str) -> bool {
+        let syntax = syntax_name.and_then(|name| self.find_syntax_by_name(name));
+        let metadata = syntax.map(|s| self.syntax_set.metadata().metadata_for_scope(&[s.scope]));
+        match metadata {
+            Some(m)
+                if m.items
+                    .iter()
+                    .any(|(_, set)| set.items.increase_indent_pattern.is_some()) =>
+            {
+                m.increase_indent(line)
+            }
+            _ => line.trim_end().ends_with(['{', '[', '(']),
+        }
+    }
+
+    /// Comment tokens from the metadata of the named syntax.
+    pub fn comment_tokens(&self, syntax_name: Option<&str>) -> super::CommentTokens {
+        let Some(syntax) = syntax_name.and_then(|name| self.find_syntax_by_name(name)) else {
+            return Default::default();
+        };
+        let metadata = self
+            .syntax_set
+            .metadata()
+            .metadata_for_scope(&[syntax.scope]);
+        super::CommentTokens {
+            line: metadata.line_comment().map(str::to_string),
+            block: metadata
+                .block_comment()
+                .map(|(open, close)| (open.to_string(), close.to_string())),
+        }
+    }
+
+    /// Byte ranges of `text` inside string or comment scopes of the named syntax.
+    pub fn literal_ranges(&self, text: &str, syntax_name: Option<&str>) -> Vec<Range<usize>> {
+        self.scope_ranges(text, syntax_name, &["string", "comment"])
+    }
+
+    /// The pairs typed together in the named syntax: brackets, plus the quotes that
+    /// start a string in it.
+    pub fn auto_pairs(&self, syntax_name: Option<&str>) -> Vec<(char, char)> {
+        let mut pairs = super::BRACKETS.to_vec();
+        for quote in ['"', '\'', '`'] {
+            // Two chars inside, so that char literals and lifetimes don't count.
+            let sample = format!("{0}ab{0}\n", quote);
+            let ranges = self.scope_ranges(&sample, syntax_name, &["string"]);
+            if ranges.first().is_some_and(|r| r.start == 0) {
+                pairs.push((quote, quote));
+            }
+        }
+        pairs
+    }
+
+    fn scope_ranges(
+        &self,
+        text: &str,
+        syntax_name: Option<&str>,
+        scopes: &[&str],
+    ) -> Vec<Range<usize>> {
+        let Some(syntax) = syntax_name.and_then(|name| self.find_syntax_by_name(name)) else {
+            return Vec::new();
+        };
+        let scopes: Vec<Scope> = scopes.iter().filter_map(|s| Scope::new(s).ok()).collect();
+        let inside = |stack: &ScopeStack| {
+            stack
+                .as_slice()
+                .iter()
+                .any(|s| scopes.iter().any(|scope| scope.is_prefix_of(*s)))
+        };
+
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+        let mut mark = |range: Range<usize>| match ranges.last_mut() {
+            Some(last) if last.end == range.start => last.end = range.end,
+            _ if !range.is_empty() => ranges.push(range),
+            _ => {}
+        };
+        let mut state = ParseState::new(syntax);
+        let mut stack = ScopeStack::new();
+        let mut offset = 0;
+        for line in LinesWithEndings::from(text) {
+            let ops = state.parse_line(line, &self.syntax_set).unwrap_or_default();
+            let mut last = 0;
+            for (i, op) in &ops {
+                if inside(&stack) {
+                    mark(offset + last..offset + i);
+                }
+                let _ = stack.apply(op);
+                last = *i;
+            }
+            if inside(&stack) {
+                mark(offset + last..offset + line.len());
+            }
+            offset += line.len();
+        }
+        ranges
+    }
+
     #[allow(dead_code)]
     pub fn syntax_set(&self) -> &SyntaxSet {
         &self.syntax_set
@@ -89,3 +213,231 @@ impl SyntaxHighlighter {
         self.syntax_set.syntaxes()
     }
 }
+
+/// Widens every tab by `extra` points, adding leading space to the text after each run
+/// of tabs since egui draws a tab as a fixed number of spaces.
+pub fn widen_tabs(job: &mut LayoutJob, extra: f32) {
+    let mut points = Vec::new();
+    let mut run = 0;
+    for (i, b) in job.text.bytes().enumerate() {
+        if b == b'\t' {
+            run += 1;
+            continue;
+        }
+        if run > 0 && b != b'\n' && b != b'\r' {
+            points.push((i, run));
+        }
+        run = 0;
+    }
+    if extra == 0.0 || points.is_empty() {
+        return;
+    }
+
+    let mut points = points.into_iter().peekable();
+    let mut sections = Vec::with_capacity(job.sections.len() + points.len());
+    for mut part in job.sections.drain(..) {
+        while let Some((pos, run)) = points.next_if(|(pos, _)| *pos < part.byte_range.end) {
+            if pos > part.byte_range.start {
+                let mut rest = part.clone();
+                part.byte_range.end = pos;
+                sections.push(part);
+                rest.byte_range.start = pos;
+                rest.leading_space = 0.0;
+                part = rest;
+            }
+            part.leading_space += run as f32 * extra;
+        }
+        sections.push(part);
+    }
+    job.sections = sections;
+}
+
+/// Paints a background behind each byte range of `job`, splitting sections at the range
+/// boundaries. `ranges` must be sorted and non-overlapping; ranges that do not fall on
+/// char boundaries of the job text are ignored.
+pub fn paint_backgrounds(job: &mut LayoutJob, ranges: &[(Range<usize>, Color32)]) {
+    let text = &job.text;
+    let ranges: Vec<&(Range<usize>, Color32)> = ranges
+        .iter()
+        .filter(|(r, _)| {
+            r.start < r.end && text.is_char_boundary(r.start) && text.is_char_boundary(r.end)
+        })
+        .collect();
+    if ranges.is_empty() {
+        return;
+    }
+
+    let mut sections = Vec::with_capacity(job.sections.len() + ranges.len() * 2);
+    let mut i = 0;
+    for section in job.sections.drain(..) {
+        let (mut start, end) = (section.byte_range.start, section.byte_range.end);
+        while start < end {
+            while ranges.get(i).is_some_and(|(r, _)| r.end <= start) {
+                i += 1;
+            }
+            let (next, background) = match ranges.get(i) {
+                Some((r, color)) if r.start <= start => (r.end.min(end), Some(*color)),
+                Some((r, _)) => (r.start.min(end), None),
+                None => (end, None),
+            };
+            let mut part = section.clone();
+            if start != section.byte_range.start {
+                part.leading_space = 0.0;
+            }
+            part.byte_range = start..next;
+            if let Some(color) = background {
+                part.format.background = color;
+            }
+            sections.push(part);
+            start = next;
+        }
+    }
+    job.sections = sections;
+}
+
+/// Stands in for the text of a folded range in the layout.
+pub const FOLD_PLACEHOLDER: char = '…';
+
+/// Hides each byte range of `job`: its text is drawn transparent with zero line height,
+/// so rows made only of hidden text take no space. The first character of a range shows
+/// as `FOLD_PLACEHOLDER` in `placeholder` format after `padding`; a line break it covers
+/// moves to the next character, so rows and character counts stay those of the buffer.
+/// Ranges follow the rules of `paint_backgrounds`.
+pub fn hide_ranges(
+    job: &mut LayoutJob,
+    ranges: &[Range<usize>],
+    placeholder: &TextFormat,
+    padding: f32,
+) {
+    let text = &job.text;
+    let ranges: Vec<&Range<usize>> = ranges
+        .iter()
+        .filter(|r| {
+            r.start < r.end && text.is_char_boundary(r.start) && text.is_char_boundary(r.end)
+        })
+        .collect();
+    if ranges.is_empty() {
+        return;
+    }
+
+    let text = std::mem::take(&mut job.text);
+    let mut out = String::with_capacity(text.len() + ranges.len() * 2);
+    let mut sections = Vec::with_capacity(job.sections.len() + ranges.len() * 3);
+    let mut i = 0;
+    let mut owed_newline = false;
+    for section in job.sections.drain(..) {
+        let (mut start, end) = (section.byte_range.start, section.byte_range.end);
+        while start < end {
+            while ranges.get(i).is_some_and(|r| r.end <= start) {
+                i += 1;
+            }
+            let (next, hidden) = match ranges.get(i) {
+                Some(r) if r.start <= start => (r.end.min(end), true),
+                Some(r) => (r.start.min(end), false),
+                None => (end, false),
+            };
+            let mut part = section.clone();
+            if start != section.byte_range.start || hidden {
+                part.leading_space = 0.0;
+            }
+            let mut rest = &text[start..next];
+            if hidden && start == ranges[i].start {
+                if let Some(first) = replaced_char(&text[ranges[i].clone()]) {
+                    let from = out.len();
+                    out.push(FOLD_PLACEHOLDER);
+                    sections.push(LayoutSection {
+                        leading_space: padding,
+                        byte_range: from..out.len(),
+                        format: placeholder.clone(),
+                    });
+                    rest = &rest[first.len_utf8()..];
+                    owed_newline = first == '\n';
+                }
+            }
+            if hidden {
+                part.format.color = Color32::TRANSPARENT;
+                part.format.background = Color32::TRANSPARENT;
+                part.format.underline = egui::Stroke::NONE;
+                part.format.strikethrough = egui::Stroke::NONE;
+                part.format.line_height = Some(0.0);
+            }
+            let from = out.len();
+            if let Some(c) = rest.chars().next().filter(|_| owed_newline) {
+                out.push('\n');
+                rest = &rest[c.len_utf8()..];
+                owed_newline = false;
+            }
+            out.push_str(rest);
+            if from < out.len() {
+                part.byte_range = from..out.len();
+                sections.push(part);
+            }
+            start = next;
+        }
+    }
+    job.text = out;
+    job.sections = sections;
+}
+
+/// The character of a hidden range that `FOLD_PLACEHOLDER` replaces, unless the range is
+/// a lone line break that has no next character to take its place.
+fn replaced_char(hidden: &str) -> Option<char> {
+    let mut chars = hidden.chars();
+    let first = chars.next()?;
+    (first != '\n' || chars.next().is_some()).then_some(first)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hidden(text: &str, range: Range<usize>) -> LayoutJob {
+        let mut job = LayoutJob::simple_singleline(text.into(), Default::default(), Color32::WHITE);
+        hide_ranges(&mut job, &[range], &TextFormat::default(), 2.0);
+        job
+    }
+
+    fn visible(job: &LayoutJob) -> String {
+        job.sections
+            .iter()
+            .filter(|s| s.format.line_height.is_none())
+            .map(|s| &job.text[s.byte_range.clone()])
+            .collect()
+    }
+
+    #[test]
+    fn hide_ranges_keeps_rows_and_chars() {
+        let job = hidden("fn a() {\n    é();\n}", 8..18);
+        assert_eq!(job.text, "fn a() {…\n   é();\n}");
+        assert_eq!(visible(&job), "fn a() {…\n}");
+        assert_eq!(job.sections[1].leading_space, 2.0);
+        assert_eq!(
+            job.text.chars().count(),
+            "fn a() {\n    é();\n}".chars().count()
+        );
+    }
+
+    #[test]
+    fn hide_ranges_crlf() {
+        let job = hidden("a\r\n b\r\nc", 1..5);
+        assert_eq!(job.text, "a…\n b\r\nc");
+        assert_eq!(visible(&job), "a…\r\nc");
+    }
+
+    #[test]
+    fn hide_ranges_lone_newline_has_no_placeholder() {
+        let job = hidden("a\n\nb", 1..2);
+        assert_eq!(job.text, "a\n\nb");
+        assert!(!job.text.contains(FOLD_PLACEHOLDER));
+    }
+
+    #[test]
+    fn hide_ranges_across_sections() {
+        let mut job = LayoutJob::default();
+        job.append("a\n", 0.0, TextFormat::default());
+        job.append("éb\nc", 0.0, TextFormat::default());
+        hide_ranges(&mut job, &[1..5, 6..7], &TextFormat::default(), 0.0);
+        assert_eq!(job.text, "a…\nb\n…");
+        assert_eq!(visible(&job), "a…\n…");
+    }
+}
@@ -0,0 +1,177 @@
+use super::{line_start, BRACKETS};
+
+/// How one level of indentation is written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IndentStyle {
+    pub spaces: bool,
+    /// Columns per level, also the display width of a tab.
+    pub width: usize,
+}
+
+impl IndentStyle {
+    pub fn unit(&self) -> String {
+        if self.spaces {
+            " ".repeat(self.width)
+        } else {
+            "\t".to_string()
+        }
+    }
+
+    /// What Tab inserts at `column`: a tab, or spaces up to the next tab stop.
+    pub fn tab_at(&self, column: usize) -> String {
+        if self.spaces {
+            " ".repeat(self.width - column % self.width)
+        } else {
+            "\t".to_string()
+        }
+    }
+
+    /// How many leading chars of `line` one outdent removes: a tab, or spaces back to
+    /// the previous tab stop.
+    pub fn outdent_len(&self, line: &str) -> usize {
+        if line.starts_with('\t') {
+            return 1;
+        }
+        match line.len() - line.trim_start_matches(' ').len() {
+            0 => 0,
+            n => (n - 1) % self.width + 1,
+        }
+    }
+}
+
+/// Guesses the indentation of `text`. Tabs win when more lines start with a tab than
+/// with spaces; otherwise the width is the most common change in leading spaces between
+/// consecutive lines, falling back to `width`.
+pub fn detect_indent(text: &str, width: usize) -> Option<IndentStyle> {
+    let (mut tabs, mut spaced) = (0, 0);
+    let mut steps = [0usize; 9];
+    let mut previous = 0;
+    for line in text.lines().filter(|l| !l.trim().is_empty()) {
+        if line.starts_with('\t') {
+            tabs += 1;
+            continue;
+        }
+        let spaces = line.len() - line.trim_start_matches(' ').len();
+        if spaces > 0 {
+            spaced += 1;
+        }
+        // A change of one column is usually alignment rather than indentation.
+        let step = spaces.abs_diff(previous);
+        if (2..steps.len()).contains(&step) {
+            steps[step] += 1;
+        }
+        previous = spaces;
+    }
+    if tabs == 0 && spaced == 0 {
+        return None;
+    }
+    if tabs > spaced {
+        return Some(IndentStyle {
+            spaces: false,
+            width,
+        });
+    }
+    let width = (2..steps.len())
+        .filter(|&w| steps[w] > 0)
+        .max_by_key(|&w| (steps[w], std::cmp::Reverse(w)))
+        .unwrap_or(width);
+    Some(IndentStyle {
+        spaces: true,
+        width,
+    })
+}
+
+pub fn leading_whitespace(line: &str) -> &str {
+    &line[..line.len() - line.trim_start_matches([' ', '\t']).len()]
+}
+
+/// What Enter inserts before and after the caret at char index `pos`: a `newline` and the
+/// indentation of its line, one level deeper when `opens` says the line up to `pos` opens
+/// a block. Between an empty bracket pair the closer moves to a line of its own.
+pub fn newline_indent(
+    chars: &[char],
+    pos: usize,
+    style: IndentStyle,
+    newline: &str,
+    opens: impl Fn(&str) -> bool,
+) -> (String, String) {
+    let before: String = chars[line_start(chars, pos)..pos].iter().collect();
+    let indent = leading_whitespace(&before);
+    let mut out = format!("{}{}", newline, indent);
+    if opens(&before) {
+        out.push_str(&style.unit());
+    }
+    let pair = before
+        .trim_end()
+        .chars()
+        .last()
+        .zip(chars.get(pos).copied());
+    let after = match pair {
+        Some(pair) if BRACKETS.contains(&pair) => format!("{}{}", newline, indent),
+        _ => String::new(),
+    };
+    (out, after)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPACES: IndentStyle = IndentStyle {
+        spaces: true,
+        width: 4,
+    };
+
+    #[test]
+    fn detect_indent_spaces() {
+        let text = "fn a() {\n    x\n        y\n    z\n}";
+        assert_eq!(detect_indent(text, 8), Some(SPACES));
+        let text = "a\r\n  é\r\n   ü\r\n  d\r\n";
+        assert_eq!(detect_indent(text, 8).map(|s| s.width), Some(2));
+        let text = "a\n  b\n      c";
+        assert_eq!(detect_indent(text, 8).map(|s| s.width), Some(2));
+    }
+
+    #[test]
+    fn detect_indent_tabs_and_flat_text() {
+        let tabs = IndentStyle {
+            spaces: false,
+            width: 3,
+        };
+        assert_eq!(detect_indent("a\n\tb\n\tc\n  d", 3), Some(tabs));
+        assert_eq!(detect_indent("a\n\nb\r\n", 4), None);
+        assert_eq!(
+            detect_indent(" a", 4),
+            Some(IndentStyle {
+                spaces: true,
+                width: 4
+            })
+        );
+    }
+
+    #[test]
+    fn tab_stops_and_outdent() {
+        assert_eq!(SPACES.tab_at(1), "   ");
+        assert_eq!(SPACES.tab_at(4), "    ");
+        assert_eq!(SPACES.outdent_len("      x"), 2);
+        assert_eq!(SPACES.outdent_len("    x"), 4);
+        assert_eq!(SPACES.outdent_len("\t  x"), 1);
+        assert_eq!(SPACES.outdent_len("x"), 0);
+    }
+
+    #[test]
+    fn newline_indent_between_brackets() {
+        let chars: Vec<char> = "    if é {}".chars().collect();
+        let opens = |l: &str| l.trim_end().ends_with('{');
+        let (before, after) = newline_indent(&chars, 10, SPACES, "\n", opens);
+        assert_eq!((before.as_str(), after.as_str()), ("\n        ", "\n    "));
+        let (before, after) = newline_indent(&chars, 4, SPACES, "\n", opens);
+        assert_eq!((before.as_str(), after.as_str()), ("\n    ", ""));
+        let chars: Vec<char> = "x\r\n    if é {}\r\n".chars().collect();
+        let (before, after) = newline_indent(&chars, 13, SPACES, "\r\n", opens);
+        assert_eq!(
+            (before.as_str(), after.as_str()),
+            ("\r\n        ", "\r\n    ")
+        );
+    }
+}
@@ -0,0 +1,529 @@
+use super::{leading_whitespace, line_end, line_start, Caret, MultiCursor};
+use std::cmp::Ordering;
+use std::ops::Range;
+
+/// Returns the byte range of every line in `text`, excluding its line ending.
+pub fn line_ranges(text: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    for line in text.split_inclusive('\n') {
+        let content = line.strip_suffix('\n').unwrap_or(line);
+        let content = content.strip_suffix('\r').unwrap_or(content);
+        ranges.push(start..start + content.len());
+        start += line.len();
+    }
+    if text.is_empty() || text.ends_with('\n') {
+        ranges.push(start..start);
+    }
+    ranges
+}
+
+/// Keeps the zero-based lines of `text` for which `keep` returns true, with their
+/// original line endings. A missing final newline stays missing.
+pub fn retain_lines(text: &str, mut keep: impl FnMut(usize) -> bool) -> String {
+    let mut out = String::with_capacity(text.len());
+    for (i, line) in text.split_inclusive('\n').enumerate() {
+        if keep(i) {
+            out.push_str(line);
+        }
+    }
+    if !text.ends_with('\n') {
+        if let Some(stripped) = out.strip_suffix('\n') {
+            let len = stripped.strip_suffix('\r').unwrap_or(stripped).len();
+            out.truncate(len);
+        }
+    }
+    out
+}
+
+/// A rearrangement of a block of whole lines.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineTransform {
+    SortAscending,
+    SortDescending,
+    /// Numbers inside the lines compare by value, so "item 2" sorts before "item 10".
+    SortNatural,
+    SortCaseInsensitive,
+    Reverse,
+    Shuffle(u64),
+    RemoveDuplicates,
+    RemoveBlank,
+}
+
+impl LineTransform {
+    pub fn apply(self, mut lines: Vec<String>) -> Vec<String> {
+        match self {
+            Self::SortAscending => lines.sort(),
+            Self::SortDescending => lines.sort_by(|a, b| b.cmp(a)),
+            Self::SortNatural => lines.sort_by(|a, b| natural_cmp(a, b)),
+            Self::SortCaseInsensitive => {
+                lines.sort_by_cached_key(|l| (l.to_lowercase(), l.clone()));
+            }
+            Self::Reverse => lines.reverse(),
+            Self::Shuffle(seed) => {
+                // xorshift64, enough to scramble a list without pulling in a crate.
+                let mut state = seed | 1;
+                for i in (1..lines.len()).rev() {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    lines.swap(i, (state % (i as u64 + 1)) as usize);
+                }
+            }
+            Self::RemoveDuplicates => {
+                let mut seen = std::collections::HashSet::new();
+                lines.retain(|l| seen.insert(l.clone()));
+            }
+            Self::RemoveBlank => lines.retain(|l| !l.trim().is_empty()),
+        }
+        lines
+    }
+}
+
+/// Compares strings with runs of ASCII digits ordered by their numeric value.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a, b);
+    loop {
+        let (Some(x), Some(y)) = (a.chars().next(), b.chars().next()) else {
+            return a.len().cmp(&b.len());
+        };
+        if x.is_ascii_digit() && y.is_ascii_digit() {
+            let split = |s: &str| s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+            let (da, ra) = a.split_at(split(a));
+            let (db, rb) = b.split_at(split(b));
+            let (na, nb) = (da.trim_start_matches('0'), db.trim_start_matches('0'));
+            let order = na.len().cmp(&nb.len()).then_with(|| na.cmp(nb));
+            if order != Ordering::Equal {
+                return order;
+            }
+            (a, b) = (ra, rb);
+        } else {
+            if x != y {
+                return x.cmp(&y);
+            }
+            (a, b) = (&a[x.len_utf8()..], &b[y.len_utf8()..]);
+        }
+    }
+}
+
+/// The line ending `text` uses: `"\r\n"` if any line ends with it, else `"\n"`.
+pub fn newline_of(text: &str) -> &'static str {
+    if text.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    }
+}
+
+/// The zero-based `lines` of `text`, without line endings.
+pub fn get_lines(text: &str, lines: Range<usize>) -> Vec<String> {
+    line_ranges(text)[lines]
+        .iter()
+        .map(|r| text[r.clone()].to_string())
+        .collect()
+}
+
+/// Replaces the zero-based `lines` of `text` with `new`, returning the new text and the
+/// char range of the replacement block.
+pub fn replace_lines(text: &str, lines: Range<usize>, new: &[String]) -> (String, Range<usize>) {
+    let ranges = line_ranges(text);
+    let (start, end) = (ranges[lines.start].start, ranges[lines.end - 1].end);
+    let block = new.join(newline_of(text));
+    let out = format!("{}{}{}", &text[..start], block, &text[end..]);
+    let first = text[..start].chars().count();
+    (out, first..first + block.chars().count())
+}
+
+/// Removes the zero-based `lines` of `text` with their line endings, returning the new
+/// text and the char index where the caret goes: the start of the following line, or
+/// the end of the previous one when the last line was removed.
+pub fn delete_lines(text: &str, lines: Range<usize>) -> (String, usize) {
+    let ranges = line_ranges(text);
+    let (start, end) = match ranges.get(lines.end) {
+        Some(next) => (ranges[lines.start].start, next.start),
+        // The last line goes with the line ending before it.
+        None => (
+            lines.start.checked_sub(1).map_or(0, |i| ranges[i].end),
+            text.len(),
+        ),
+    };
+    let out = format!("{}{}", &text[..start], &text[end..]);
+    (out, text[..start].chars().count())
+}
+
+/// Inserts a copy of the zero-based `lines` below them, returning the new text and the
+/// number of chars inserted.
+pub fn duplicate_lines(text: &str, lines: Range<usize>) -> (String, usize) {
+    let ranges = line_ranges(text);
+    let (start, end) = (ranges[lines.start].start, ranges[lines.end - 1].end);
+    let copy = format!("{}{}", newline_of(text), &text[start..end]);
+    let out = format!("{}{}{}", &text[..end], copy, &text[end..]);
+    (out, copy.chars().count())
+}
+
+/// Swaps the zero-based `lines` with the line above or below them, returning the new
+/// text and how many chars the block moved, or `None` at the edge of the text.
+pub fn move_lines(text: &str, lines: Range<usize>, up: bool) -> Option<(String, isize)> {
+    let count = line_ranges(text).len();
+    let (span, other) = if up {
+        (lines.start.checked_sub(1)?..lines.end, lines.start - 1)
+    } else if lines.end < count {
+        (lines.start..lines.end + 1, lines.end)
+    } else {
+        return None;
+    };
+    let mut block = get_lines(text, lines);
+    let other_line = get_lines(text, other..other + 1).remove(0);
+    let moved = (other_line.chars().count() + newline_of(text).chars().count()) as isize;
+    if up {
+        block.push(other_line);
+        Some((replace_lines(text, span, &block).0, -moved))
+    } else {
+        block.insert(0, other_line);
+        Some((replace_lines(text, span, &block).0, moved))
+    }
+}
+
+/// Joins the zero-based `lines`, or a single line with the next one, separating them
+/// with one space. Returns the new text and the char index of the end of the joined line.
+pub fn join_lines(text: &str, lines: Range<usize>) -> (String, usize) {
+    let count = line_ranges(text).len();
+    let lines = if lines.len() == 1 {
+        lines.start..(lines.end + 1).min(count)
+    } else {
+        lines
+    };
+    let mut joined = String::new();
+    for line in get_lines(text, lines.clone()) {
+        let line = if joined.is_empty() {
+            line.trim_end()
+        } else {
+            line.trim()
+        };
+        if !joined.is_empty() && !line.is_empty() {
+            joined.push(' ');
+        }
+        joined.push_str(line);
+    }
+    let (out, range) = replace_lines(text, lines, &[joined]);
+    (out, range.end)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineEdit {
+    Duplicate,
+    Delete,
+    MoveUp,
+    MoveDown,
+    Join,
+}
+
+/// Zero-based line spans touched by the carets, merged where they overlap or touch,
+/// with the indices of the carets in each.
+pub fn caret_blocks(cursor: &MultiCursor, text: &str) -> Vec<(Range<usize>, Vec<usize>)> {
+    let starts = char_line_starts(text);
+    let line_of = |pos: usize| starts.partition_point(|&s| s <= pos) - 1;
+    let mut blocks: Vec<(Range<usize>, Vec<usize>)> = Vec::new();
+    for (i, caret) in cursor.carets().iter().enumerate() {
+        let range = caret.range();
+        let first = line_of(range.start);
+        let mut last = line_of(range.end);
+        if last > first && starts[last] == range.end {
+            last -= 1;
+        }
+        match blocks.last_mut() {
+            Some((span, carets)) if first <= span.end => {
+                span.end = span.end.max(last + 1);
+                carets.push(i);
+            }
+            _ => blocks.push((first..last + 1, vec![i])),
+        }
+    }
+    blocks
+}
+
+fn char_line_starts(text: &str) -> Vec<usize> {
+    let mut starts = vec![0];
+    starts.extend(
+        text.chars()
+            .enumerate()
+            .filter(|&(_, c)| c == '\n')
+            .map(|(i, _)| i + 1),
+    );
+    starts
+}
+
+/// Applies `edit` to the lines touched by each caret as one text change. Blocks that
+/// cannot move past the start or end of the text stay put. Returns whether the text
+/// changed.
+pub fn edit_line_blocks(cursor: &mut MultiCursor, text: &mut String, edit: LineEdit) -> bool {
+    let blocks = caret_blocks(cursor, text);
+    let line_count = line_ranges(text).len();
+    let primary = cursor.primary();
+    let mut carets = cursor.carets().to_vec();
+    let mut out = text.clone();
+    // Bottom-up, so the line numbers of the blocks above stay valid.
+    for (b, (span, indices)) in blocks.iter().enumerate().rev() {
+        let before = out.chars().count() as isize;
+        let moved = match edit {
+            LineEdit::Duplicate => {
+                let (next, inserted) = duplicate_lines(&out, span.clone());
+                out = next;
+                Some(inserted as isize)
+            }
+            LineEdit::Delete => {
+                let (next, pos) = delete_lines(&out, span.clone());
+                out = next;
+                indices.iter().for_each(|&i| carets[i] = Caret::at(pos));
+                None
+            }
+            LineEdit::MoveUp | LineEdit::MoveDown => {
+                match move_lines(&out, span.clone(), edit == LineEdit::MoveUp) {
+                    Some((next, delta)) => {
+                        out = next;
+                        Some(delta)
+                    }
+                    None => continue,
+                }
+            }
+            LineEdit::Join => {
+                if span.len() == 1 && span.end >= line_count {
+                    continue;
+                }
+                let (next, pos) = join_lines(&out, span.clone());
+                out = next;
+                indices.iter().for_each(|&i| carets[i] = Caret::at(pos));
+                None
+            }
+        };
+        if let Some(delta) = moved {
+            for &i in indices {
+                carets[i] = carets[i].shifted(delta);
+            }
+        }
+        let delta = out.chars().count() as isize - before;
+        if delta != 0 {
+            for (_, below) in &blocks[b + 1..] {
+                for &i in below {
+                    carets[i] = carets[i].shifted(delta);
+                }
+            }
+        }
+    }
+    if out == *text {
+        return false;
+    }
+    let index = cursor
+        .carets()
+        .iter()
+        .position(|&c| c == primary)
+        .unwrap_or(0);
+    *cursor = MultiCursor::new(carets.clone(), carets[index]);
+    *text = out;
+    true
+}
+
+/// Rearranges the lines touched by each caret when any caret has a selection, leaving
+/// each rearranged block selected, or else the whole text. Returns whether the text
+/// changed.
+pub fn transform_lines(
+    cursor: &mut MultiCursor,
+    text: &mut String,
+    transform: LineTransform,
+) -> bool {
+    let selected = cursor.carets().iter().any(|c| !c.is_empty());
+    let blocks = if selected {
+        caret_blocks(cursor, text)
+    } else {
+        let ranges = line_ranges(text);
+        let count = ranges.len() - usize::from(ranges.len() > 1 && text.ends_with('\n'));
+        vec![(0..count, (0..cursor.carets().len()).collect())]
+    };
+    let primary = cursor.primary();
+    let mut carets = cursor.carets().to_vec();
+    let mut out = text.clone();
+    for (b, (span, indices)) in blocks.iter().enumerate().rev() {
+        let before = out.chars().count() as isize;
+        let lines = transform.apply(get_lines(&out, span.clone()));
+        let block = if lines.is_empty() {
+            let (next, pos) = delete_lines(&out, span.clone());
+            out = next;
+            pos..pos
+        } else {
+            let (next, block) = replace_lines(&out, span.clone(), &lines);
+            out = next;
+            block
+        };
+        if selected {
+            indices
+                .iter()
+                .for_each(|&i| carets[i] = Caret::new(block.start, block.end));
+        }
+        let delta = out.chars().count() as isize - before;
+        for (_, below) in &blocks[b + 1..] {
+            for &i in below {
+                carets[i] = carets[i].shifted(delta);
+            }
+        }
+    }
+    if out == *text {
+        return false;
+    }
+    let len = out.chars().count();
+    let carets: Vec<Caret> = carets
+        .into_iter()
+        .map(|c| Caret::new(c.anchor.min(len), c.head.min(len)))
+        .collect();
+    let index = cursor
+        .carets()
+        .iter()
+        .position(|&c| c == primary)
+        .unwrap_or(0);
+    *cursor = MultiCursor::new(carets.clone(), carets[index]);
+    *text = out;
+    true
+}
+
+/// Breaks the line at each empty caret, keeping the caret before the break, and puts
+/// every word of a selection on its own line. New lines keep the indentation of the
+/// line they came from.
+pub fn split_lines(cursor: &mut MultiCursor, text: &mut String) -> bool {
+    let newline = newline_of(text);
+    let chars: Vec<char> = text.chars().collect();
+    let selected = cursor.selected_texts(text);
+    let pieces: Vec<(String, String)> = cursor
+        .carets()
+        .iter()
+        .zip(&selected)
+        .map(|(caret, selection)| {
+            let start = line_start(&chars, caret.range().start);
+            let line: String = chars[start..line_end(&chars, start)].iter().collect();
+            let separator = format!("{}{}", newline, leading_whitespace(&line));
+            if caret.is_empty() {
+                (String::new(), separator)
+            } else {
+                let words: Vec<&str> = selection.split_whitespace().collect();
+                (words.join(&separator), String::new())
+            }
+        })
+        .collect();
+    cursor.insert_around(text, &pieces);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_ranges_exclude_line_endings() {
+        assert_eq!(line_ranges("a\r\nbé\n"), vec![0..1, 3..6, 7..7]);
+        assert_eq!(line_ranges("x"), vec![0..1]);
+        assert_eq!(line_ranges(""), vec![0..0]);
+    }
+
+    #[test]
+    fn retain_lines_keeps_line_endings() {
+        assert_eq!(retain_lines("a\r\nb\r\nc", |i| i != 2), "a\r\nb");
+        assert_eq!(retain_lines("a\r\nb\r\nc", |i| i != 1), "a\r\nc");
+        assert_eq!(retain_lines("a\nb\n", |i| i == 0), "a\n");
+        assert_eq!(retain_lines("a\nb", |_| false), "");
+    }
+
+    fn lines(text: &str) -> Vec<String> {
+        text.split(' ').map(String::from).collect()
+    }
+
+    #[test]
+    fn natural_order() {
+        assert_eq!(natural_cmp("item 2", "item 10"), Ordering::Less);
+        assert_eq!(natural_cmp("é10", "é9"), Ordering::Greater);
+        assert_eq!(natural_cmp("a01", "a1"), Ordering::Equal);
+        assert_eq!(natural_cmp("a", "a1"), Ordering::Less);
+    }
+
+    #[test]
+    fn line_transforms() {
+        let sorted = LineTransform::SortNatural.apply(lines("x10 x9 X1"));
+        assert_eq!(sorted, lines("X1 x9 x10"));
+        let sorted = LineTransform::SortCaseInsensitive.apply(lines("b B a"));
+        assert_eq!(sorted, lines("a B b"));
+        let unique = LineTransform::RemoveDuplicates.apply(lines("b a b a"));
+        assert_eq!(unique, lines("b a"));
+        let shuffled = LineTransform::Shuffle(7).apply(lines("a b c d e"));
+        assert_eq!(
+            shuffled,
+            LineTransform::Shuffle(7).apply(lines("a b c d e"))
+        );
+        let mut sorted = shuffled.clone();
+        sorted.sort();
+        assert_eq!(sorted, lines("a b c d e"));
+        let kept = LineTransform::RemoveBlank.apply(vec!["a".into(), " \t".into()]);
+        assert_eq!(kept, ["a"]);
+    }
+
+    #[test]
+    fn line_operations_keep_crlf() {
+        let text = "a\r\nb\r\nc";
+        assert_eq!(delete_lines(text, 2..3), ("a\r\nb".to_string(), 4));
+        assert_eq!(delete_lines(text, 0..1), ("b\r\nc".to_string(), 0));
+        assert_eq!(
+            duplicate_lines(text, 0..1),
+            ("a\r\na\r\nb\r\nc".to_string(), 3)
+        );
+        assert_eq!(
+            move_lines(text, 1..2, true),
+            Some(("b\r\na\r\nc".to_string(), -3))
+        );
+        assert_eq!(move_lines(text, 2..3, false), None);
+        assert_eq!(move_lines(text, 0..1, true), None);
+        assert_eq!(
+            join_lines("a  \r\n  b\r\nc", 0..1),
+            ("a b\r\nc".to_string(), 3)
+        );
+    }
+
+    #[test]
+    fn edit_line_blocks_moves_carets() {
+        let mut text = "a\nb\nc".to_string();
+        let mut cursor = MultiCursor::new(vec![Caret::at(0), Caret::at(4)], Caret::at(4));
+        assert!(edit_line_blocks(
+            &mut cursor,
+            &mut text,
+            LineEdit::Duplicate
+        ));
+        assert_eq!(text, "a\na\nb\nc\nc");
+        assert_eq!(cursor.carets(), [Caret::at(2), Caret::at(8)]);
+        assert_eq!(cursor.primary(), Caret::at(8));
+        assert!(edit_line_blocks(&mut cursor, &mut text, LineEdit::MoveDown));
+        assert_eq!(text, "a\nb\na\nc\nc");
+        assert_eq!(cursor.carets(), [Caret::at(4), Caret::at(8)]);
+        let mut last = MultiCursor::new(vec![Caret::at(8)], Caret::at(8));
+        assert!(!edit_line_blocks(&mut last, &mut text, LineEdit::MoveDown));
+    }
+
+    #[test]
+    fn transform_lines_keeps_final_newline() {
+        let mut text = "b\r\na\r\n".to_string();
+        let mut cursor = MultiCursor::new(vec![Caret::at(0)], Caret::at(0));
+        assert!(transform_lines(
+            &mut cursor,
+            &mut text,
+            LineTransform::SortAscending
+        ));
+        assert_eq!(text, "a\r\nb\r\n");
+        assert!(!transform_lines(
+            &mut cursor,
+            &mut text,
+            LineTransform::SortAscending
+        ));
+    }
+
+    #[test]
+    fn split_lines_keeps_indentation() {
+        let mut text = "  a b\r\n".to_string();
+        let mut cursor = MultiCursor::new(vec![Caret::new(2, 5)], Caret::new(2, 5));
+        split_lines(&mut cursor, &mut text);
+        assert_eq!(text, "  a\r\n  b\r\n");
+    }
+}
@@ -0,0 +1,84 @@
+use egui::text::LayoutJob;
+use egui::Color32;
+use std::ops::Range;
+
+/// The colored runs of one minimap line, as display columns.
+pub type MinimapLine = Vec<(Range<usize>, Color32)>;
+
+/// Splits highlighted `job` into runs of non-whitespace per line, in the color of their
+/// section. Tabs advance to the next tab stop and runs past `max_columns` are cut off.
+pub fn minimap_lines(job: &LayoutJob, tab_width: usize, max_columns: usize) -> Vec<MinimapLine> {
+    let mut lines = Vec::new();
+    let mut line = MinimapLine::new();
+    let mut column = 0;
+    for section in &job.sections {
+        let color = section.format.color;
+        for c in job.text[section.byte_range.clone()].chars() {
+            match c {
+                '\n' => {
+                    lines.push(std::mem::take(&mut line));
+                    column = 0;
+                    continue;
+                }
+                '\t' => {
+                    column += tab_width - column % tab_width;
+                    continue;
+                }
+                _ if c.is_whitespace() => {
+                    column += 1;
+                    continue;
+                }
+                _ => {}
+            }
+            if column < max_columns {
+                match line.last_mut() {
+                    Some((run, last)) if run.end == column && *last == color => run.end += 1,
+                    _ => line.push((column..column + 1, color)),
+                }
+            }
+            column += 1;
+        }
+    }
+    lines.push(line);
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use egui::text::TextFormat;
+
+    fn job(parts: &[(&str, Color32)]) -> LayoutJob {
+        let mut job = LayoutJob::default();
+        for (text, color) in parts {
+            job.append(text, 0.0, TextFormat::simple(Default::default(), *color));
+        }
+        job
+    }
+
+    #[test]
+    fn minimap_lines_merge_runs_by_color() {
+        let (red, blue) = (Color32::RED, Color32::BLUE);
+        let lines = minimap_lines(
+            &job(&[("ab", red), ("c d\r\n", blue), ("\n😀é", red)]),
+            4,
+            80,
+        );
+        assert_eq!(
+            lines,
+            vec![
+                vec![(0..2, red), (2..3, blue), (4..5, blue)],
+                vec![],
+                vec![(0..2, red)],
+            ]
+        );
+    }
+
+    #[test]
+    fn minimap_lines_tabs_and_cutoff() {
+        let white = Color32::WHITE;
+        let lines = minimap_lines(&job(&[("a\tbc  defgh", white)]), 4, 8);
+        assert_eq!(lines, vec![vec![(0..1, white), (4..6, white)]]);
+        assert_eq!(minimap_lines(&job(&[]), 4, 8), vec![vec![]]);
+    }
+}
@@ -1,3 +1,33 @@
+mod brackets;
+mod caret_input;
+mod carets;
+mod comments;
+mod diff;
+mod diff_view;
+mod folding;
+mod goto;
+mod gutter;
 mod highlight;
+mod indent;
+mod lines;
+mod minimap;
+mod reflow;
+mod search;
+mod transform;
 
+pub use brackets::*;
+pub use caret_input::*;
+pub use carets::*;
+pub use comments::*;
+pub use diff::*;
+pub use diff_view::*;
+pub use folding::*;
+pub use goto::*;
+pub use gutter::*;
 pub use highlight::*;
+pub use indent::*;
+pub use lines::*;
+pub use minimap::*;
+pub use reflow::*;
+pub use search::*;
+pub use transform::*;
@@ -0,0 +1,308 @@
+use super::{
+    caret_blocks, get_lines, line_end, line_ranges, line_start, replace_lines, Caret, MultiCursor,
+};
+use std::ops::Range;
+
+/// The start of a paragraph line: indentation, blockquote markers and a list marker.
+struct Prefix<'a> {
+    first: &'a str,
+    /// `first` with the list marker blanked out, for the lines that follow.
+    rest: String,
+    quote: String,
+    list: bool,
+    body: &'a str,
+}
+
+fn prefix(line: &str) -> Prefix<'_> {
+    let mut end = line.len() - line.trim_start().len();
+    let mut quote = String::new();
+    while line[end..].starts_with('>') {
+        quote.push('>');
+        end += 1;
+        end += line[end..].len() - line[end..].trim_start().len();
+    }
+    let quoted = end;
+    let rest = &line[end..];
+    let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let marker = if rest.starts_with(['-', '*', '+']) {
+        1
+    } else if (1..10).contains(&digits) && rest[digits..].starts_with(['.', ')']) {
+        digits + 1
+    } else {
+        0
+    };
+    let list = marker > 0 && rest[marker..].starts_with([' ', '\t']);
+    if list {
+        end += marker;
+        end += line[end..].len() - line[end..].trim_start().len();
+    }
+    let first = &line[..end];
+    let blank = " ".repeat(first[quoted..].chars().count());
+    Prefix {
+        first,
+        rest: format!("{}{}", &line[..quoted], blank),
+        quote,
+        list,
+        body: line[end..].trim_end(),
+    }
+}
+
+/// Display width of `s`, with tabs advancing to the next tab stop.
+fn width(s: &str, tab_width: usize) -> usize {
+    s.chars().fold(0, |w, c| match c {
+        '\t' => w + tab_width - w % tab_width,
+        _ => w + 1,
+    })
+}
+
+/// Lines that are never rewrapped: Markdown headings, fences, tables and rules.
+fn verbatim(body: &str) -> bool {
+    body.starts_with(['#', '|'])
+        || body.starts_with("```")
+        || body.starts_with("~~~")
+        || (body.len() >= 3 && body.chars().all(|c| matches!(c, '-' | '*' | '_' | ' ')))
+}
+
+/// Rewraps the paragraphs in `lines` to `column`. Blank lines separate paragraphs, and
+/// each list item starts a new one whose following lines line up under its text.
+pub fn reflow(lines: &[String], column: usize, tab_width: usize) -> Vec<String> {
+    let mut out = Vec::with_capacity(lines.len());
+    let mut paragraph: Option<(String, String, String, Vec<&str>)> = None;
+    let flush = |paragraph: &mut Option<(String, String, String, Vec<&str>)>,
+                 out: &mut Vec<String>| {
+        let Some((first, rest, _, words)) = paragraph.take() else {
+            return;
+        };
+        let mut line = first;
+        let mut empty = true;
+        for word in words {
+            if !empty && width(&line, tab_width) + 1 + word.chars().count() > column {
+                out.push(std::mem::replace(&mut line, rest.clone()));
+                empty = true;
+            }
+            if !empty {
+                line.push(' ');
+            }
+            line.push_str(word);
+            empty = false;
+        }
+        out.push(line);
+    };
+    for line in lines {
+        let p = prefix(line);
+        if p.body.is_empty() || verbatim(p.body) {
+            flush(&mut paragraph, &mut out);
+            out.push(line.clone());
+            continue;
+        }
+        let continues =
+            matches!(&paragraph, Some((_, _, quote, _)) if !p.list && *quote == p.quote);
+        if !continues {
+            flush(&mut paragraph, &mut out);
+            paragraph = Some((p.first.to_string(), p.rest, p.quote, Vec::new()));
+        }
+        if let Some((_, _, _, words)) = &mut paragraph {
+            words.extend(p.body.split_whitespace());
+        }
+    }
+    flush(&mut paragraph, &mut out);
+    out
+}
+
+/// Reflows the lines touched by each selection, or the paragraph around each caret when
+/// nothing is selected. Returns whether the text changed.
+pub fn reflow_paragraphs(
+    cursor: &mut MultiCursor,
+    text: &mut String,
+    column: usize,
+    tab_width: usize,
+) -> bool {
+    let selected = cursor.carets().iter().any(|c| !c.is_empty());
+    let mut spans: Vec<Range<usize>> = if selected {
+        caret_blocks(cursor, text)
+            .into_iter()
+            .map(|(span, _)| span)
+            .collect()
+    } else {
+        let lines = get_lines(text, 0..line_ranges(text).len());
+        let blank = |i: usize| prefix(&lines[i]).body.is_empty();
+        let mut spans: Vec<Range<usize>> = Vec::new();
+        for caret in cursor.carets() {
+            let line = text.chars().take(caret.head).filter(|&c| c == '\n').count();
+            if blank(line) {
+                continue;
+            }
+            let (mut start, mut end) = (line, line + 1);
+            while start > 0 && !blank(start - 1) {
+                start -= 1;
+            }
+            while end < lines.len() && !blank(end) {
+                end += 1;
+            }
+            match spans.last_mut() {
+                Some(last) if last.end >= start => last.end = last.end.max(end),
+                _ => spans.push(start..end),
+            }
+        }
+        spans
+    };
+    let mut out = text.clone();
+    let mut carets: Vec<Caret> = Vec::with_capacity(spans.len());
+    spans.reverse();
+    for span in spans {
+        let lines = reflow(&get_lines(&out, span.clone()), column, tab_width);
+        let (next, block) = replace_lines(&out, span, &lines);
+        let delta = next.chars().count() as isize - out.chars().count() as isize;
+        out = next;
+        for c in &mut carets {
+            *c = c.shifted(delta);
+        }
+        carets.push(if selected {
+            Caret::new(block.start, block.end)
+        } else {
+            Caret::at(block.end)
+        });
+    }
+    if out == *text || carets.is_empty() {
+        return false;
+    }
+    let primary = carets[0];
+    *cursor = MultiCursor::new(carets, primary);
+    *text = out;
+    true
+}
+
+/// Wraps the line of each caret at the last space before `column` once typing has run
+/// past it, continuing with the line's prefix. Returns whether the text changed.
+pub fn wrap_typed_lines(
+    cursor: &mut MultiCursor,
+    text: &mut String,
+    column: usize,
+    tab_width: usize,
+) -> bool {
+    let newline = if text.contains("\r\n") { "\r\n" } else { "\n" };
+    let chars: Vec<char> = text.chars().collect();
+    let mut pieces = Vec::new();
+    let mut breaks = Vec::new();
+    for caret in cursor.carets() {
+        let start = line_start(&chars, caret.head);
+        if breaks
+            .last()
+            .is_some_and(|b: &Range<usize>| b.start >= start)
+        {
+            continue;
+        }
+        let end = line_end(&chars, caret.head);
+        let line: String = chars[start..end].iter().collect();
+        if !caret.is_empty() || width(&line, tab_width) <= column {
+            continue;
+        }
+        let p = prefix(&line);
+        if verbatim(p.body) {
+            continue;
+        }
+        let body_start = p.first.chars().count();
+        let head = caret.head - start;
+        // The last space that keeps the line within the column, or else the first one.
+        let spaces: Vec<usize> = (body_start.max(1)..head.min(line.chars().count()))
+            .filter(|&i| chars[start + i] == ' ' && chars[start + i - 1] != ' ')
+            .collect();
+        let Some(&at) = spaces
+            .iter()
+            .rev()
+            .find(|&&i| width(&line.chars().take(i).collect::<String>(), tab_width) <= column)
+            .or(spaces.first())
+        else {
+            continue;
+        };
+        let run = chars[start + at..]
+            .iter()
+            .take_while(|&&c| c == ' ')
+            .count();
+        breaks.push((start + at)..(start + at + run));
+        pieces.push(format!("{}{}", newline, p.rest));
+    }
+    if breaks.is_empty() {
+        return false;
+    }
+    let mut out = String::with_capacity(text.len() + 16);
+    let mut last = 0;
+    for (range, piece) in breaks.iter().zip(&pieces) {
+        out.extend(&chars[last..range.start]);
+        out.push_str(piece);
+        last = range.end;
+    }
+    out.extend(&chars[last..]);
+    let carets: Vec<Caret> = cursor
+        .carets()
+        .iter()
+        .map(|c| {
+            let delta: isize = breaks
+                .iter()
+                .zip(&pieces)
+                .filter(|(range, _)| range.end <= c.range().start)
+                .map(|(range, piece)| piece.chars().count() as isize - range.len() as isize)
+                .sum();
+            c.shifted(delta)
+        })
+        .collect();
+    let index = cursor
+        .carets()
+        .iter()
+        .position(|&c| c == cursor.primary())
+        .unwrap_or(0);
+    *cursor = MultiCursor::new(carets.clone(), carets[index]);
+    *text = out;
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reflowed(lines: &[&str], column: usize) -> Vec<String> {
+        let lines: Vec<String> = lines.iter().map(|l| l.to_string()).collect();
+        reflow(&lines, column, 4)
+    }
+
+    #[test]
+    fn reflow_wraps_and_joins_paragraphs() {
+        assert_eq!(reflowed(&["aa bb cc dd"], 5), ["aa bb", "cc dd"]);
+        assert_eq!(
+            reflowed(&["one", "two  three", "", "four"], 80),
+            ["one two three", "", "four"]
+        );
+        assert_eq!(reflowed(&["abcdefghij k"], 5), ["abcdefghij", "k"]);
+    }
+
+    #[test]
+    fn reflow_counts_chars_and_tab_stops() {
+        assert_eq!(reflowed(&["é é é é"], 3), ["é é", "é é"]);
+        assert_eq!(reflowed(&["\tab cd"], 8), ["\tab", "\tcd"]);
+    }
+
+    #[test]
+    fn reflow_keeps_list_and_quote_prefixes() {
+        assert_eq!(
+            reflowed(&["> - alpha beta gamma", "> delta", "- x y"], 12),
+            ["> - alpha", ">   beta", ">   gamma", ">   delta", "- x y"]
+        );
+        assert_eq!(reflowed(&["1. a b", "2) c"], 80), ["1. a b", "2) c"]);
+    }
+
+    #[test]
+    fn reflow_leaves_verbatim_lines() {
+        let lines = ["# a b c d", "| a | b |", "---", "```"];
+        assert_eq!(reflowed(&lines, 3), lines);
+    }
+
+    #[test]
+    fn wrap_typed_lines_keeps_crlf() {
+        let mut text = "aa bb cc\r\nx".to_string();
+        let mut cursor = MultiCursor::new(vec![Caret::at(8)], Caret::at(8));
+        assert!(wrap_typed_lines(&mut cursor, &mut text, 5, 4));
+        assert_eq!(text, "aa bb\r\ncc\r\nx");
+        assert_eq!(cursor.primary(), Caret::at(9));
+        assert!(!wrap_typed_lines(&mut cursor, &mut text, 5, 4));
+    }
+}
@@ -0,0 +1,491 @@
+use regex::{Captures, Regex, RegexBuilder};
+use std::borrow::Cow;
+use std::ops::Range;
+use unicode_normalization::char::{decompose_canonical, is_combining_mark};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SearchOptions {
+    pub regex: bool,
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+    pub ignore_diacritics: bool,
+    /// Match the case pattern of the replaced text ("FOO", "Foo", "foo").
+    pub preserve_case: bool,
+}
+
+/// A compiled search query. Literal queries are escaped and run through the same
+/// regex engine so both modes report matches the same way.
+pub struct Searcher {
+    regex: Regex,
+    options: SearchOptions,
+}
+
+impl Searcher {
+    pub fn new(query: &str, options: SearchOptions) -> Result<Self, String> {
+        let query = if options.ignore_diacritics {
+            Cow::Owned(Folded::new(query).text)
+        } else {
+            Cow::Borrowed(query)
+        };
+        let mut pattern = if options.regex {
+            query.into_owned()
+        } else {
+            regex::escape(&query)
+        };
+        if options.whole_word {
+            pattern = format!(r"\b{{start-half}}(?:{})\b{{end-half}}", pattern);
+        }
+        let regex = RegexBuilder::new(&pattern)
+            .case_insensitive(!options.case_sensitive)
+            .multi_line(true)
+            .crlf(true)
+            .build()
+            .map_err(|e| error_message(&e))?;
+        Ok(Self { regex, options })
+    }
+
+    /// Returns the byte range of every non-empty match in `text`.
+    pub fn find_all(&self, text: &str) -> Vec<Range<usize>> {
+        if !self.options.ignore_diacritics {
+            return self
+                .regex
+                .find_iter(text)
+                .filter(|m| !m.is_empty())
+                .map(|m| m.range())
+                .collect();
+        }
+        let folded = Folded::new(text);
+        self.regex
+            .find_iter(&folded.text)
+            .filter(|m| !m.is_empty())
+            .map(|m| folded.original(text, m.range()))
+            .collect()
+    }
+
+    /// Builds the replacement for the match at `range`. In regex mode `$1`, `${name}`
+    /// and `$$` in `template` are expanded from the match's capture groups.
+    pub fn replacement(&self, text: &str, range: Range<usize>, template: &str) -> String {
+        let folded = self.fold(text);
+        let map = |r: Range<usize>| folded.as_ref().map_or(r.clone(), |f| f.original(text, r));
+        let (haystack, start) = match &folded {
+            Some(f) => (
+                f.text.as_str(),
+                f.offsets.partition_point(|&o| o < range.start),
+            ),
+            None => (text, range.start),
+        };
+        match self
+            .regex
+            .captures_at(haystack, start)
+            .filter(|c| c.get(0).map(|m| map(m.range())) == Some(range.clone()))
+        {
+            Some(caps) => self.render(text, &caps, &map, template),
+            None => template.to_string(),
+        }
+    }
+
+    /// Replaces every match lying inside the byte range `scope`, returning the new text
+    /// and the number of replacements.
+    pub fn replace_all(&self, text: &str, scope: Range<usize>, template: &str) -> (String, usize) {
+        let folded = self.fold(text);
+        let map = |r: Range<usize>| folded.as_ref().map_or(r.clone(), |f| f.original(text, r));
+        let haystack = folded.as_ref().map_or(text, |f| f.text.as_str());
+
+        let mut out = String::with_capacity(text.len());
+        let (mut last, mut count) = (0, 0);
+        for caps in self.regex.captures_iter(haystack) {
+            let Some(range) = caps
+                .get(0)
+                .filter(|m| !m.is_empty())
+                .map(|m| map(m.range()))
+            else {
+                continue;
+            };
+            if range.start < scope.start || range.end > scope.end || range.start < last {
+                continue;
+            }
+            out.push_str(&text[last..range.start]);
+            out.push_str(&self.render(text, &caps, &map, template));
+            last = range.end;
+            count += 1;
+        }
+        out.push_str(&text[last..]);
+        (out, count)
+    }
+
+    fn fold(&self, text: &str) -> Option<Folded> {
+        self.options.ignore_diacritics.then(|| Folded::new(text))
+    }
+
+    fn render(
+        &self,
+        text: &str,
+        caps: &Captures,
+        map: &dyn Fn(Range<usize>) -> Range<usize>,
+        template: &str,
+    ) -> String {
+        let out = if self.options.regex {
+            expand(&self.regex, caps, template, |r| &text[map(r)])
+        } else {
+            template.to_string()
+        };
+        match caps.get(0).filter(|_| self.options.preserve_case) {
+            Some(m) => preserve_case(&text[map(m.range())], &out),
+            None => out,
+        }
+    }
+}
+
+/// Returns the byte offset of char index `index`, or `text.len()` past the end.
+pub fn byte_offset(text: &str, index: usize) -> usize {
+    text.char_indices()
+        .nth(index)
+        .map_or(text.len(), |(b, _)| b)
+}
+
+/// Adapts `replacement` to the casing of `matched`: all caps, all lower or capitalized.
+fn preserve_case(matched: &str, replacement: &str) -> String {
+    let mut letters = matched.chars().filter(|c| c.is_alphabetic());
+    let Some(first) = letters.next() else {
+        return replacement.to_string();
+    };
+    let rest: Vec<char> = letters.collect();
+    if first.is_uppercase() && !rest.is_empty() && rest.iter().all(|c| c.is_uppercase()) {
+        replacement.to_uppercase()
+    } else if first.is_lowercase() && rest.iter().all(|c| c.is_lowercase()) {
+        replacement.to_lowercase()
+    } else if first.is_uppercase() && rest.iter().all(|c| c.is_lowercase()) {
+        let mut chars = replacement.chars();
+        chars
+            .next()
+            .map(|c| c.to_uppercase().chain(chars).collect())
+            .unwrap_or_default()
+    } else {
+        replacement.to_string()
+    }
+}
+
+/// Converts sorted, non-overlapping byte ranges of `text` into char ranges.
+pub fn char_ranges(text: &str, ranges: &[Range<usize>]) -> Vec<Range<usize>> {
+    let mut out = Vec::with_capacity(ranges.len());
+    let (mut byte, mut chars) = (0, 0);
+    let mut advance = |to: usize| {
+        chars += text[byte..to].chars().count();
+        byte = to;
+        chars
+    };
+    for range in ranges {
+        let start = advance(range.start);
+        let end = advance(range.end);
+        out.push(start..end);
+    }
+    out
+}
+
+/// Converts sorted, non-overlapping char ranges of `text` into byte ranges.
+pub fn byte_ranges(text: &str, ranges: &[Range<usize>]) -> Vec<Range<usize>> {
+    let mut out = Vec::with_capacity(ranges.len());
+    let mut indices = text
+        .char_indices()
+        .skip(1)
+        .map(|(i, _)| i)
+        .chain([text.len()]);
+    let (mut chars, mut byte) = (0, 0);
+    let mut advance = |to: usize| {
+        while chars < to {
+            match indices.next() {
+                Some(b) => byte = b,
+                None => break,
+            }
+            chars += 1;
+        }
+        byte
+    };
+    for range in ranges {
+        let start = advance(range.start);
+        let end = advance(range.end);
+        out.push(start..end);
+    }
+    out
+}
+
+/// Moves byte `range` of `old` to where the same text is in `new`, assuming a single
+/// edited stretch found by comparing their common prefix and suffix. Edits before the
+/// range shift it and edits inside it resize it; returns `None` when the edit overlaps
+/// one of its ends.
+pub fn remap_range(old: &str, new: &str, range: Range<usize>) -> Option<Range<usize>> {
+    let mut prefix = old
+        .bytes()
+        .zip(new.bytes())
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !old.is_char_boundary(prefix) {
+        prefix -= 1;
+    }
+    let max_suffix = old.len().min(new.len()) - prefix;
+    let mut suffix = old
+        .bytes()
+        .rev()
+        .zip(new.bytes().rev())
+        .take(max_suffix)
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !old.is_char_boundary(old.len() - suffix) || !new.is_char_boundary(new.len() - suffix) {
+        suffix -= 1;
+    }
+    let edited_end = old.len() - suffix;
+    let delta = new.len() as isize - old.len() as isize;
+    let shift = |i: usize| (i as isize + delta) as usize;
+    if old == new || prefix >= range.end {
+        Some(range)
+    } else if edited_end <= range.start {
+        Some(shift(range.start)..shift(range.end))
+    } else if prefix >= range.start && edited_end <= range.end {
+        Some(range.start..shift(range.end))
+    } else {
+        None
+    }
+}
+
+/// `text` with canonical decompositions applied and combining marks removed, so
+/// "é" matches "e". Hangul syllables are kept whole so Korean is not split into jamo.
+struct Folded {
+    text: String,
+    /// Original byte offset of the char each folded byte came from.
+    offsets: Vec<usize>,
+}
+
+impl Folded {
+    fn new(original: &str) -> Self {
+        let mut text = String::with_capacity(original.len());
+        let mut offsets = Vec::with_capacity(original.len());
+        for (i, c) in original.char_indices() {
+            let before = text.len();
+            if ('\u{AC00}'..='\u{D7A3}').contains(&c) {
+                text.push(c);
+            } else {
+                decompose_canonical(c, |d| {
+                    if !is_combining_mark(d) {
+                        text.push(d);
+                    }
+                });
+            }
+            offsets.resize(offsets.len() + text.len() - before, i);
+        }
+        Self { text, offsets }
+    }
+
+    /// Maps a folded byte range back to the original text, widened to whole chars and
+    /// the combining marks that follow them.
+    fn original(&self, original: &str, range: Range<usize>) -> Range<usize> {
+        let start = self
+            .offsets
+            .get(range.start)
+            .copied()
+            .unwrap_or(original.len());
+        let end = match range.end.checked_sub(1).and_then(|i| self.offsets.get(i)) {
+            Some(&last) => {
+                let mut chars = original[last..].chars();
+                let base = chars.next().map_or(0, char::len_utf8);
+                let marks: usize = chars
+                    .take_while(|&c| is_combining_mark(c))
+                    .map(char::len_utf8)
+                    .sum();
+                last + base + marks
+            }
+            None => start,
+        };
+        start..end.max(start)
+    }
+}
+
+/// Expands `$n`, `${n}`, `$name`, `${name}` and `$$` in `template` like
+/// `Captures::expand`, reading group text through `group`.
+fn expand<'t>(
+    regex: &Regex,
+    caps: &Captures,
+    template: &str,
+    group: impl Fn(Range<usize>) -> &'t str,
+) -> String {
+    let lookup = |name: &str| {
+        let m = match name.parse::<usize>() {
+            Ok(i) => caps.get(i),
+            Err(_) => regex
+                .capture_names()
+                .position(|n| n == Some(name))
+                .and_then(|i| caps.get(i)),
+        };
+        m.map(|m| group(m.range())).unwrap_or("")
+    };
+    let is_name = |c: char| c == '_' || c.is_ascii_alphanumeric();
+
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(i) = rest.find('$') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i + 1..];
+        if let Some(after) = rest.strip_prefix('$') {
+            out.push('$');
+            rest = after;
+        } else if let Some((name, after)) = rest
+            .strip_prefix('{')
+            .and_then(|r| r.split_once('}'))
+            .filter(|(n, _)| !n.is_empty())
+        {
+            out.push_str(lookup(name));
+            rest = after;
+        } else {
+            let len = rest.find(|c| !is_name(c)).unwrap_or(rest.len());
+            if len == 0 {
+                out.push('$');
+            } else {
+                out.push_str(lookup(&rest[..len]));
+            }
+            rest = &rest[len..];
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Reduces a regex error to its last line, e.g. "unclosed group".
+fn error_message(e: &regex::Error) -> String {
+    let text = e.to_string();
+    let last = text.lines().last().unwrap_or(&text).trim();
+    last.strip_prefix("error: ").unwrap_or(last).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn regex() -> SearchOptions {
+        SearchOptions {
+            regex: true,
+            case_sensitive: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn expand_groups() {
+        let re = Regex::new(r"(?<key>\w+)=(\w+)").unwrap();
+        let text = "size=10";
+        let caps = re.captures(text).unwrap();
+        let out = expand(&re, &caps, "$2 ${key} $key$$ $1x ${1}x $ $9 ${}", |r| {
+            &text[r]
+        });
+        assert_eq!(out, "10 size size$  sizex $  ${}");
+    }
+
+    #[test]
+    fn regex_find_all_crlf() {
+        let searcher = Searcher::new("o$", regex()).unwrap();
+        assert_eq!(searcher.find_all("foo\r\nbo\nx"), vec![2..3, 6..7]);
+        assert!(Searcher::new("(", regex()).is_err());
+    }
+
+    #[test]
+    fn folded_maps_back_to_whole_chars() {
+        let text = "Cafe\u{301} é 한국";
+        let folded = Folded::new(text);
+        assert_eq!(folded.text, "Cafe e 한국");
+        assert_eq!(folded.original(text, 0..4), 0..6);
+        assert_eq!(folded.original(text, 3..4), 3..6);
+        assert_eq!(folded.original(text, 5..6), 7..9);
+        assert_eq!(folded.original(text, 7..13), 10..16);
+        assert_eq!(folded.original(text, 13..13), 16..16);
+    }
+
+    #[test]
+    fn ignore_diacritics_reports_original_ranges() {
+        let options = SearchOptions {
+            ignore_diacritics: true,
+            ..Default::default()
+        };
+        let searcher = Searcher::new("cafe", options).unwrap();
+        assert_eq!(searcher.find_all("Café, cafe\u{301}!"), vec![0..5, 7..13]);
+    }
+
+    #[test]
+    fn whole_word_case_sensitive() {
+        let options = SearchOptions {
+            whole_word: true,
+            case_sensitive: true,
+            ..Default::default()
+        };
+        let searcher = Searcher::new("ab", options).unwrap();
+        assert_eq!(searcher.find_all("ab abc Ab ab_ (ab)"), vec![0..2, 15..17]);
+    }
+
+    #[test]
+    fn char_and_byte_ranges_round_trip() {
+        let text = "aé\r\n😀b";
+        let bytes = vec![0..1, 1..3, 5..9, 9..10, 10..10];
+        let chars = char_ranges(text, &bytes);
+        assert_eq!(chars, vec![0..1, 1..2, 4..5, 5..6, 6..6]);
+        assert_eq!(byte_ranges(text, &chars), bytes);
+        assert_eq!(byte_ranges(text, &[2..3, 3..9]), vec![3..4, 4..10]);
+        assert_eq!(byte_offset(text, 4), 5);
+        assert_eq!(byte_offset(text, 9), text.len());
+    }
+
+    #[test]
+    fn replace_all_skips_matches_outside_scope() {
+        let searcher = Searcher::new("ab", SearchOptions::default()).unwrap();
+        let text = "ab ab\r\nab ab";
+        let (out, count) = searcher.replace_all(text, 2..9, "é");
+        assert_eq!((out.as_str(), count), ("ab é\r\né ab", 2));
+        let (out, count) = searcher.replace_all(text, 4..8, "x");
+        assert_eq!((out.as_str(), count), (text, 0));
+    }
+
+    #[test]
+    fn replace_all_preserves_case() {
+        let options = SearchOptions {
+            preserve_case: true,
+            ..Default::default()
+        };
+        let searcher = Searcher::new("foo", options).unwrap();
+        let text = "foo Foo FOO fOo";
+        let (out, count) = searcher.replace_all(text, 0..text.len(), "bar");
+        assert_eq!((out.as_str(), count), ("bar Bar BAR bar", 4));
+    }
+
+    #[test]
+    fn replace_all_ignoring_diacritics() {
+        let options = SearchOptions {
+            ignore_diacritics: true,
+            ..Default::default()
+        };
+        let searcher = Searcher::new("cafe", options).unwrap();
+        let text = "café cafe\u{301}s";
+        let (out, count) = searcher.replace_all(text, 0..text.len(), "tea");
+        assert_eq!((out.as_str(), count), ("tea teas", 2));
+    }
+
+    #[test]
+    fn remap_range_follows_edits() {
+        let old = "head é scope tail";
+        // Typing before, inside and after the scope.
+        assert_eq!(remap_range(old, "xx head é scope tail", 5..13), Some(8..16));
+        assert_eq!(remap_range(old, "head é sco-pe tail", 5..13), Some(5..14));
+        assert_eq!(remap_range(old, "head é scope tail!", 5..13), Some(5..13));
+        // Deleting before it, and multibyte text changing next to it.
+        assert_eq!(remap_range(old, "hd é scope tail", 5..13), Some(3..11));
+        assert_eq!(remap_range(old, "head è scope tail", 8..13), Some(8..13));
+        assert_eq!(remap_range(old, "head ü scope tail", 5..13), Some(5..13));
+        // An edit across either end drops the scope.
+        assert_eq!(remap_range(old, "head é scoXail", 5..13), None);
+        assert_eq!(remap_range(old, "heope tail", 5..13), None);
+        assert_eq!(remap_range(old, old, 5..13), Some(5..13));
+    }
+
+    #[test]
+    fn regex_replace_all_captures() {
+        let searcher = Searcher::new(r"(\w+)@(\w+)", regex()).unwrap();
+        let text = "é@x, a@b";
+        let (out, count) = searcher.replace_all(text, 0..text.len(), "$2.$1");
+        assert_eq!((out.as_str(), count), ("x.é, b.a", 2));
+    }
+}
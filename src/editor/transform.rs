@@ -0,0 +1,531 @@
+use super::{Caret, MultiCursor};
+use unicode_normalization::UnicodeNormalization;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextTransform {
+    Upper,
+    Lower,
+    Title,
+    Camel,
+    Snake,
+    Kebab,
+    TrimTrailing,
+    /// Expands tabs to the given tab width.
+    TabsToSpaces(usize),
+    /// Rewrites leading spaces as tabs of the given width.
+    SpacesToTabs(usize),
+    Nfc,
+    Nfd,
+    Base64Encode,
+    Base64Decode,
+    UrlEncode,
+    UrlDecode,
+    HtmlEncode,
+    HtmlDecode,
+    JsonEscape,
+    JsonUnescape,
+}
+
+impl TextTransform {
+    /// Transforms `text`, or explains why it cannot be decoded.
+    pub fn apply(self, text: &str) -> Result<String, String> {
+        Ok(match self {
+            Self::Upper => text.to_uppercase(),
+            Self::Lower => text.to_lowercase(),
+            Self::Title => title_case(text),
+            Self::Camel => map_lines(text, |l| join_words(l, "", true)),
+            Self::Snake => map_lines(text, |l| join_words(l, "_", false)),
+            Self::Kebab => map_lines(text, |l| join_words(l, "-", false)),
+            Self::TrimTrailing => map_lines(text, |l| l.trim_end().to_string()),
+            Self::TabsToSpaces(width) => map_lines(text, |l| expand_tabs(l, width)),
+            Self::SpacesToTabs(width) => map_lines(text, |l| tabify(l, width)),
+            Self::Nfc => text.nfc().collect(),
+            Self::Nfd => text.nfd().collect(),
+            Self::Base64Encode => base64_encode(text.as_bytes()),
+            Self::Base64Decode => base64_decode(text)?,
+            Self::UrlEncode => url_encode(text),
+            Self::UrlDecode => url_decode(text)?,
+            Self::HtmlEncode => html_encode(text),
+            Self::HtmlDecode => html_decode(text),
+            Self::JsonEscape => json_escape(text),
+            Self::JsonUnescape => json_unescape(text)?,
+        })
+    }
+}
+
+/// Transforms the text selected by each caret and selects the results, or the whole
+/// text when nothing is selected. Returns whether the text changed.
+pub fn transform_selections(
+    cursor: &mut MultiCursor,
+    text: &mut String,
+    transform: TextTransform,
+) -> Result<bool, String> {
+    if cursor.carets().iter().all(Caret::is_empty) {
+        let out = transform.apply(text)?;
+        if out == *text {
+            return Ok(false);
+        }
+        let len = out.chars().count();
+        let clamp = |c: Caret| Caret::new(c.anchor.min(len), c.head.min(len));
+        let carets = cursor.carets().iter().map(|&c| clamp(c)).collect();
+        *cursor = MultiCursor::new(carets, clamp(cursor.primary()));
+        *text = out;
+        return Ok(true);
+    }
+    let selected = cursor.selected_texts(text);
+    let pieces = selected
+        .iter()
+        .map(|s| transform.apply(s))
+        .collect::<Result<Vec<_>, _>>()?;
+    if pieces == selected {
+        return Ok(false);
+    }
+    let primary = cursor.carets().iter().position(|&c| c == cursor.primary());
+    cursor.insert_each(text, &pieces);
+    if cursor.carets().len() == pieces.len() {
+        let carets: Vec<Caret> = cursor
+            .carets()
+            .iter()
+            .zip(&pieces)
+            .map(|(c, p)| Caret::new(c.head - p.chars().count(), c.head))
+            .collect();
+        let primary = carets[primary.unwrap_or(0)];
+        *cursor = MultiCursor::new(carets, primary);
+    }
+    Ok(true)
+}
+
+/// Applies `f` to every line of `text`, keeping the line endings.
+fn map_lines(text: &str, f: impl Fn(&str) -> String) -> String {
+    let mut out = String::with_capacity(text.len());
+    for line in text.split_inclusive('\n') {
+        let content = line.strip_suffix('\n').unwrap_or(line);
+        let content = content.strip_suffix('\r').unwrap_or(content);
+        out.push_str(&f(content));
+        out.push_str(&line[content.len()..]);
+    }
+    out
+}
+
+fn title_case(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut in_word = false;
+    for c in text.chars() {
+        if c.is_alphanumeric() || (in_word && c == '\'') {
+            if in_word {
+                out.extend(c.to_lowercase());
+            } else {
+                out.extend(c.to_uppercase());
+            }
+            in_word = true;
+        } else {
+            out.push(c);
+            in_word = false;
+        }
+    }
+    out
+}
+
+/// Splits an identifier or phrase into lowercase words at separators and case changes,
+/// so "HTTPServer_name" gives "http", "server" and "name".
+fn words(text: &str) -> Vec<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut words = Vec::new();
+    let mut word = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if !c.is_alphanumeric() {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            continue;
+        }
+        let prev = i.checked_sub(1).map(|p| chars[p]);
+        let next = chars.get(i + 1);
+        let boundary = c.is_uppercase()
+            && prev.is_some_and(|p| {
+                p.is_lowercase()
+                    || p.is_numeric()
+                    || (p.is_uppercase() && next.is_some_and(|n| n.is_lowercase()))
+            });
+        if boundary && !word.is_empty() {
+            words.push(std::mem::take(&mut word));
+        }
+        word.extend(c.to_lowercase());
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+/// Rewrites the words of `line` joined by `separator`, keeping its surrounding
+/// whitespace. `camel` capitalizes every word but the first.
+fn join_words(line: &str, separator: &str, camel: bool) -> String {
+    let body = line.trim();
+    if body.is_empty() {
+        return line.to_string();
+    }
+    let start = line.len() - line.trim_start().len();
+    let joined = words(body)
+        .iter()
+        .enumerate()
+        .map(|(i, w)| {
+            let mut chars = w.chars();
+            match chars.next() {
+                Some(first) if camel && i > 0 => first.to_uppercase().chain(chars).collect(),
+                _ => w.clone(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(separator);
+    format!(
+        "{}{}{}",
+        &line[..start],
+        joined,
+        &line[start + body.len()..]
+    )
+}
+
+fn expand_tabs(line: &str, width: usize) -> String {
+    let mut out = String::with_capacity(line.len());
+    let mut column = 0;
+    for c in line.chars() {
+        if c == '\t' {
+            let n = width - column % width;
+            out.extend(std::iter::repeat_n(' ', n));
+            column += n;
+        } else {
+            out.push(c);
+            column += 1;
+        }
+    }
+    out
+}
+
+fn tabify(line: &str, width: usize) -> String {
+    let rest = line.trim_start_matches([' ', '\t']);
+    let columns = expand_tabs(&line[..line.len() - rest.len()], width).len();
+    format!(
+        "{}{}{}",
+        "\t".repeat(columns / width),
+        " ".repeat(columns % width),
+        rest
+    )
+}
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Decodes standard or URL-safe Base64, ignoring whitespace and missing padding.
+fn base64_decode(text: &str) -> Result<String, String> {
+    let mut bytes = Vec::with_capacity(text.len() * 3 / 4);
+    let (mut n, mut bits) = (0u32, 0);
+    for c in text
+        .trim_end_matches(|c: char| c == '=' || c.is_whitespace())
+        .chars()
+    {
+        let value = match c {
+            '-' => 62,
+            '_' => 63,
+            c if c.is_whitespace() => continue,
+            c => BASE64
+                .iter()
+                .position(|&b| b as char == c)
+                .ok_or("Invalid Base64")? as u32,
+        };
+        n = n << 6 | value;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((n >> bits) as u8);
+        }
+    }
+    String::from_utf8(bytes).map_err(|_| "Decoded Base64 is not UTF-8 text".to_string())
+}
+
+fn url_encode(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for b in text.bytes() {
+        if b.is_ascii_alphanumeric() || b"-_.~".contains(&b) {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{:02X}", b));
+        }
+    }
+    out
+}
+
+fn url_decode(text: &str) -> Result<String, String> {
+    let bytes = text.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let byte = text
+                .get(i + 1..i + 3)
+                .and_then(|h| u8::from_str_radix(h, 16).ok())
+                .ok_or("Invalid percent escape")?;
+            out.push(byte);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).map_err(|_| "Decoded URL is not UTF-8 text".to_string())
+}
+
+const ENTITIES: [(&str, char); 8] = [
+    ("amp", '&'),
+    ("lt", '<'),
+    ("gt", '>'),
+    ("quot", '"'),
+    ("apos", '\''),
+    ("nbsp", '\u{A0}'),
+    ("copy", '©'),
+    ("reg", '®'),
+];
+
+fn html_encode(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Decodes named and numeric character references. Unknown ones are left as they are.
+fn html_decode(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(i) = rest.find('&') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+        let decoded = rest[1..].find(';').and_then(|end| {
+            let name = &rest[1..end + 1];
+            let c = match name.strip_prefix('#') {
+                Some(n) => match n.strip_prefix(['x', 'X']) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => n.parse().ok(),
+                }
+                .and_then(char::from_u32),
+                None => ENTITIES.iter().find(|(e, _)| *e == name).map(|&(_, c)| c),
+            }?;
+            Some((c, end + 2))
+        });
+        match decoded {
+            Some((c, len)) => {
+                out.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Escapes `text` for use inside a JSON string literal, without the quotes.
+fn json_escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{8}' => out.push_str("\\b"),
+            '\u{c}' => out.push_str("\\f"),
+            c if c < ' ' => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn json_unescape(text: &str) -> Result<String, String> {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    let hex = |chars: &mut std::str::Chars| {
+        let digits: String = chars.by_ref().take(4).collect();
+        u32::from_str_radix(&digits, 16)
+            .ok()
+            .filter(|_| digits.len() == 4)
+            .ok_or_else(|| format!("Invalid escape \\u{}", digits))
+    };
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some('b') => out.push('\u{8}'),
+            Some('f') => out.push('\u{c}'),
+            Some(c @ ('"' | '\\' | '/')) => out.push(c),
+            Some('u') => {
+                let mut code = hex(&mut chars)?;
+                if (0xD800..0xDC00).contains(&code) {
+                    // A high surrogate must be followed by an escaped low surrogate.
+                    let low = match (chars.next(), chars.next()) {
+                        (Some('\\'), Some('u')) => hex(&mut chars)?,
+                        _ => 0,
+                    };
+                    if !(0xDC00..0xE000).contains(&low) {
+                        return Err("Unpaired surrogate escape".to_string());
+                    }
+                    code = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
+                }
+                out.push(char::from_u32(code).ok_or("Unpaired surrogate escape")?);
+            }
+            Some(c) => return Err(format!("Invalid escape \\{}", c)),
+            None => return Err("Trailing backslash".to_string()),
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use TextTransform::*;
+
+    fn apply(transform: TextTransform, text: &str) -> String {
+        transform.apply(text).unwrap_or_else(|e| e)
+    }
+
+    #[test]
+    fn case_transforms() {
+        assert_eq!(apply(Title, "o'neil é-bar"), "O'neil É-Bar");
+        assert_eq!(
+            apply(Camel, "HTTPServer_name\r\n  foo bar "),
+            "httpServerName\r\n  fooBar "
+        );
+        assert_eq!(apply(Snake, "fooBar2Baz"), "foo_bar2_baz");
+        assert_eq!(apply(Kebab, "XMLHttpRequest\n\n"), "xml-http-request\n\n");
+        assert_eq!(apply(Upper, "straße"), "STRASSE");
+    }
+
+    #[test]
+    fn whitespace_transforms_keep_line_endings() {
+        assert_eq!(apply(TrimTrailing, "a  \r\nb\t\n"), "a\r\nb\n");
+        assert_eq!(apply(TabsToSpaces(4), "a\tb\r\n\t"), "a   b\r\n    ");
+        assert_eq!(
+            apply(SpacesToTabs(4), "      x\r\n\t  \ty"),
+            "\t  x\r\n\t\ty"
+        );
+    }
+
+    #[test]
+    fn unicode_normalization() {
+        assert_eq!(apply(Nfc, "e\u{301}"), "é");
+        assert_eq!(apply(Nfd, "é"), "e\u{301}");
+    }
+
+    #[test]
+    fn base64_round_trip() {
+        assert_eq!(apply(Base64Encode, "ab"), "YWI=");
+        assert_eq!(
+            apply(Base64Decode, &apply(Base64Encode, "héllo 😀")),
+            "héllo 😀"
+        );
+        assert_eq!(apply(Base64Decode, "YW\nI"), "ab");
+        assert_eq!(apply(Base64Decode, "-_8="), apply(Base64Decode, "+/8="));
+        assert!(Base64Decode.apply("!").is_err());
+        assert!(Base64Decode.apply("/w==").is_err());
+    }
+
+    #[test]
+    fn url_round_trip() {
+        assert_eq!(apply(UrlEncode, "a b/é~"), "a%20b%2F%C3%A9~");
+        assert_eq!(apply(UrlDecode, "a%20b%2F%c3%a9~"), "a b/é~");
+        assert!(UrlDecode.apply("%zz").is_err());
+        assert!(UrlDecode.apply("%4").is_err());
+        assert!(UrlDecode.apply("%FF").is_err());
+    }
+
+    #[test]
+    fn html_round_trip() {
+        assert_eq!(
+            apply(HtmlEncode, "<a href=\"x\">&'"),
+            "&lt;a href=&quot;x&quot;&gt;&amp;&#39;"
+        );
+        assert_eq!(
+            apply(HtmlDecode, "&lt;&#233;&#xE9;&copy;&bogus; & x&#xD800;"),
+            "<éé©&bogus; & x&#xD800;"
+        );
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let text = "a\"\\\r\n\t\u{1}é";
+        assert_eq!(apply(JsonEscape, text), "a\\\"\\\\\\r\\n\\t\\u0001é");
+        assert_eq!(apply(JsonUnescape, &apply(JsonEscape, text)), text);
+        assert_eq!(apply(JsonUnescape, "\\ud83d\\ude00\\/"), "😀/");
+        for bad in ["\\ud83d", "\\ude00", "\\x", "\\", "\\u12"] {
+            assert!(JsonUnescape.apply(bad).is_err(), "{bad:?}");
+        }
+    }
+
+    #[test]
+    fn transform_selections_selects_results() {
+        let mut text = "ab cd é".to_string();
+        let carets = vec![Caret::new(0, 2), Caret::new(6, 7)];
+        let mut cursor = MultiCursor::new(carets, Caret::new(6, 7));
+        assert_eq!(
+            transform_selections(&mut cursor, &mut text, Upper),
+            Ok(true)
+        );
+        assert_eq!(text, "AB cd É");
+        assert_eq!(cursor.carets(), [Caret::new(0, 2), Caret::new(6, 7)]);
+        assert_eq!(cursor.primary(), Caret::new(6, 7));
+    }
+
+    #[test]
+    fn transform_selections_whole_text() {
+        let mut text = "YWI=".to_string();
+        let mut cursor = MultiCursor::new(vec![Caret::at(4)], Caret::at(4));
+        assert_eq!(
+            transform_selections(&mut cursor, &mut text, Base64Decode),
+            Ok(true)
+        );
+        assert_eq!((text.as_str(), cursor.primary()), ("ab", Caret::at(2)));
+        assert_eq!(
+            transform_selections(&mut cursor, &mut text, Lower),
+            Ok(false)
+        );
+        let mut bad = "%".to_string();
+        assert!(transform_selections(&mut cursor, &mut bad, UrlDecode).is_err());
+        assert_eq!(bad, "%");
+    }
+}
@@ -0,0 +1,156 @@
+use crate::config::{HISTORY_MAX_AGE_DAYS, HISTORY_MAX_ENTRIES};
+use chrono::{DateTime, Local};
+use std::io;
+use std::path::{Path, PathBuf};
+
+const SOURCE_FILE: &str = "source";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SnapshotKind {
+    Save,
+    Auto,
+}
+
+impl SnapshotKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SnapshotKind::Save => "Saved",
+            SnapshotKind::Auto => "Auto",
+        }
+    }
+
+    fn tag(&self) -> &'static str {
+        match self {
+            SnapshotKind::Save => "save",
+            SnapshotKind::Auto => "auto",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    pub time: DateTime<Local>,
+    pub kind: SnapshotKind,
+    pub size: u64,
+    path: PathBuf,
+}
+
+/// On-disk store of file snapshots, one directory per absolute file path.
+pub struct LocalHistory {
+    root: PathBuf,
+}
+
+impl LocalHistory {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    pub fn open_default() -> Option<Self> {
+        eframe::storage_dir("MemoChan").map(|dir| Self::new(dir.join("history")))
+    }
+
+    fn dir_for(&self, file: &Path) -> PathBuf {
+        let abs = std::fs::canonicalize(file).unwrap_or_else(|_| file.to_path_buf());
+        let key = fnv1a(abs.to_string_lossy().as_bytes());
+        self.root.join(format!("{:016x}", key))
+    }
+
+    /// Stores `content` unless it matches the latest snapshot. Returns whether one was written.
+    pub fn record(&self, file: &Path, content: &str, kind: SnapshotKind) -> io::Result<bool> {
+        if let Some(latest) = self.list(file).first() {
+            if self.read(latest).ok().as_deref() == Some(content) {
+                return Ok(false);
+            }
+        }
+        let dir = self.dir_for(file);
+        std::fs::create_dir_all(&dir)?;
+        let source = std::fs::canonicalize(file).unwrap_or_else(|_| file.to_path_buf());
+        std::fs::write(dir.join(SOURCE_FILE), source.to_string_lossy().as_bytes())?;
+        let name = format!("{}-{}.txt", Local::now().timestamp_millis(), kind.tag());
+        std::fs::write(dir.join(name), content)?;
+        self.prune(file);
+        Ok(true)
+    }
+
+    /// Lists snapshots of `file`, newest first.
+    pub fn list(&self, file: &Path) -> Vec<Snapshot> {
+        let Ok(entries) = std::fs::read_dir(self.dir_for(file)) else {
+            return Vec::new();
+        };
+        let mut snapshots: Vec<Snapshot> = entries
+            .filter_map(|e| e.ok())
+            .filter_map(|e| {
+                let name = e.file_name().to_str()?.strip_suffix(".txt")?.to_string();
+                let (millis, tag) = name.split_once('-')?;
+                let kind = match tag {
+                    "save" => SnapshotKind::Save,
+                    "auto" => SnapshotKind::Auto,
+                    _ => return None,
+                };
+                let time = DateTime::from_timestamp_millis(millis.parse().ok()?)?;
+                Some(Snapshot {
+                    time: time.with_timezone(&Local),
+                    kind,
+                    size: e.metadata().map(|m| m.len()).unwrap_or(0),
+                    path: e.path(),
+                })
+            })
+            .collect();
+        snapshots.sort_by_key(|s| std::cmp::Reverse(s.time));
+        snapshots
+    }
+
+    pub fn read(&self, snapshot: &Snapshot) -> io::Result<String> {
+        std::fs::read_to_string(&snapshot.path)
+    }
+
+    fn prune(&self, file: &Path) {
+        let cutoff = Local::now() - chrono::Duration::days(HISTORY_MAX_AGE_DAYS);
+        for (i, snapshot) in self.list(file).iter().enumerate() {
+            if i >= HISTORY_MAX_ENTRIES || snapshot.time < cutoff {
+                let _ = std::fs::remove_file(&snapshot.path);
+            }
+        }
+    }
+}
+
+/// Stable 64-bit FNV-1a hash, so directory names survive toolchain upgrades.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for b in bytes {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fnv1a_known_values() {
+        assert_eq!(fnv1a(b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+    }
+
+    #[test]
+    fn record_skips_unchanged_content() {
+        let root = std::env::temp_dir().join(format!("memochan-history-{}", std::process::id()));
+        let history = LocalHistory::new(root.clone());
+        let file = root.join("notes é.txt");
+
+        assert!(history.record(&file, "a\r\n", SnapshotKind::Save).unwrap());
+        assert!(!history.record(&file, "a\r\n", SnapshotKind::Auto).unwrap());
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        assert!(history.record(&file, "b", SnapshotKind::Auto).unwrap());
+
+        let snapshots = history.list(&file);
+        let kinds: Vec<SnapshotKind> = snapshots.iter().map(|s| s.kind).collect();
+        assert_eq!(kinds, [SnapshotKind::Auto, SnapshotKind::Save]);
+        assert_eq!(history.read(&snapshots[0]).unwrap(), "b");
+        assert_eq!(history.read(&snapshots[1]).unwrap(), "a\r\n");
+        assert!(history.list(&root.join("other.txt")).is_empty());
+        let _ = std::fs::remove_dir_all(root);
+    }
+}
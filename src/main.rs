@@ -6,7 +6,9 @@
 mod app;
 mod config;
 mod editor;
+mod history;
 mod theme;
+mod vcs;
 
 use crate::app::Notepad;
 use crate::config::{PRETENDARD_FONT, PRETENDARD_FONT_NAME};
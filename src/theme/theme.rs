@@ -46,9 +46,19 @@ pub struct ThemeColors {
     pub line_number_bg: Color32,
     pub gutter_bg: Color32,
     pub current_line_bg: Color32,
+    pub diff_added_bg: Color32,
+    pub diff_added_emph: Color32,
+    pub diff_removed_bg: Color32,
+    pub diff_removed_emph: Color32,
+    pub git_added: Color32,
+    pub git_modified: Color32,
+    pub git_deleted: Color32,
+    pub search_match_bg: Color32,
+    pub search_current_bg: Color32,
+    pub search_flash_bg: Color32,
+    pub bracket_match_bg: Color32,
 }
 
-#[allow(dead_code)]
 impl ThemeColors {
     pub fn dark() -> Self {
         Self {
@@ -61,6 +71,17 @@ impl ThemeColors {
             line_number_bg: Color32::from_rgb(0x1e, 0x1e, 0x1e),
             gutter_bg: Color32::from_rgb(0x25, 0x25, 0x26),
             current_line_bg: Color32::from_rgba_unmultiplied(0xff, 0xff, 0xff, 0x08),
+            diff_added_bg: Color32::from_rgb(0x1e, 0x3a, 0x24),
+            diff_added_emph: Color32::from_rgb(0x2e, 0x6b, 0x3a),
+            diff_removed_bg: Color32::from_rgb(0x42, 0x20, 0x22),
+            diff_removed_emph: Color32::from_rgb(0x7a, 0x2e, 0x33),
+            git_added: Color32::from_rgb(0x58, 0x7c, 0x0c),
+            git_modified: Color32::from_rgb(0x0c, 0x7d, 0x9d),
+            git_deleted: Color32::from_rgb(0x94, 0x15, 0x1b),
+            search_match_bg: Color32::from_rgba_unmultiplied(0xea, 0x5c, 0x00, 0x55),
+            search_current_bg: Color32::from_rgb(0x9e, 0x6a, 0x03),
+            search_flash_bg: Color32::from_rgb(0xff, 0xc8, 0x00),
+            bracket_match_bg: Color32::from_rgba_unmultiplied(0x88, 0x88, 0x88, 0x50),
         }
     }
 
@@ -75,6 +96,17 @@ impl ThemeColors {
             line_number_bg: Color32::from_rgb(0xff, 0xff, 0xff),
             gutter_bg: Color32::from_rgb(0xf5, 0xf5, 0xf5),
             current_line_bg: Color32::from_rgba_unmultiplied(0x00, 0x00, 0x00, 0x08),
+            diff_added_bg: Color32::from_rgb(0xe6, 0xff, 0xec),
+            diff_added_emph: Color32::from_rgb(0xac, 0xf2, 0xbd),
+            diff_removed_bg: Color32::from_rgb(0xff, 0xeb, 0xe9),
+            diff_removed_emph: Color32::from_rgb(0xfd, 0xb8, 0xc0),
+            git_added: Color32::from_rgb(0x48, 0x98, 0x5d),
+            git_modified: Color32::from_rgb(0x20, 0x90, 0xd3),
+            git_deleted: Color32::from_rgb(0xe5, 0x1d, 0x1d),
+            search_match_bg: Color32::from_rgba_unmultiplied(0xea, 0x5c, 0x00, 0x40),
+            search_current_bg: Color32::from_rgb(0xf8, 0xc9, 0x4f),
+            search_flash_bg: Color32::from_rgb(0xff, 0x96, 0x32),
+            bracket_match_bg: Color32::from_rgba_unmultiplied(0x90, 0x90, 0x90, 0x50),
         }
     }
 
@@ -0,0 +1,244 @@
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+const UNCOMMITTED: &str = "0000000000000000000000000000000000000000";
+
+#[derive(Clone, Debug)]
+pub struct BlameLine {
+    pub commit: String,
+    pub author: String,
+    pub time: i64,
+    pub summary: String,
+}
+
+impl BlameLine {
+    pub fn is_uncommitted(&self) -> bool {
+        self.commit == UNCOMMITTED
+    }
+
+    pub fn short_commit(&self) -> &str {
+        &self.commit[..self.commit.len().min(7)]
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct FileCommit {
+    pub hash: String,
+    pub short: String,
+    pub author: String,
+    pub date: String,
+    pub subject: String,
+    /// Path of the file at this commit, relative to the repository root.
+    pub path: String,
+}
+
+fn command(dir: &Path, args: &[&str]) -> Command {
+    let mut cmd = Command::new("git");
+    cmd.arg("-C").arg(dir).args(args);
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+        cmd.creation_flags(CREATE_NO_WINDOW);
+    }
+    cmd
+}
+
+/// Runs `git -C <dir> <args>` and returns stdout when the command succeeds.
+pub fn git(dir: &Path, args: &[&str]) -> Option<String> {
+    let output = command(dir, args).output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Like [`git`], feeding `input` to the command's stdin.
+pub fn git_with_input(dir: &Path, args: &[&str], input: &str) -> Option<String> {
+    let mut child = command(dir, args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+    let mut stdin = child.stdin.take()?;
+    let input = input.to_string();
+    let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+    let output = child.wait_with_output().ok()?;
+    writer.join().ok()?.ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Returns the content of `file` at HEAD as it would be checked out, with line ending
+/// conversion and other filters applied, or `None` if it is not tracked in a repository.
+pub fn head_content(file: &Path) -> Option<String> {
+    let dir = file.parent()?;
+    let name = file.file_name()?.to_str()?;
+    git(dir, &["cat-file", "--filters", &format!("HEAD:./{}", name)])
+}
+
+/// Blames `contents` as the current version of `file`, one entry per line.
+pub fn blame(file: &Path, contents: &str) -> Option<Vec<BlameLine>> {
+    let dir = file.parent()?;
+    let name = file.file_name()?.to_str()?;
+    let out = git_with_input(
+        dir,
+        &["blame", "--porcelain", "--contents", "-", "--", name],
+        contents,
+    )?;
+    Some(parse_blame(&out))
+}
+
+/// Reads `git blame --porcelain` output, where commit details follow only the first
+/// line blamed on each commit.
+fn parse_blame(out: &str) -> Vec<BlameLine> {
+    let mut commits: HashMap<String, BlameLine> = HashMap::new();
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    for line in out.lines() {
+        if line.starts_with('\t') {
+            if let Some(info) = commits.get(&current) {
+                lines.push(info.clone());
+            }
+            continue;
+        }
+        let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+        if key.len() == 40 && key.bytes().all(|b| b.is_ascii_hexdigit()) {
+            current = key.to_string();
+            commits.entry(current.clone()).or_insert_with(|| BlameLine {
+                commit: current.clone(),
+                author: String::new(),
+                time: 0,
+                summary: String::new(),
+            });
+            continue;
+        }
+        if let Some(info) = commits.get_mut(&current) {
+            match key {
+                "author" => info.author = value.to_string(),
+                "author-time" => info.time = value.parse().unwrap_or(0),
+                "summary" => info.summary = value.to_string(),
+                _ => {}
+            }
+        }
+    }
+    lines
+}
+
+/// Lists the commits touching `file`, newest first, following renames.
+pub fn file_log(file: &Path) -> Vec<FileCommit> {
+    let (Some(dir), Some(name)) = (file.parent(), file.file_name().and_then(|n| n.to_str())) else {
+        return Vec::new();
+    };
+    let Some(out) = git(
+        dir,
+        &[
+            "log",
+            "--follow",
+            "--name-only",
+            "--date=short",
+            "--format=%x1e%H%x1f%h%x1f%an%x1f%ad%x1f%s",
+            "--",
+            name,
+        ],
+    ) else {
+        return Vec::new();
+    };
+
+    out.split('\u{1e}')
+        .filter_map(|record| {
+            let (header, names) = record.split_once('\n')?;
+            let mut fields = header.split('\u{1f}');
+            Some(FileCommit {
+                hash: fields.next()?.to_string(),
+                short: fields.next()?.to_string(),
+                author: fields.next()?.to_string(),
+                date: fields.next()?.to_string(),
+                subject: fields.next().unwrap_or_default().to_string(),
+                path: names.lines().find(|l| !l.is_empty())?.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Returns the content of the file as of `commit`.
+pub fn show_revision(file: &Path, commit: &FileCommit) -> Option<String> {
+    git(
+        file.parent()?,
+        &["show", &format!("{}:{}", commit.hash, commit.path)],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_blame_porcelain() {
+        let a = "a".repeat(40);
+        let out = format!(
+            "{a} 1 1 2\nauthor Zoë\nauthor-time 1700000000\nsummary Fix: é\nfilename x\n\tone\n\
+             {UNCOMMITTED} 2 2 1\nauthor Not Committed Yet\nsummary Version of x\n\ttwo\r\n\
+             {a} 2 3\n\tthree\n"
+        );
+        let lines = parse_blame(&out);
+        let summary: Vec<(&str, &str, i64, bool)> = lines
+            .iter()
+            .map(|l| {
+                (
+                    l.short_commit(),
+                    l.author.as_str(),
+                    l.time,
+                    l.is_uncommitted(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("aaaaaaa", "Zoë", 1700000000, false),
+                ("0000000", "Not Committed Yet", 0, true),
+                ("aaaaaaa", "Zoë", 1700000000, false),
+            ]
+        );
+        assert_eq!(lines[0].summary, "Fix: é");
+    }
+
+    #[test]
+    fn head_content_converts_line_endings() {
+        let dir = std::env::temp_dir().join(format!("memochan-git-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(".gitattributes"), "*.txt text eol=crlf\n").unwrap();
+        std::fs::write(dir.join("a é.txt"), "one\ntwo\n").unwrap();
+        let setup = [
+            &["init", "-q"][..],
+            &["add", "."],
+            &[
+                "-c",
+                "user.name=t",
+                "-c",
+                "user.email=t@t",
+                "commit",
+                "-qm",
+                "x",
+            ],
+        ];
+        for args in setup {
+            if git(&dir, args).is_none() {
+                return;
+            }
+        }
+        assert_eq!(
+            head_content(&dir.join("a é.txt")).as_deref(),
+            Some("one\r\ntwo\r\n")
+        );
+        assert_eq!(head_content(&dir.join("untracked.txt")), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,3 @@
+mod git;
+
+pub use git::*;